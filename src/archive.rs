@@ -0,0 +1,313 @@
+use std::io::{Read, Write};
+
+use crate::{
+    decode_to_vec, encode,
+    error::Error,
+    format::{read_varint_from, write_varint},
+    is_canonical_code,
+};
+
+/// Container for many Lehmer codes of possibly different lengths, with random access to each code.
+///
+/// Layout:
+/// - `MAGIC`, 4 bytes
+/// - `VERSION`, 1 byte
+/// - code count as LEB128 varint
+/// - table with the element count and the byte length of every code, both as LEB128 varint
+/// - the codes of `big_lehmer::encode`, concatenated
+///
+/// The codes are stored without high zero bytes, so near identity permutations take only a few bytes.
+/// The table gives the position of every code, `LehmerArchive::get` decodes one code without touching the others.
+///
+/// # Examples
+/// ```
+/// let mut archive = big_lehmer::LehmerArchive::new();
+/// archive.push(&[2, 0, 1]).unwrap();
+/// archive.push(&(0..1000).collect::<Vec<u32>>()).unwrap();
+///
+/// let mut stored = vec![];
+/// archive.write(&mut stored).unwrap();
+/// let read = big_lehmer::LehmerArchive::read(&stored[..]).unwrap();
+/// assert_eq!(read.len(), 2);
+/// assert_eq!(read.get(0).unwrap(), [2, 0, 1]);
+/// assert_eq!(read.element_count(1), Some(1000));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LehmerArchive {
+    element_counts: Vec<u32>,
+    /// Start of every code in `codes`, one more than there are codes
+    offsets: Vec<usize>,
+    codes: Vec<u8>,
+}
+
+impl LehmerArchive {
+    pub const MAGIC: [u8; 4] = *b"LHMA";
+    pub const VERSION: u8 = 1;
+
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            element_counts: vec![],
+            offsets: vec![0],
+            codes: vec![],
+        }
+    }
+
+    /// Number of codes
+    #[must_use]
+    pub fn len(&self) -> usize {
+        self.element_counts.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.element_counts.is_empty()
+    }
+
+    /// Encodes `numbers` and appends it as the next code
+    ///
+    /// # Errors
+    ///
+    /// Same as `big_lehmer::encode`
+    pub fn push(&mut self, numbers: &[u32]) -> Result<(), Error> {
+        let code = encode(numbers)?;
+        // encode already verified the length fits into u32
+        self.append(&code, u32::try_from(numbers.len()).unwrap());
+        Ok(())
+    }
+
+    /// Appends an existing code of `element_count` elements
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::Decode` when `code` is not the canonical code of a permutation of `element_count` elements,
+    /// see `big_lehmer::is_canonical_code`.
+    pub fn push_code(&mut self, code: &[u8], element_count: u32) -> Result<(), Error> {
+        if !is_canonical_code(code, element_count) {
+            return Err(Error::Decode);
+        }
+        self.append(code, element_count);
+        Ok(())
+    }
+
+    fn append(&mut self, code: &[u8], element_count: u32) {
+        self.codes.extend_from_slice(code);
+        self.element_counts.push(element_count);
+        self.offsets.push(self.codes.len());
+    }
+
+    /// Element count of the code at `index`
+    #[must_use]
+    pub fn element_count(&self, index: usize) -> Option<u32> {
+        self.element_counts.get(index).copied()
+    }
+
+    /// The code at `index`, as produced by `big_lehmer::encode`
+    #[must_use]
+    pub fn code(&self, index: usize) -> Option<&[u8]> {
+        let (&start, &end) = (self.offsets.get(index)?, self.offsets.get(index + 1)?);
+        Some(&self.codes[start..end])
+    }
+
+    /// Decodes the permutation at `index`
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::IndexOutOfRange` when `index` is not smaller than `len`.
+    /// Same as `big_lehmer::decode`, a read archive can contain invalid codes.
+    pub fn get(&self, index: usize) -> Result<Vec<u32>, Error> {
+        let (Some(code), Some(element_count)) = (self.code(index), self.element_count(index))
+        else {
+            return Err(Error::IndexOutOfRange {
+                index,
+                len: self.len(),
+            });
+        };
+        decode_to_vec(code, element_count)
+    }
+
+    /// Writes the archive in the layout above
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` when writing fails.
+    pub fn write<W: Write>(&self, mut writer: W) -> Result<(), Error> {
+        let mut header = Self::MAGIC.to_vec();
+        header.push(Self::VERSION);
+        write_varint(&mut header, self.len() as u64);
+        for (index, &element_count) in self.element_counts.iter().enumerate() {
+            write_varint(&mut header, u64::from(element_count));
+            write_varint(
+                &mut header,
+                (self.offsets[index + 1] - self.offsets[index]) as u64,
+            );
+        }
+        writer.write_all(&header)?;
+        writer.write_all(&self.codes)?;
+        Ok(())
+    }
+
+    /// Reads an archive written by `LehmerArchive::write`.
+    /// The codes are not decoded, invalid codes are only reported by `LehmerArchive::get`.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidHeader` when the magic bytes, the count or the table are invalid.
+    /// `Error::UnsupportedVersion` when the archive was written by an unknown format version.
+    /// `Error::Io` when reading fails or the reader ends within the archive.
+    pub fn read<R: Read>(mut reader: R) -> Result<Self, Error> {
+        let mut magic = [0; 4];
+        reader.read_exact(&mut magic)?;
+        if magic != Self::MAGIC {
+            return Err(Error::InvalidHeader);
+        }
+        let mut version = [0];
+        reader.read_exact(&mut version)?;
+        if version[0] != Self::VERSION {
+            return Err(Error::UnsupportedVersion {
+                version: version[0],
+            });
+        }
+        let mut next_varint = || -> Result<u64, Error> {
+            read_varint_from(&mut reader)?
+                .ok_or_else(|| std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into())
+        };
+        let count = usize::try_from(next_varint()?).map_err(|_| Error::InvalidHeader)?;
+        // Grows with the entries read, a corrupted count can not allocate more than the table contains
+        let mut archive = Self::new();
+        for _ in 0..count {
+            let element_count = u32::try_from(next_varint()?).map_err(|_| Error::InvalidHeader)?;
+            let code_len = usize::try_from(next_varint()?).map_err(|_| Error::InvalidHeader)?;
+            let end = archive.offsets[archive.len()]
+                .checked_add(code_len)
+                .ok_or(Error::InvalidHeader)?;
+            archive.element_counts.push(element_count);
+            archive.offsets.push(end);
+        }
+        let codes_len = archive.offsets[archive.len()] as u64;
+        // Reads through take, so a corrupted table can not allocate more than the reader contains
+        reader.take(codes_len).read_to_end(&mut archive.codes)?;
+        if archive.codes.len() as u64 != codes_len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(archive)
+    }
+}
+
+impl Default for LehmerArchive {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample() -> (LehmerArchive, Vec<Vec<u32>>) {
+        let perms: Vec<Vec<u32>> = vec![
+            vec![],
+            vec![0],
+            vec![7, 2, 0, 6, 5, 1, 4, 3],
+            (0..5000).rev().collect(),
+            (0..300).collect(),
+            crate::tests::shuffled(200, 920),
+        ];
+        let mut archive = LehmerArchive::new();
+        for perm in &perms {
+            archive.push(perm).unwrap();
+        }
+        (archive, perms)
+    }
+
+    #[test]
+    fn test_archive_roundtrip() {
+        let (archive, perms) = sample();
+        let mut stored = vec![];
+        archive.write(&mut stored).unwrap();
+        let read = LehmerArchive::read(&stored[..]).unwrap();
+        assert_eq!(read, archive);
+        assert_eq!(read.len(), perms.len());
+        // Random access, back to front
+        for (index, perm) in perms.iter().enumerate().rev() {
+            assert_eq!(read.get(index).unwrap(), *perm);
+            assert_eq!(read.code(index).unwrap(), &*encode(perm).unwrap());
+        }
+        assert_eq!(
+            read.get(perms.len()),
+            Err(Error::IndexOutOfRange { index: 6, len: 6 })
+        );
+        assert_eq!(read.code(perms.len()), None);
+
+        // The identity of 300 elements is stored as 0 bytes
+        assert_eq!(read.code(4).unwrap(), []);
+
+        let mut empty = vec![];
+        LehmerArchive::new().write(&mut empty).unwrap();
+        assert_eq!(empty, [b'L', b'H', b'M', b'A', 1, 0]);
+        assert!(LehmerArchive::read(&empty[..]).unwrap().is_empty());
+
+        let mut pushed = LehmerArchive::new();
+        pushed.push_code(&[3], 3).unwrap();
+        assert_eq!(pushed.get(0).unwrap(), [1, 2, 0]);
+        assert_eq!(pushed.push_code(&[6], 3), Err(Error::Decode));
+        assert_eq!(pushed.push_code(&[3, 0], 3), Err(Error::Decode));
+        assert_eq!(pushed.len(), 1);
+        // Short codes of huge permutations skip the factorial
+        pushed.push_code(&[6], u32::MAX).unwrap();
+        assert_eq!(pushed.element_count(1), Some(u32::MAX));
+        assert_eq!(
+            pushed.get(7).unwrap_err().to_string(),
+            "Index 7 is out of range, there are only 2 entries"
+        );
+    }
+
+    #[test]
+    fn test_archive_invalid() {
+        let (archive, _) = sample();
+        let mut stored = vec![];
+        archive.write(&mut stored).unwrap();
+
+        let mut version = stored.clone();
+        version[4] = 2;
+        assert_eq!(
+            LehmerArchive::read(&version[..]),
+            Err(Error::UnsupportedVersion { version: 2 })
+        );
+        let mut magic = stored.clone();
+        magic[0] = b'X';
+        assert_eq!(LehmerArchive::read(&magic[..]), Err(Error::InvalidHeader));
+
+        // Truncated anywhere, including within the header and the table
+        for len in [0, 3, 5, 8, stored.len() - 1] {
+            let error = LehmerArchive::read(&stored[..len]).unwrap_err();
+            assert!(
+                matches!(
+                    error,
+                    Error::Io {
+                        kind: std::io::ErrorKind::UnexpectedEof,
+                        ..
+                    }
+                ),
+                "{len}: {error:?}"
+            );
+        }
+
+        // A huge count or code length only fails at the end of the reader
+        let mut huge = LehmerArchive::MAGIC.to_vec();
+        huge.push(LehmerArchive::VERSION);
+        write_varint(&mut huge, u64::MAX >> 1);
+        assert!(LehmerArchive::read(&huge[..]).is_err());
+        let mut huge = LehmerArchive::MAGIC.to_vec();
+        huge.extend([LehmerArchive::VERSION, 1, 3]);
+        write_varint(&mut huge, u64::MAX >> 1);
+        assert!(LehmerArchive::read(&huge[..]).is_err());
+
+        // An invalid code is read, but fails on access
+        let mut invalid = LehmerArchive::MAGIC.to_vec();
+        invalid.extend([LehmerArchive::VERSION, 2, 3, 1, 3, 1, 1, 6]);
+        let read = LehmerArchive::read(&invalid[..]).unwrap();
+        assert_eq!(read.get(0).unwrap(), [0, 2, 1]);
+        assert!(read.get(1).is_err());
+    }
+}
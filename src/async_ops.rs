@@ -0,0 +1,67 @@
+use tokio::task::{spawn_blocking, JoinError};
+
+use crate::{decode, encode, error::Error};
+
+fn propagate_panic(error: JoinError) -> ! {
+    std::panic::resume_unwind(error.into_panic())
+}
+
+/// Async adapter of `big_lehmer::encode` that runs it via `tokio::task::spawn_blocking`.  
+/// Only worth it for large inputs, where encode would otherwise block the async runtime for a noticeable time.
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+///
+/// # Panics
+///
+/// Propagates panics of the blocking task. Panics when the runtime shuts down before the task completes.
+pub async fn encode_async(numbers: Vec<u32>) -> Result<Box<[u8]>, Error> {
+    spawn_blocking(move || encode(&numbers))
+        .await
+        .unwrap_or_else(|e| propagate_panic(e))
+}
+
+/// Async adapter of `big_lehmer::decode` that runs it via `tokio::task::spawn_blocking`.  
+/// Only worth it for large inputs, where decode would otherwise block the async runtime for a noticeable time.
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+///
+/// # Panics
+///
+/// Propagates panics of the blocking task. Panics when the runtime shuts down before the task completes.
+pub async fn decode_async(encoded: Vec<u8>, element_count: u32) -> Result<Vec<u32>, Error> {
+    spawn_blocking(move || {
+        let mut results = vec![0; element_count as usize];
+        decode(&encoded, &mut results).map(|()| results)
+    })
+    .await
+    .unwrap_or_else(|e| propagate_panic(e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_async() {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap();
+        let sequence: Vec<u32> = (0..5000).rev().collect();
+
+        let roundtrip = runtime.block_on(async {
+            let encoded = encode_async(sequence.clone()).await.unwrap();
+            decode_async(encoded.into_vec(), 5000).await.unwrap()
+        });
+        assert_eq!(sequence, roundtrip);
+
+        let invalid = runtime.block_on(encode_async(vec![0, 0]));
+        assert!(matches!(
+            invalid,
+            Err(Error::ValidationDuplicateNumber { .. })
+        ));
+    }
+}
@@ -0,0 +1,268 @@
+//! Pluggable big number backend for `big_lehmer::encode_with_backend` and `big_lehmer::decode_with_backend`.
+
+use std::num::NonZeroU32;
+
+use dashu::{
+    base::{BitTest, DivRem},
+    integer::UBig,
+};
+
+use crate::{
+    decode::{check_element_count, divide, remainders_to_permutation, DecodeConfig, WorkItem},
+    encode::{Cache, Encoder},
+    error::Error,
+    ranking::OrderStatisticTree,
+};
+
+/// The big number operations encode and decode need.  
+/// Implemented for `dashu::integer::UBig` and, with the `num-bigint` feature, for `num_bigint::BigUint`.
+///
+/// Only `big_lehmer::encode_with_backend` and `big_lehmer::decode_with_backend` are generic over the backend,
+/// the rest of the crate always uses `dashu`.
+pub trait BigInt: Sized {
+    fn from_u64(value: u64) -> Self;
+    fn from_le_bytes(bytes: &[u8]) -> Self;
+    /// Little endian bytes without high zero bytes, zero has no bytes
+    fn to_le_bytes(&self) -> Box<[u8]>;
+    fn mul(&self, rhs: &Self) -> Self;
+    fn add(&self, rhs: &Self) -> Self;
+    /// Quotient and remainder
+    fn div_rem(self, divisor: &Self) -> (Self, Self);
+    fn bit_length(&self) -> usize;
+    /// None when the number does not fit into u64
+    fn to_u64(&self) -> Option<u64>;
+}
+
+impl BigInt for UBig {
+    fn from_u64(value: u64) -> Self {
+        value.into()
+    }
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        UBig::from_le_bytes(bytes)
+    }
+
+    fn to_le_bytes(&self) -> Box<[u8]> {
+        UBig::to_le_bytes(self)
+    }
+
+    fn mul(&self, rhs: &Self) -> Self {
+        self * rhs
+    }
+
+    fn add(&self, rhs: &Self) -> Self {
+        self + rhs
+    }
+
+    fn div_rem(self, divisor: &Self) -> (Self, Self) {
+        DivRem::div_rem(self, divisor)
+    }
+
+    fn bit_length(&self) -> usize {
+        BitTest::bit_len(self)
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        u64::try_from(self).ok()
+    }
+}
+
+#[cfg(feature = "num-bigint")]
+impl BigInt for num_bigint::BigUint {
+    fn from_u64(value: u64) -> Self {
+        value.into()
+    }
+
+    fn from_le_bytes(bytes: &[u8]) -> Self {
+        num_bigint::BigUint::from_bytes_le(bytes)
+    }
+
+    fn to_le_bytes(&self) -> Box<[u8]> {
+        if self.bits() == 0 {
+            return Box::new([]);
+        }
+        self.to_bytes_le().into_boxed_slice()
+    }
+
+    fn mul(&self, rhs: &Self) -> Self {
+        self * rhs
+    }
+
+    fn add(&self, rhs: &Self) -> Self {
+        self + rhs
+    }
+
+    fn div_rem(self, divisor: &Self) -> (Self, Self) {
+        (&self / divisor, self % divisor)
+    }
+
+    fn bit_length(&self) -> usize {
+        usize::try_from(self.bits()).unwrap_or(usize::MAX)
+    }
+
+    fn to_u64(&self) -> Option<u64> {
+        u64::try_from(self).ok()
+    }
+}
+
+/// Same as `big_lehmer::encode`, but does the big number math with the backend `B`.  
+/// Always takes the serial path.
+///
+/// # Examples
+/// ```
+/// use dashu::integer::UBig;
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode_with_backend::<UBig>(&sequence).unwrap();
+/// assert_eq!(encoded, big_lehmer::encode(&sequence).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_with_backend<B: BigInt>(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
+    let mut encoder = Encoder::new();
+    encoder.fill_caches::<true>(numbers, 0, None)?;
+    Ok(reduce_caches::<B>(&encoder.caches).0.to_le_bytes())
+}
+
+/// Same as `big_lehmer::decode`, but does the big number math with the backend `B`.  
+/// Always takes the serial path.
+///
+/// # Examples
+/// ```
+/// use dashu::integer::UBig;
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode(&sequence).unwrap();
+/// let mut roundtrip = [0; 8];
+/// big_lehmer::decode_with_backend::<UBig>(&encoded, &mut roundtrip).unwrap();
+/// assert_eq!(sequence, roundtrip);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_with_backend<B: BigInt>(encoded: &[u8], results: &mut [u32]) -> Result<(), Error> {
+    let input = B::from_le_bytes(encoded);
+    let code_bits = input.bit_length();
+    // supports up to u32::MAX elements
+    let element_count = u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+        element_count: results.len(),
+    })?;
+
+    let mut remainders = vec![None; results.len()];
+    if !remainders.is_empty() {
+        split_divide(input, &mut remainders, 2);
+    }
+    check_element_count(&remainders, code_bits)?;
+
+    let mut tree = OrderStatisticTree::new(element_count)?;
+    remainders_to_permutation(&remainders, results, &mut tree)
+}
+
+/// Pairwise serial reduce of the caches, returns add and mul
+fn reduce_caches<B: BigInt>(caches: &[Cache]) -> (B, B) {
+    let mut level: Vec<(B, B)> = caches
+        .iter()
+        .map(|cache| {
+            (
+                B::from_le_bytes(&cache.add.to_le_bytes()),
+                B::from_le_bytes(&cache.mul.to_le_bytes()),
+            )
+        })
+        .collect();
+    while level.len() > 1 {
+        let mut next = Vec::with_capacity(level.len().div_ceil(2));
+        let mut iter = level.into_iter();
+        while let Some(left) = iter.next() {
+            next.push(match iter.next() {
+                Some(right) => (left.0.mul(&right.1).add(&right.0), left.1.mul(&right.1)),
+                None => left,
+            });
+        }
+        level = next;
+    }
+    level
+        .pop()
+        .unwrap_or_else(|| (B::from_u64(0), B::from_u64(1)))
+}
+
+/// Same splits as `decode::split`, the u64 leaves are divided with `decode::divide`
+fn split_divide<B: BigInt>(dividend: B, remainders: &mut [Option<NonZeroU32>], start_index: u32) {
+    let length = dividend.bit_length();
+    if length <= usize::try_from(u64::BITS).unwrap() || remainders.len() == 1 {
+        let Some(dividend) = dividend.to_u64() else {
+            *remainders.last_mut().unwrap() = None;
+            return;
+        };
+        divide(WorkItem {
+            dividend: dividend.into(),
+            start_index,
+            remainders,
+            progress: None,
+        });
+        return;
+    }
+    let split_length = DecodeConfig::default_split_policy(length);
+
+    let max_split_index = start_index + u32::try_from(remainders.len() - 1).unwrap();
+    let mut split_index = start_index;
+    let mut factorial = B::from_u64(1);
+    loop {
+        factorial = factorial.mul(&B::from_u64(u64::from(split_index)));
+        split_index += 1;
+        if factorial.bit_length() >= split_length || split_index == max_split_index {
+            break;
+        }
+    }
+
+    let (quotient, remain) = dividend.div_rem(&factorial);
+    let (left, right) =
+        remainders.split_at_mut(usize::try_from(split_index - start_index).unwrap());
+    split_divide(quotient, right, split_index);
+    split_divide(remain, left, start_index);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, encode};
+
+    fn roundtrip<B: BigInt>() {
+        for len in [0, 1, 2, 100, 5000] {
+            let sequence: Vec<u32> = crate::tests::shuffled(len, 833);
+            let sequence = if len % 7 == 0 {
+                sequence.into_iter().rev().collect()
+            } else {
+                sequence
+            };
+            let encoded = encode_with_backend::<B>(&sequence).unwrap();
+            assert_eq!(encoded, encode(&sequence).unwrap());
+            let mut roundtrip = vec![0; sequence.len()];
+            decode_with_backend::<B>(&encoded, &mut roundtrip).unwrap();
+            assert_eq!(sequence, roundtrip);
+        }
+
+        let mut expected = [0; 50];
+        let mut results = [0; 50];
+        assert_eq!(
+            decode(&[0xAB; 20], &mut expected).is_ok(),
+            decode_with_backend::<B>(&[0xAB; 20], &mut results).is_ok()
+        );
+        assert_eq!(expected, results);
+        assert!(matches!(
+            decode_with_backend::<B>(&[0xAB; 4000], &mut results),
+            Err(Error::ElementCountMismatch { got: 50, .. })
+        ));
+    }
+
+    #[test]
+    fn test_dashu_backend() {
+        roundtrip::<UBig>();
+    }
+
+    #[cfg(feature = "num-bigint")]
+    #[test]
+    fn test_num_bigint_backend() {
+        roundtrip::<num_bigint::BigUint>();
+    }
+}
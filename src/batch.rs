@@ -0,0 +1,231 @@
+#[cfg(all(feature = "parallel", not(feature = "wasm")))]
+use rayon::iter::{IndexedParallelIterator, IntoParallelRefIterator, ParallelIterator};
+
+use crate::{
+    decode::DecodeScratch, decode_with_scratch, encode::Encoder, error::Error, validate_code,
+};
+
+/// Codes `big_lehmer::validate_codes` takes from the iterator and checks concurrently at a time
+const VALIDATE_CHUNK: usize = 64;
+
+/// Encodes many permutations concurrently.  
+/// Each worker owns its own `Encoder`, so there is no shared state between the permutations.
+/// Scales better than the parallelism inside `big_lehmer::encode` when there are many small permutations.
+///
+/// # Examples
+/// ```
+/// let perms: [&[u32]; 2] = [&[0, 1, 2], &[2, 0, 1]];
+/// let codes = big_lehmer::encode_batch(&perms).unwrap();
+/// assert_eq!(codes[1], big_lehmer::encode(perms[1]).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Returns the first error (by position) of `big_lehmer::encode`.
+/// Use `big_lehmer::encode_batch_results` to get the result of every permutation.
+pub fn encode_batch(perms: &[&[u32]]) -> Result<Vec<Box<[u8]>>, Error> {
+    encode_batch_results(perms).into_iter().collect()
+}
+
+/// Same as `big_lehmer::encode_batch`, but returns the individual result of every permutation.
+#[cfg(all(feature = "parallel", not(feature = "wasm")))]
+#[must_use]
+pub fn encode_batch_results(perms: &[&[u32]]) -> Vec<Result<Box<[u8]>, Error>> {
+    perms
+        .par_iter()
+        .map_init(Encoder::new, |encoder, perm| encoder.encode(perm))
+        .collect()
+}
+
+/// Same as `big_lehmer::encode_batch`, but returns the individual result of every permutation.
+#[cfg(not(all(feature = "parallel", not(feature = "wasm"))))]
+#[must_use]
+pub fn encode_batch_results(perms: &[&[u32]]) -> Vec<Result<Box<[u8]>, Error>> {
+    let mut encoder = Encoder::new();
+    perms.iter().map(|perm| encoder.encode(perm)).collect()
+}
+
+/// Decodes many codes of `element_count` elements concurrently, the decode counterpart of `big_lehmer::encode_batch`.  
+/// Each worker owns its own `big_lehmer::DecodeScratch`, the remainders and tree are reused for all codes of a worker.
+///
+/// # Examples
+/// ```
+/// let codes = [
+///     big_lehmer::encode(&[0, 1, 2]).unwrap(),
+///     big_lehmer::encode(&[2, 0, 1]).unwrap(),
+/// ];
+/// let codes: Vec<&[u8]> = codes.iter().map(|code| &code[..]).collect();
+/// let perms = big_lehmer::decode_batch(&codes, 3).unwrap();
+/// assert_eq!(perms, [[0, 1, 2], [2, 0, 1]]);
+/// ```
+///
+/// # Errors
+///
+/// Returns the first error (by position) of `big_lehmer::decode`.
+/// Use `big_lehmer::decode_batch_results` to get the result of every code.
+pub fn decode_batch(codes: &[&[u8]], element_count: u32) -> Result<Vec<Vec<u32>>, Error> {
+    decode_batch_results(codes, element_count)
+        .into_iter()
+        .collect()
+}
+
+/// Same as `big_lehmer::decode_batch`, but returns the individual result of every code.
+#[cfg(all(feature = "parallel", not(feature = "wasm")))]
+#[must_use]
+pub fn decode_batch_results(codes: &[&[u8]], element_count: u32) -> Vec<Result<Vec<u32>, Error>> {
+    codes
+        .par_iter()
+        .map_init(DecodeScratch::new, |scratch, code| {
+            decode_one(code, element_count, scratch)
+        })
+        .collect()
+}
+
+/// Same as `big_lehmer::decode_batch`, but returns the individual result of every code.
+#[cfg(not(all(feature = "parallel", not(feature = "wasm"))))]
+#[must_use]
+pub fn decode_batch_results(codes: &[&[u8]], element_count: u32) -> Vec<Result<Vec<u32>, Error>> {
+    let mut scratch = DecodeScratch::new();
+    codes
+        .iter()
+        .map(|code| decode_one(code, element_count, &mut scratch))
+        .collect()
+}
+
+/// Encodes and decodes every permutation concurrently and checks that each one roundtrips.  
+/// A correctness check for large test suites that also stresses the parallel paths,
+/// each worker owns its own `Encoder` and `big_lehmer::DecodeScratch` like `big_lehmer::encode_batch`.
+///
+/// # Examples
+/// ```
+/// let perms: [&[u32]; 3] = [&[0, 1, 2], &[], &[3, 1, 0, 2]];
+/// assert!(big_lehmer::verify_batch(&perms).is_ok());
+/// let perms: [&[u32]; 2] = [&[0, 1, 2], &[1, 1]];
+/// assert!(matches!(
+///     big_lehmer::verify_batch(&perms),
+///     Err(big_lehmer::Error::RoundtripFailed { index: 1, .. })
+/// ));
+/// ```
+///
+/// # Errors
+///
+/// `Error::RoundtripFailed` with the index of the first (by position) permutation that fails to encode, decode
+/// or does not decode to itself. The message contains the underlying error or the first differing position.
+pub fn verify_batch(perms: &[&[u32]]) -> Result<(), Error> {
+    let verify = |encoder: &mut Encoder, scratch: &mut DecodeScratch, perm: &[u32]| {
+        let code = encoder.encode(perm).map_err(|error| error.to_string())?;
+        let mut results = vec![0; perm.len()];
+        decode_with_scratch(&code, &mut results, scratch).map_err(|error| error.to_string())?;
+        match results.iter().zip(perm).position(|(a, b)| a != b) {
+            Some(position) => Err(format!(
+                "the decoded permutation differs at position {position}"
+            )),
+            None => Ok(()),
+        }
+    };
+    #[cfg(all(feature = "parallel", not(feature = "wasm")))]
+    let failure = perms
+        .par_iter()
+        .enumerate()
+        .map_init(
+            || (Encoder::new(), DecodeScratch::new()),
+            |(encoder, scratch), (index, perm)| {
+                verify(encoder, scratch, perm)
+                    .err()
+                    .map(|message| (index, message))
+            },
+        )
+        .find_map_first(|failure| failure);
+    #[cfg(not(all(feature = "parallel", not(feature = "wasm"))))]
+    let failure = {
+        let (mut encoder, mut scratch) = (Encoder::new(), DecodeScratch::new());
+        perms.iter().enumerate().find_map(|(index, perm)| {
+            verify(&mut encoder, &mut scratch, perm)
+                .err()
+                .map(|message| (index, message))
+        })
+    };
+    match failure {
+        Some((index, message)) => Err(Error::RoundtripFailed { index, message }),
+        None => Ok(()),
+    }
+}
+
+/// Checks that every `(code, element_count)` is the canonical code of a permutation of `element_count` elements,
+/// the exact bytes `big_lehmer::encode` produces, and decodes to a valid permutation.  
+/// A bulk integrity check for a corpus of externally produced codes, per code it is `big_lehmer::try_decode_any`.
+/// The element counts are not trusted: a code smaller than `m!` is the identity on all but its last `m` elements,
+/// so only those are decoded and the work per code is bounded by its length.
+///
+/// Streams the codes: 64 at a time are taken from the iterator and checked concurrently,
+/// the first chunk with an invalid code stops the iteration.
+///
+/// # Examples
+/// ```
+/// let codes = vec![
+///     (big_lehmer::encode(&[2, 0, 1]).unwrap().into_vec(), 3),
+///     (vec![], 5),
+///     (vec![6], 3),
+/// ];
+/// assert!(big_lehmer::validate_codes(codes[..2].iter().cloned()).is_ok());
+/// assert_eq!(
+///     big_lehmer::validate_codes(codes).unwrap_err(),
+///     (2, big_lehmer::Error::Decode)
+/// );
+/// ```
+///
+/// # Errors
+///
+/// The position in `codes` and the error of the first invalid code.
+/// `Error::Decode` for a code that is not canonical, otherwise same as `big_lehmer::try_decode_any`.
+pub fn validate_codes<I>(codes: I) -> Result<(), (usize, Error)>
+where
+    I: IntoIterator<Item = (Vec<u8>, u32)>,
+{
+    let mut codes = codes.into_iter();
+    let mut chunk = Vec::with_capacity(VALIDATE_CHUNK);
+    let mut start = 0;
+    loop {
+        chunk.clear();
+        chunk.extend(codes.by_ref().take(VALIDATE_CHUNK));
+        if chunk.is_empty() {
+            return Ok(());
+        }
+        #[cfg(all(feature = "parallel", not(feature = "wasm")))]
+        let failure = chunk
+            .par_iter()
+            .enumerate()
+            .map(|(index, (code, element_count))| {
+                validate_code(code, *element_count)
+                    .err()
+                    .map(|error| (index, error))
+            })
+            .find_map_first(|failure| failure);
+        #[cfg(not(all(feature = "parallel", not(feature = "wasm"))))]
+        let failure = chunk
+            .iter()
+            .enumerate()
+            .find_map(|(index, (code, element_count))| {
+                validate_code(code, *element_count)
+                    .err()
+                    .map(|error| (index, error))
+            });
+        if let Some((index, error)) = failure {
+            return Err((start + index, error));
+        }
+        start += chunk.len();
+    }
+}
+
+fn decode_one(
+    code: &[u8],
+    element_count: u32,
+    scratch: &mut DecodeScratch,
+) -> Result<Vec<u32>, Error> {
+    let len = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    let mut results = vec![0; len];
+    decode_with_scratch(code, &mut results, scratch)?;
+    Ok(results)
+}
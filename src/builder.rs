@@ -0,0 +1,294 @@
+use crate::{
+    byte_order::ByteOrder, decode::DecodeConfig, decode_with_config, encode::Encoder, error::Error,
+    get_encode_size,
+};
+
+/// Big number library a `big_lehmer::LehmerBuilder` does the math with, see `big_lehmer::BigInt`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Backend {
+    /// `dashu::integer::UBig`, the backend of all free functions (default)
+    #[default]
+    Dashu,
+    /// `num_bigint::BigUint`, always takes the serial path
+    #[cfg(feature = "num-bigint")]
+    NumBigint,
+}
+
+/// Single entry point for the encode and decode settings, the free functions stay the defaults.  
+/// Cheap to construct and `Copy`, so one builder can be reused for many calls.
+/// Every call allocates its own buffers, use `big_lehmer::Encoder` or `big_lehmer::DecodeScratch` to reuse them.
+///
+/// Interactions of the options:
+/// - `with_fixed_width(true)` pads the code to `big_lehmer::get_encode_size(N)` bytes.
+///   Decode then requires exactly that many bytes, so it has to get the same element count as encode.
+///   The padding goes to the most significant end, behind the code for little endian and in front of it for big endian.
+/// - `with_parallel(false)` overrides the single thread setting of `with_decode_config`.
+/// - `with_thread_pool` only takes effect while `with_parallel` is true.
+/// - `Backend::NumBigint` always runs serially, it ignores `with_parallel`, `with_thread_pool` and `with_decode_config`.
+/// - Byte order and width never change the rank, the codes only differ in their bytes.
+///
+/// # Examples
+/// ```
+/// use big_lehmer::{ByteOrder, LehmerBuilder};
+///
+/// let builder = LehmerBuilder::new()
+///     .with_byte_order(ByteOrder::BigEndian)
+///     .with_fixed_width(true);
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = builder.encode(&sequence).unwrap();
+/// assert_eq!(encoded.len(), big_lehmer::get_encode_size(8));
+///
+/// let mut roundtrip = [0; 8];
+/// builder.decode(&encoded, &mut roundtrip).unwrap();
+/// assert_eq!(sequence, roundtrip);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct LehmerBuilder<'a> {
+    parallel: bool,
+    #[cfg(feature = "parallel")]
+    thread_pool: Option<&'a rayon::ThreadPool>,
+    #[cfg(not(feature = "parallel"))]
+    thread_pool: std::marker::PhantomData<&'a ()>,
+    byte_order: ByteOrder,
+    fixed_width: bool,
+    backend: Backend,
+    decode_config: DecodeConfig,
+}
+
+impl Default for LehmerBuilder<'_> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<'a> LehmerBuilder<'a> {
+    #[must_use]
+    pub fn new() -> Self {
+        Self {
+            parallel: true,
+            thread_pool: Default::default(),
+            byte_order: ByteOrder::default(),
+            fixed_width: false,
+            backend: Backend::default(),
+            decode_config: DecodeConfig::default(),
+        }
+    }
+
+    /// Whether encode and decode may use the parallel path (default true).  
+    /// Without the `parallel` feature everything runs on the current thread anyway.
+    #[must_use]
+    pub fn with_parallel(mut self, parallel: bool) -> Self {
+        self.parallel = parallel;
+        self
+    }
+
+    /// Runs the parallel work in `thread_pool` instead of the global rayon pool
+    #[cfg(feature = "parallel")]
+    #[must_use]
+    pub fn with_thread_pool(mut self, thread_pool: &'a rayon::ThreadPool) -> Self {
+        self.thread_pool = Some(thread_pool);
+        self
+    }
+
+    /// Byte order of the code (default little endian, same as `big_lehmer::encode`)
+    #[must_use]
+    pub fn with_byte_order(mut self, byte_order: ByteOrder) -> Self {
+        self.byte_order = byte_order;
+        self
+    }
+
+    /// Pads the code to `big_lehmer::get_encode_size(N)` bytes (default false, the code is trimmed)
+    #[must_use]
+    pub fn with_fixed_width(mut self, fixed_width: bool) -> Self {
+        self.fixed_width = fixed_width;
+        self
+    }
+
+    /// Big number library of the math (default `Backend::Dashu`)
+    #[must_use]
+    pub fn with_backend(mut self, backend: Backend) -> Self {
+        self.backend = backend;
+        self
+    }
+
+    /// Decode settings (default `DecodeConfig::default()`)
+    #[must_use]
+    pub fn with_decode_config(mut self, decode_config: DecodeConfig) -> Self {
+        self.decode_config = decode_config;
+        self
+    }
+
+    /// Same as `big_lehmer::encode`, with the settings of this builder
+    ///
+    /// # Errors
+    ///
+    /// Same as `big_lehmer::encode`
+    pub fn encode(&self, numbers: &[u32]) -> Result<Box<[u8]>, Error> {
+        let mut code = match self.backend {
+            Backend::Dashu => self.run(|| {
+                Encoder::new()
+                    .with_single_thread(!self.parallel)
+                    .encode(numbers)
+            })?,
+            #[cfg(feature = "num-bigint")]
+            Backend::NumBigint => crate::encode_with_backend::<num_bigint::BigUint>(numbers)?,
+        }
+        .into_vec();
+        if self.fixed_width {
+            // The validated numbers fit into u32
+            code.resize(get_encode_size(u32::try_from(numbers.len()).unwrap()), 0);
+        }
+        if self.byte_order == ByteOrder::BigEndian {
+            code.reverse();
+        }
+        Ok(code.into_boxed_slice())
+    }
+
+    /// Same as `big_lehmer::decode`, with the settings of this builder
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::LengthMismatch` when fixed width is enabled and `encoded` is not exactly
+    /// `big_lehmer::get_encode_size(results.len())` bytes.  
+    /// Same as `big_lehmer::decode`
+    pub fn decode(&self, encoded: &[u8], results: &mut [u32]) -> Result<(), Error> {
+        if self.fixed_width {
+            let element_count =
+                u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+                    element_count: results.len(),
+                })?;
+            let width = get_encode_size(element_count);
+            if encoded.len() != width {
+                return Err(Error::LengthMismatch {
+                    expected: width,
+                    got: encoded.len(),
+                });
+            }
+        }
+        let reversed: Vec<u8>;
+        let encoded = match self.byte_order {
+            ByteOrder::LittleEndian => encoded,
+            ByteOrder::BigEndian => {
+                reversed = encoded.iter().rev().copied().collect();
+                &reversed
+            }
+        };
+        match self.backend {
+            Backend::Dashu => {
+                let config = if self.parallel {
+                    self.decode_config
+                } else {
+                    self.decode_config.with_single_thread(true)
+                };
+                self.run(|| decode_with_config(encoded, results, config))
+            }
+            #[cfg(feature = "num-bigint")]
+            Backend::NumBigint => {
+                crate::decode_with_backend::<num_bigint::BigUint>(encoded, results)
+            }
+        }
+    }
+
+    /// Runs `work` in the thread pool, when there is one and parallelism is enabled
+    fn run<T: Send>(&self, work: impl FnOnce() -> T + Send) -> T {
+        #[cfg(feature = "parallel")]
+        if let (true, Some(thread_pool)) = (self.parallel, self.thread_pool) {
+            return thread_pool.install(work);
+        }
+        work()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(builder: LehmerBuilder<'_>, sequence: &[u32]) -> Box<[u8]> {
+        let encoded = builder.encode(sequence).unwrap();
+        let mut results = vec![0; sequence.len()];
+        builder.decode(&encoded, &mut results).unwrap();
+        assert_eq!(sequence, results);
+        encoded
+    }
+
+    #[test]
+    fn test_builder_matches_free_functions() {
+        let sequence: Vec<u32> = crate::tests::shuffled(2000, 874);
+        let plain = crate::encode(&sequence).unwrap();
+        assert_eq!(roundtrip(LehmerBuilder::new(), &sequence), plain);
+        assert_eq!(
+            roundtrip(LehmerBuilder::new().with_parallel(false), &sequence),
+            plain
+        );
+        let decode_config = DecodeConfig::new().with_strategy(crate::DecodeStrategy::Serial);
+        assert_eq!(
+            roundtrip(
+                LehmerBuilder::new().with_decode_config(decode_config),
+                &sequence
+            ),
+            plain
+        );
+        let big_endian = roundtrip(
+            LehmerBuilder::new().with_byte_order(ByteOrder::BigEndian),
+            &sequence,
+        );
+        assert_eq!(
+            big_endian,
+            crate::encode_with_byte_order(&sequence, ByteOrder::BigEndian).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_builder_fixed_width() {
+        let builder = LehmerBuilder::new().with_fixed_width(true);
+        // The identity is all padding
+        assert_eq!(*roundtrip(builder, &[0, 1, 2, 3, 4]), [0; 1]);
+        let sequence: Vec<u32> = (0..100).rev().collect();
+        for byte_order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+            let builder = builder.with_byte_order(byte_order);
+            let encoded = roundtrip(builder, &sequence);
+            assert_eq!(encoded.len(), get_encode_size(100));
+        }
+        let big_endian = builder.with_byte_order(ByteOrder::BigEndian);
+        assert_eq!(
+            *big_endian.encode(&sequence).unwrap(),
+            *crate::encode_db(&sequence).unwrap()
+        );
+
+        // The width depends on the element count
+        let encoded = builder.encode(&sequence).unwrap();
+        assert!(matches!(
+            builder.decode(&encoded, &mut [0; 99]),
+            Err(Error::LengthMismatch { got: 66, .. })
+        ));
+        assert!(LehmerBuilder::new().decode(&encoded, &mut [0; 100]).is_ok());
+    }
+
+    #[cfg(feature = "parallel")]
+    #[test]
+    fn test_builder_thread_pool() {
+        let thread_pool = rayon::ThreadPoolBuilder::new()
+            .num_threads(2)
+            .build()
+            .unwrap();
+        let builder = LehmerBuilder::new().with_thread_pool(&thread_pool);
+        let copy = builder;
+        let sequence: Vec<u32> = crate::tests::shuffled(5000, 873);
+        assert_eq!(roundtrip(builder, &sequence), roundtrip(copy, &sequence));
+        assert_eq!(
+            roundtrip(builder, &sequence),
+            crate::encode(&sequence).unwrap()
+        );
+    }
+
+    #[cfg(feature = "num-bigint")]
+    #[test]
+    fn test_builder_num_bigint() {
+        let sequence: Vec<u32> = (0..500).rev().collect();
+        let builder = LehmerBuilder::new().with_backend(Backend::NumBigint);
+        assert_eq!(
+            roundtrip(builder, &sequence),
+            crate::encode(&sequence).unwrap()
+        );
+    }
+}
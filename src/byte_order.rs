@@ -0,0 +1,201 @@
+use dashu::integer::UBig;
+
+use crate::{decode_from_ubig, encode_to_ubig, error::Error, get_encode_size};
+
+/// Byte order of a Lehmer code.  
+/// `big_lehmer::encode` and `big_lehmer::decode` always use `LittleEndian`.
+///
+/// The code itself does not store its byte order. Decoding a code with the other byte order is undefined:
+/// it either errors (usually `Error::ElementCountMismatch`) or silently produces a different, valid looking permutation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ByteOrder {
+    /// Least significant byte first (default)
+    #[default]
+    LittleEndian,
+    /// Most significant byte first
+    BigEndian,
+}
+
+/// Same as `big_lehmer::encode`, but writes the code in the given byte order.  
+/// Decode with `big_lehmer::decode_with_byte_order` and the same byte order.
+///
+/// # Examples
+/// ```
+/// use big_lehmer::ByteOrder;
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode_with_byte_order(&sequence, ByteOrder::BigEndian).unwrap();
+/// let mut little = big_lehmer::encode(&sequence).unwrap().to_vec();
+/// little.reverse();
+/// assert_eq!(*encoded, *little);
+///
+/// let mut roundtrip = [0; 8];
+/// big_lehmer::decode_with_byte_order(&encoded, &mut roundtrip, ByteOrder::BigEndian).unwrap();
+/// assert_eq!(sequence, roundtrip);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_with_byte_order(numbers: &[u32], order: ByteOrder) -> Result<Box<[u8]>, Error> {
+    let rank = encode_to_ubig(numbers)?;
+    Ok(match order {
+        ByteOrder::LittleEndian => rank.to_le_bytes(),
+        ByteOrder::BigEndian => rank.to_be_bytes(),
+    })
+}
+
+/// Same as `big_lehmer::decode`, but reads the code in the given byte order.  
+/// `order` has to match the byte order used for encode, see `big_lehmer::ByteOrder`.
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_with_byte_order(
+    encoded: &[u8],
+    results: &mut [u32],
+    order: ByteOrder,
+) -> Result<(), Error> {
+    let rank = match order {
+        ByteOrder::LittleEndian => UBig::from_le_bytes(encoded),
+        ByteOrder::BigEndian => UBig::from_be_bytes(encoded),
+    };
+    decode_from_ubig(rank, results)
+}
+
+/// Same as `big_lehmer::encode`, but for a fixed width database column like SQL `BINARY(k)`.  
+/// Always outputs exactly `big_lehmer::get_encode_size(numbers.len())` bytes in big endian order, padded with leading zeros.
+/// Big endian keeps the byte wise column order the same as the rank order, so `ORDER BY` sorts lexicographically.
+///
+/// The column width only depends on the element count:
+///
+/// | Elements | Column width (byte) |
+/// |----------|---------------------|
+/// | 0, 1     | 0                   |
+/// | 2        | 1                   |
+/// | 8        | 2                   |
+/// | 16       | 6                   |
+/// | 32       | 15                  |
+/// | 64       | 37                  |
+/// | 100      | 66                  |
+/// | 256      | 211                 |
+/// | 1000     | 1067                |
+/// | 10000    | 14810               |
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let column = big_lehmer::encode_db(&sequence).unwrap();
+/// assert_eq!(column.len(), big_lehmer::get_encode_size(8));
+///
+/// let mut roundtrip = [0; 8];
+/// big_lehmer::decode_db(&column, &mut roundtrip).unwrap();
+/// assert_eq!(sequence, roundtrip);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_db(numbers: &[u32]) -> Result<Vec<u8>, Error> {
+    let element_count = u32::try_from(numbers.len()).map_err(|_| Error::SequenceToLong {
+        element_count: numbers.len(),
+    })?;
+    let code = encode_to_ubig(numbers)?.to_be_bytes();
+    let mut column = vec![0; get_encode_size(element_count)];
+    let padding = column.len() - code.len();
+    column[padding..].copy_from_slice(&code);
+    Ok(column)
+}
+
+/// Decodes a column written by `big_lehmer::encode_db`.
+///
+/// # Errors
+///
+/// Errors with `Error::LengthMismatch` when `encoded` is not exactly `big_lehmer::get_encode_size(results.len())` bytes,
+/// usually the column belongs to a different element count.  
+/// Same as `big_lehmer::decode`
+pub fn decode_db(encoded: &[u8], results: &mut [u32]) -> Result<(), Error> {
+    let element_count = u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+        element_count: results.len(),
+    })?;
+    let width = get_encode_size(element_count);
+    if encoded.len() != width {
+        return Err(Error::LengthMismatch {
+            expected: width,
+            got: encoded.len(),
+        });
+    }
+    decode_with_byte_order(encoded, results, ByteOrder::BigEndian)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_byte_order_roundtrip() {
+        let sequence: Vec<u32> = crate::tests::shuffled(1000, 821);
+        for order in [ByteOrder::LittleEndian, ByteOrder::BigEndian] {
+            let encoded = encode_with_byte_order(&sequence, order).unwrap();
+            let mut roundtrip = vec![0; sequence.len()];
+            decode_with_byte_order(&encoded, &mut roundtrip, order).unwrap();
+            assert_eq!(sequence, roundtrip);
+        }
+    }
+
+    #[test]
+    fn test_byte_order_cross_decode() {
+        let sequence: Vec<u32> = crate::tests::shuffled(1000, 820);
+        let encoded = encode_with_byte_order(&sequence, ByteOrder::BigEndian).unwrap();
+        let mut roundtrip = vec![0; sequence.len()];
+        // Undefined, but never the original permutation
+        let result = decode_with_byte_order(&encoded, &mut roundtrip, ByteOrder::LittleEndian);
+        assert!(result.is_err() || roundtrip != sequence);
+    }
+
+    #[test]
+    fn test_db_column_compatibility() {
+        // Stored column of [7, 2, 0, 6, 5, 1, 4, 3], byte for byte as read back from the database
+        let stored: [u8; 2] = [0x8F, 0xE3];
+        let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+        assert_eq!(encode_db(&sequence).unwrap(), stored);
+        let mut roundtrip = [0; 8];
+        decode_db(&stored, &mut roundtrip).unwrap();
+        assert_eq!(roundtrip, sequence);
+
+        for len in [0u32, 1, 2, 3, 100, 1000] {
+            let mut sequence: Vec<u32> = (0..len).collect();
+            // Identity is the smallest code, so it is all padding
+            let column = encode_db(&sequence).unwrap();
+            assert_eq!(column.len(), get_encode_size(len));
+            assert!(column.iter().all(|&byte| byte == 0));
+            sequence.reverse();
+            let column = encode_db(&sequence).unwrap();
+            assert_eq!(column.len(), get_encode_size(len));
+            let stored = column.clone();
+            let mut roundtrip = vec![0; sequence.len()];
+            decode_db(&stored, &mut roundtrip).unwrap();
+            assert_eq!(roundtrip, sequence);
+        }
+
+        assert!(matches!(
+            decode_db(&stored, &mut [0; 16]),
+            Err(Error::LengthMismatch {
+                expected: 6,
+                got: 2
+            })
+        ));
+    }
+
+    #[test]
+    fn test_db_column_order() {
+        // Byte wise order of the columns is the rank order
+        let columns: Vec<Vec<u8>> = (0u16..120)
+            .map(|rank| {
+                let mut numbers = [0; 5];
+                crate::decode(&rank.to_le_bytes(), &mut numbers).unwrap();
+                encode_db(&numbers).unwrap()
+            })
+            .collect();
+        assert!(columns.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
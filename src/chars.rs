@@ -0,0 +1,79 @@
+use crate::{decode, encode, error::Error};
+
+/// Ranks the order of the chars of `s`, the sorted chars of `s` are the alphabet.  
+/// For example all anagrams of a word with distinct letters get a distinct code.
+/// Decode with `big_lehmer::unrank_chars` and the sorted chars.
+///
+/// # Examples
+/// ```
+/// let code = big_lehmer::rank_chars("dcba").unwrap();
+/// // Reversed alphabet, the largest rank of 4 elements
+/// assert_eq!(*code, [23]);
+/// assert_eq!(big_lehmer::unrank_chars(&code, "abcd").unwrap(), "dcba");
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::ValidationDuplicateNumber` when `s` contains a char more than once,
+/// with the char index of the second occurrence and the alphabet index of the char.  
+/// Same as `big_lehmer::encode`
+pub fn rank_chars(s: &str) -> Result<Box<[u8]>, Error> {
+    let chars: Vec<char> = s.chars().collect();
+    let mut alphabet = chars.clone();
+    alphabet.sort_unstable();
+
+    // Duplicate chars map to the same number, which encode reports
+    let numbers: Vec<u32> = chars
+        .iter()
+        .map(|c| u32::try_from(alphabet.partition_point(|a| a < c)))
+        .collect::<Result<_, _>>()
+        .map_err(|_| Error::SequenceToLong {
+            element_count: chars.len(),
+        })?;
+    encode(&numbers)
+}
+
+/// Decodes a code of `big_lehmer::rank_chars` back into its string.  
+/// `sorted_chars` is the alphabet, for `big_lehmer::rank_chars` the sorted chars of the original string.
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode` with `sorted_chars.chars().count()` elements
+pub fn unrank_chars(code: &[u8], sorted_chars: &str) -> Result<String, Error> {
+    let alphabet: Vec<char> = sorted_chars.chars().collect();
+    let mut numbers = vec![0; alphabet.len()];
+    decode(code, &mut numbers)?;
+    Ok(numbers
+        .iter()
+        .map(|&number| alphabet[number as usize])
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_chars_roundtrip() {
+        for s in ["", "a", "quick brown", "zyxwvuts", "ÿäöü→"] {
+            let code = rank_chars(s).unwrap();
+            let mut sorted: Vec<char> = s.chars().collect();
+            sorted.sort_unstable();
+            let sorted: String = sorted.into_iter().collect();
+            assert_eq!(unrank_chars(&code, &sorted).unwrap(), s);
+        }
+        // Sorted input is the identity
+        assert_eq!(*rank_chars("abcdef").unwrap(), []);
+    }
+
+    #[test]
+    fn test_rank_chars_duplicate() {
+        assert!(matches!(
+            rank_chars("lehmer"),
+            Err(Error::ValidationDuplicateNumber {
+                index: 4,
+                number: 0
+            })
+        ));
+    }
+}
@@ -0,0 +1,81 @@
+use crate::{decode, encode, error::Error};
+
+const CHECKSUM_LEN: usize = 4;
+
+/// Same as `big_lehmer::encode`, but appends a 4 byte CRC32 (little endian) of the code.  
+/// Decode with `big_lehmer::decode_with_checksum` to detect corruption before decoding.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let mut encoded = big_lehmer::encode_with_checksum(&sequence).unwrap().into_vec();
+/// let mut roundtrip = [0; 8];
+/// big_lehmer::decode_with_checksum(&encoded, &mut roundtrip).unwrap();
+/// assert_eq!(sequence, roundtrip);
+///
+/// encoded[0] ^= 1;
+/// assert!(big_lehmer::decode_with_checksum(&encoded, &mut roundtrip).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_with_checksum(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
+    let mut code = encode(numbers)?.into_vec();
+    let checksum = crc32fast::hash(&code);
+    code.extend_from_slice(&checksum.to_le_bytes());
+    Ok(code.into_boxed_slice())
+}
+
+/// Verifies the checksum appended by `big_lehmer::encode_with_checksum`, then decodes the code.
+///
+/// # Errors
+///
+/// `Error::ChecksumMismatch` when the checksum does not match (or `encoded` is too short to contain one).  
+/// Same errors as `big_lehmer::decode`
+pub fn decode_with_checksum(encoded: &[u8], results: &mut [u32]) -> Result<(), Error> {
+    let split = encoded
+        .len()
+        .checked_sub(CHECKSUM_LEN)
+        .ok_or(Error::ChecksumMismatch)?;
+    let (code, checksum) = encoded.split_at(split);
+    if crc32fast::hash(code).to_le_bytes() != checksum {
+        return Err(Error::ChecksumMismatch);
+    }
+    decode(code, results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksum_detects_corruption() {
+        let sequence: Vec<u32> = (0..100).rev().collect();
+        let encoded = encode_with_checksum(&sequence).unwrap();
+        let mut roundtrip = vec![0; sequence.len()];
+
+        for index in 0..encoded.len() {
+            let mut corrupted = encoded.to_vec();
+            corrupted[index] ^= 0x10;
+            assert!(matches!(
+                decode_with_checksum(&corrupted, &mut roundtrip),
+                Err(Error::ChecksumMismatch)
+            ));
+        }
+        assert!(matches!(
+            decode_with_checksum(&encoded[..3], &mut roundtrip),
+            Err(Error::ChecksumMismatch)
+        ));
+
+        decode_with_checksum(&encoded, &mut roundtrip).unwrap();
+        assert_eq!(sequence, roundtrip);
+    }
+
+    #[test]
+    fn test_checksum_empty() {
+        let encoded = encode_with_checksum(&[]).unwrap();
+        assert_eq!(encoded.len(), CHECKSUM_LEN);
+        decode_with_checksum(&encoded, &mut []).unwrap();
+    }
+}
@@ -0,0 +1,253 @@
+use std::{
+    cmp::Ordering,
+    hash::{Hash, Hasher},
+};
+
+use dashu::integer::UBig;
+
+use crate::error::Error;
+
+/// Compares two little endian Lehmer codes by their numeric value, the rank of the permutation.  
+/// High zero bytes are ignored, so codes of different byte length compare correctly.
+/// For two codes of the same element count this is the lexicographic order of the permutations.
+///
+/// # Examples
+/// ```
+/// use std::cmp::Ordering;
+/// let small = big_lehmer::encode(&[0, 2, 1]).unwrap();
+/// let large = big_lehmer::encode(&[2, 1, 0]).unwrap();
+/// assert_eq!(big_lehmer::compare_codes(&small, &large), Ordering::Less);
+/// assert_eq!(big_lehmer::compare_codes(&[5, 0, 0], &[5]), Ordering::Equal);
+/// ```
+#[must_use]
+pub fn compare_codes(a: &[u8], b: &[u8]) -> Ordering {
+    let (a, b) = (trim(a), trim(b));
+    // Without high zero bytes the longer code is the larger number,
+    // equal lengths compare from the most significant byte down
+    a.len()
+        .cmp(&b.len())
+        .then_with(|| a.iter().rev().cmp(b.iter().rev()))
+}
+
+/// `code` without its high zero bytes
+fn trim(code: &[u8]) -> &[u8] {
+    let len = code
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |last| last + 1);
+    &code[..len]
+}
+
+/// Owned Lehmer code, as produced by `big_lehmer::encode`.
+///
+/// `Eq`, `Ord` and `Hash` use the numeric value of the code (same as `big_lehmer::compare_codes`), not the raw bytes.
+/// That way codes can be used as keys of a `HashMap` or `BTreeMap` to dedupe and order permutations,
+/// a code with additional high zero bytes is the same key.
+///
+/// # Examples
+/// ```
+/// use std::collections::BTreeSet;
+/// let codes: BTreeSet<_> = [[2, 1, 0], [0, 1, 2], [2, 1, 0], [1, 0, 2]]
+///     .iter()
+///     .map(|sequence| big_lehmer::LehmerCode::encode(sequence).unwrap())
+///     .collect();
+/// assert_eq!(codes.len(), 3);
+/// let first = codes.first().unwrap();
+/// assert_eq!(*first, big_lehmer::LehmerCode::encode(&[0, 1, 2]).unwrap());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct LehmerCode(Box<[u8]>);
+
+impl LehmerCode {
+    /// Wraps the bytes of an existing code
+    #[must_use]
+    pub fn new(code: Box<[u8]>) -> Self {
+        Self(code)
+    }
+
+    /// Encodes the number sequence, same as `big_lehmer::encode`
+    ///
+    /// # Errors
+    ///
+    /// Same as `big_lehmer::encode`
+    pub fn encode(numbers: &[u32]) -> Result<Self, Error> {
+        crate::encode(numbers).map(Self)
+    }
+
+    /// Decodes the code into `results`, same as `big_lehmer::decode`
+    ///
+    /// # Errors
+    ///
+    /// Same as `big_lehmer::decode`
+    pub fn decode(&self, results: &mut [u32]) -> Result<(), Error> {
+        crate::decode(&self.0, results)
+    }
+
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.0
+    }
+
+    #[must_use]
+    pub fn into_bytes(self) -> Box<[u8]> {
+        self.0
+    }
+}
+
+impl From<Box<[u8]>> for LehmerCode {
+    fn from(code: Box<[u8]>) -> Self {
+        Self(code)
+    }
+}
+
+/// Stores the minimal little endian bytes of the rank, the same bytes `big_lehmer::encode` produces.  
+/// A bare `UBig` does not carry the element count: any rank is accepted here,
+/// the element count is only checked on decode (`Error::ElementCountMismatch` when the rank is too large).
+///
+/// # Examples
+/// ```
+/// use dashu::integer::UBig;
+/// let code = big_lehmer::LehmerCode::from(UBig::from(23u8));
+/// assert_eq!(code, big_lehmer::LehmerCode::encode(&[3, 2, 1, 0]).unwrap());
+/// assert_eq!(UBig::from(&code), UBig::from(23u8));
+/// ```
+impl From<UBig> for LehmerCode {
+    fn from(rank: UBig) -> Self {
+        Self(rank.to_le_bytes())
+    }
+}
+
+/// The rank of the code, high zero bytes are ignored
+impl From<&LehmerCode> for UBig {
+    fn from(code: &LehmerCode) -> Self {
+        Self::from_le_bytes(&code.0)
+    }
+}
+
+/// The rank of the code, high zero bytes are ignored
+impl From<LehmerCode> for UBig {
+    fn from(code: LehmerCode) -> Self {
+        Self::from(&code)
+    }
+}
+
+impl AsRef<[u8]> for LehmerCode {
+    fn as_ref(&self) -> &[u8] {
+        &self.0
+    }
+}
+
+impl PartialEq for LehmerCode {
+    fn eq(&self, other: &Self) -> bool {
+        trim(&self.0) == trim(&other.0)
+    }
+}
+
+impl Eq for LehmerCode {}
+
+impl PartialOrd for LehmerCode {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for LehmerCode {
+    fn cmp(&self, other: &Self) -> Ordering {
+        compare_codes(&self.0, &other.0)
+    }
+}
+
+impl Hash for LehmerCode {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Must agree with Eq, high zero bytes are ignored
+        trim(&self.0).hash(state);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::{BTreeSet, HashSet};
+
+    use super::*;
+    use crate::permutation_rank;
+
+    #[test]
+    fn test_btree_set_rank_order() {
+        let sequences: Vec<Vec<u32>> = vec![
+            (0..300).rev().collect(),
+            (0..300).collect(),
+            crate::tests::shuffled(300, 845),
+            crate::tests::shuffled(300, 844),
+            (0..300).map(|i| (i + 1) % 300).collect(),
+        ];
+        let codes: BTreeSet<LehmerCode> = sequences
+            .iter()
+            .map(|sequence| LehmerCode::encode(sequence).unwrap())
+            .collect();
+        assert_eq!(codes.len(), sequences.len());
+
+        let mut ranks: Vec<_> = sequences
+            .iter()
+            .map(|sequence| permutation_rank(sequence).unwrap())
+            .collect();
+        ranks.sort();
+        let code_ranks: Vec<_> = codes
+            .iter()
+            .map(|code| UBig::from_le_bytes(code.as_bytes()))
+            .collect();
+        assert_eq!(code_ranks, ranks);
+
+        // Ord agrees with compare_codes
+        for a in &codes {
+            for b in &codes {
+                assert_eq!(a.cmp(b), compare_codes(a.as_bytes(), b.as_bytes()));
+            }
+        }
+    }
+
+    #[test]
+    fn test_high_zero_bytes() {
+        let short = LehmerCode::new(Box::new([1, 2]));
+        let padded = LehmerCode::new(Box::new([1, 2, 0, 0]));
+        let larger = LehmerCode::new(Box::new([0, 0, 1]));
+        assert_eq!(short, padded);
+        assert!(short < larger);
+        assert!(padded < larger);
+        assert_eq!(compare_codes(&[], &[0, 0]), Ordering::Equal);
+
+        let set: HashSet<LehmerCode> = [short, padded, larger].into_iter().collect();
+        assert_eq!(set.len(), 2);
+    }
+
+    #[test]
+    fn test_ubig_conversions() {
+        for sequence in [
+            vec![],
+            vec![0, 1],
+            vec![1, 0],
+            (0..300).rev().collect(),
+            crate::tests::shuffled(3000, 906),
+        ] {
+            let code = LehmerCode::encode(&sequence).unwrap();
+            let rank = permutation_rank(&sequence).unwrap();
+            assert_eq!(UBig::from(&code), rank);
+            // Bit for bit the bytes of encode
+            let from_rank = LehmerCode::from(rank.clone());
+            assert_eq!(from_rank.as_bytes(), code.as_bytes());
+            assert_eq!(UBig::from(from_rank), rank);
+        }
+        assert_eq!(
+            UBig::from(LehmerCode::new(Box::new([5, 0, 0]))),
+            UBig::from(5u8)
+        );
+        assert!(LehmerCode::from(UBig::ZERO).as_bytes().is_empty());
+
+        // The element count is checked on decode
+        let too_large = LehmerCode::from(UBig::from(6u8));
+        assert!(matches!(
+            too_large.decode(&mut [0; 3]),
+            Err(Error::ElementCountMismatch { .. })
+        ));
+        too_large.decode(&mut [0; 4]).unwrap();
+    }
+}
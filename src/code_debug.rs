@@ -0,0 +1,81 @@
+use std::fmt::{self, Binary, Display, Formatter};
+
+use dashu::integer::UBig;
+
+use crate::decode::code_digits;
+
+/// Debug view of a Lehmer code of `element_count` elements, does not change the code itself.
+///
+/// `Display` prints the space separated factoradic digits (most significant first, same as `big_lehmer::to_factoradic`).
+/// Prints `<invalid code>` when the code does not decode.  
+/// `Binary` (`{:b}`) prints the code as binary number.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[7, 2, 0, 6, 5, 1, 4, 3]).unwrap();
+/// let view = big_lehmer::CodeDebug::new(&encoded, 8);
+/// assert_eq!(view.to_string(), "7 2 0 4 3 0 1 0");
+/// // Rank 36835
+/// assert_eq!(format!("{view:b}"), "1000111111100011");
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct CodeDebug<'a> {
+    code: &'a [u8],
+    element_count: u32,
+}
+
+impl<'a> CodeDebug<'a> {
+    #[must_use]
+    pub fn new(code: &'a [u8], element_count: u32) -> Self {
+        Self {
+            code,
+            element_count,
+        }
+    }
+}
+
+impl Display for CodeDebug<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let digits = usize::try_from(self.element_count)
+            .ok()
+            .and_then(|count| code_digits(UBig::from_le_bytes(self.code), count).ok());
+        let Some(digits) = digits else {
+            return f.write_str("<invalid code>");
+        };
+        for (index, digit) in digits.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            f.write_fmt(format_args!("{digit}"))?;
+        }
+        Ok(())
+    }
+}
+
+impl Binary for CodeDebug<'_> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Binary::fmt(&UBig::from_le_bytes(self.code), f)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode, to_factoradic};
+
+    #[test]
+    fn test_code_debug() {
+        let sequence: Vec<u32> = crate::tests::shuffled(50, 822);
+        let encoded = encode(&sequence).unwrap();
+        let digits: Vec<String> = to_factoradic(&sequence)
+            .unwrap()
+            .iter()
+            .map(u32::to_string)
+            .collect();
+        assert_eq!(CodeDebug::new(&encoded, 50).to_string(), digits.join(" "));
+
+        assert_eq!(CodeDebug::new(&[], 0).to_string(), "");
+        assert_eq!(format!("{:b}", CodeDebug::new(&[], 0)), "0");
+        assert_eq!(CodeDebug::new(&encoded, 10).to_string(), "<invalid code>");
+    }
+}
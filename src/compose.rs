@@ -0,0 +1,218 @@
+use dashu::{base::DivRem, integer::UBig};
+
+use crate::{
+    decode, encode,
+    encode::{reduce_big_caches_serial, BigCache},
+    error::Error,
+    factorial::factorial,
+    permutation_rank, permutation_unrank,
+    validation::is_valid_permutation,
+};
+
+/// Code of the composition `a ∘ b` (first apply `b`, then `a`) of the permutations of the codes `a` and `b`.  
+/// The result is `result[i] = a[b[i]]`.
+///
+/// Decodes both, composes and re-encodes, so it costs two decodes and one encode.
+/// A direct algorithm in rank space is not known: composition does not act on the factoradic digits independently,
+/// every digit of the result depends on the full prefix of both permutations.
+///
+/// # Examples
+/// ```
+/// let a = big_lehmer::encode(&[1, 2, 0]).unwrap();
+/// let b = big_lehmer::encode(&[2, 1, 0]).unwrap();
+/// let composed = big_lehmer::compose_codes(&a, &b, 3).unwrap();
+/// assert_eq!(composed, big_lehmer::encode(&[0, 2, 1]).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode` for both codes
+pub fn compose_codes(a: &[u8], b: &[u8], element_count: u32) -> Result<Box<[u8]>, Error> {
+    let len = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    let mut first = vec![0; len];
+    decode(a, &mut first)?;
+    let mut second = vec![0; len];
+    decode(b, &mut second)?;
+
+    for number in &mut second {
+        *number = first[*number as usize];
+    }
+    encode(&second)
+}
+
+/// Code of `numbers`, a permutation of `0..n`, embedded at the front of a permutation of `0..total`.  
+/// The tail convention: the positions `n..total` hold the remaining numbers `n..total` in ascending order (identity tail).
+/// The result is the code of `[numbers..., n, n + 1, ..., total - 1]` in the `total!` space.
+///
+/// The tail contributes only zero digits and no tail number is smaller than a front number,
+/// so the front digits are the same as in `big_lehmer::encode(numbers)`, just with larger weights.
+/// Useful for building a permutation incrementally while keeping a code of the final size.
+///
+/// # Examples
+/// ```
+/// let embedded = big_lehmer::embed(&[1, 0], 4).unwrap();
+/// assert_eq!(embedded, big_lehmer::encode(&[1, 0, 2, 3]).unwrap());
+/// // Same digits, but with the weights of the larger space
+/// assert_eq!(big_lehmer::encode(&[1, 0]).unwrap()[0], 1);
+/// assert_eq!(embedded[0], 6);
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::LengthMismatch` when `total` is smaller than `numbers.len()`.  
+/// Validation errors of `numbers` as a permutation of `0..numbers.len()`, same as `big_lehmer::encode`
+pub fn embed(numbers: &[u32], total: u32) -> Result<Box<[u8]>, Error> {
+    let total_len = usize::try_from(total).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    if total_len < numbers.len() {
+        return Err(Error::LengthMismatch {
+            expected: total_len,
+            got: numbers.len(),
+        });
+    }
+    // Validate on its own, otherwise a front number >= n would be reported as a duplicate in the tail
+    is_valid_permutation(numbers)?;
+    let front = u32::try_from(numbers.len()).unwrap();
+    let embedded: Vec<u32> = numbers.iter().copied().chain(front..total).collect();
+    encode(&embedded)
+}
+
+/// Packs several permutations into one code, a mixed radix number with the radix `len_i!` per permutation.  
+/// The first permutation is the most significant, like the first Lehmer digit in `big_lehmer::encode`.
+/// So the code is `((rank_0 * len_1! + rank_1) * len_2! + rank_2) ...`, combined pairwise like the encode reduce.
+///
+/// The code has `ceil(log2(len_0! * len_1! * ...) / 8)` bytes, which is never more
+/// than concatenating the individual codes, each padded to `big_lehmer::get_encode_size`.
+/// The lengths are not stored, they have to be passed to `big_lehmer::unpack`.
+///
+/// # Examples
+/// ```
+/// let packed = big_lehmer::pack(&[&[1, 0], &[2, 0, 1]]).unwrap();
+/// // Rank 1 of 2 elements and rank 4 of 3 elements: 1 * 3! + 4
+/// assert_eq!(*packed, [10]);
+/// assert_eq!(big_lehmer::unpack(&packed, &[2, 3]).unwrap(), [vec![1, 0], vec![2, 0, 1]]);
+/// ```
+///
+/// # Errors
+///
+/// Validation errors of the first invalid permutation, same as `big_lehmer::encode`.
+/// The index in the error is the position inside that permutation.
+pub fn pack(perms: &[&[u32]]) -> Result<Box<[u8]>, Error> {
+    let segments = perms
+        .iter()
+        .map(|numbers| {
+            // permutation_rank already checked the length fits into u32
+            Ok(BigCache {
+                add: permutation_rank(numbers)?,
+                mul: factorial(u32::try_from(numbers.len()).unwrap()),
+            })
+        })
+        .collect::<Result<Vec<_>, Error>>()?;
+    Ok(reduce_big_caches_serial(segments).add.to_le_bytes())
+}
+
+/// Inverse of `big_lehmer::pack`, splits the code into permutations of the given `lengths`.  
+/// Divides off one `len_i!` after the other from the least significant end,
+/// so with many permutations it costs about one big number division of the whole code per permutation.
+///
+/// # Examples
+/// ```
+/// let first: Vec<u32> = (0..8).rev().collect();
+/// let second: Vec<u32> = (0..16).collect();
+/// let packed = big_lehmer::pack(&[&first, &second]).unwrap();
+/// assert_eq!(big_lehmer::unpack(&packed, &[8, 16]).unwrap(), [first, second]);
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::Decode` when the code is not smaller than the product of the `len_i!`,
+/// it was packed with other lengths.
+pub fn unpack(code: &[u8], lengths: &[u32]) -> Result<Vec<Vec<u32>>, Error> {
+    let mut rank = UBig::from_le_bytes(code);
+    let mut perms = Vec::with_capacity(lengths.len());
+    for &length in lengths.iter().rev() {
+        let (quotient, remainder) = rank.div_rem(factorial(length));
+        perms.push(permutation_unrank(&remainder, length)?);
+        rank = quotient;
+    }
+    if rank != UBig::ZERO {
+        return Err(Error::Decode);
+    }
+    perms.reverse();
+    Ok(perms)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_compose_associative() {
+        let codes: Vec<Box<[u8]>> = (0u8..24).map(|rank| Box::from([rank])).collect();
+        for a in &codes {
+            for b in &codes {
+                let ab = compose_codes(a, b, 4).unwrap();
+                for c in &codes {
+                    let bc = compose_codes(b, c, 4).unwrap();
+                    assert_eq!(
+                        compose_codes(&ab, c, 4).unwrap(),
+                        compose_codes(a, &bc, 4).unwrap()
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_compose_identity() {
+        let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+        let code = encode(&sequence).unwrap();
+        assert_eq!(compose_codes(&code, &[], 8).unwrap(), code);
+        assert_eq!(compose_codes(&[], &code, 8).unwrap(), code);
+        assert!(compose_codes(&code, &[], 4).is_err());
+    }
+
+    #[test]
+    fn test_embed() {
+        // Identity embeds to the identity
+        assert!(embed(&[0, 1, 2], 10).unwrap().is_empty());
+        assert!(embed(&[], 0).unwrap().is_empty());
+        // Same space is a plain encode
+        let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+        assert_eq!(embed(&sequence, 8).unwrap(), encode(&sequence).unwrap());
+
+        // All permutations of 3 elements in the 5! space
+        let mut numbers = [0, 1, 2];
+        let mut rank = dashu::integer::UBig::ZERO;
+        for rank_small in 0u8..6 {
+            decode(&[rank_small], &mut numbers).unwrap();
+            let embedded = embed(&numbers, 5).unwrap();
+            let mut decoded = [0; 5];
+            decode(&embedded, &mut decoded).unwrap();
+            assert_eq!(decoded[..3], numbers);
+            assert_eq!(decoded[3..], [3, 4]);
+            // Ranks keep their order
+            let embedded_rank = dashu::integer::UBig::from_le_bytes(&embedded);
+            assert!(rank_small == 0 || embedded_rank > rank);
+            rank = embedded_rank;
+        }
+
+        assert!(matches!(
+            embed(&sequence, 7),
+            Err(Error::LengthMismatch {
+                expected: 7,
+                got: 8
+            })
+        ));
+        assert!(matches!(
+            embed(&[0, 3], 4),
+            Err(Error::ValidationOutOfRange {
+                index: 1,
+                number: 3
+            })
+        ));
+    }
+}
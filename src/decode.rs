@@ -2,6 +2,257 @@ use std::num::NonZeroU32;
 
 use dashu::{base::BitTest, base::DivRem, integer::UBig};
 
+use crate::{
+    error::Error, factorial::factorial_with_bits, progress::ProgressPhase,
+    ranking::OrderStatisticTree,
+};
+#[cfg(all(feature = "parallel", not(feature = "wasm")))]
+use rayon::iter::{IntoParallelIterator, ParallelIterator};
+
+/// How the factoradic digits are computed during decode.  
+/// All strategies produce identical results, they only differ in performance.
+///
+/// No strategy is ahead at 100_000 elements, at 1_000_000 elements `FlatParallel` was about 15% faster than `Serial`.
+///
+/// The big number divisions of the first splits dominate, which no strategy parallelizes.
+/// The 4 thread pool on one core only adds scheduling overhead, the crossover on many core machines still needs to be profiled.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DecodeStrategy {
+    /// Recursively splits and divides on the current thread
+    Serial,
+    /// Recursively splits, both halves of large work items run in parallel (default)
+    #[default]
+    Recursive,
+    /// Splits everything into leaf work items first, then divides all leaves with one parallel iterator.
+    /// The splits themselves (the expensive big number divisions) run serially.
+    FlatParallel,
+    /// Splits the largest work item serially until there are `HYBRID_CHUNKS_PER_THREAD` work items per thread,
+    /// then hands them to one parallel iterator, which serially splits and divides each of them.
+    /// Gives rayon a flat pool of independent chunks to steal from, without the per leaf overhead of `FlatParallel`.
+    Hybrid,
+}
+
+/// Work items per rayon thread `DecodeStrategy::Hybrid` splits into before going parallel
+pub(crate) const HYBRID_CHUNKS_PER_THREAD: usize = 4;
+
+/// Decode settings for `big_lehmer::decode_with_config`.  
+/// The defaults are the settings of `big_lehmer::decode`.
+///
+/// # Examples
+/// ```
+/// use big_lehmer::{DecodeConfig, DecodeStrategy};
+/// let config = DecodeConfig::new()
+///     .with_strategy(DecodeStrategy::Recursive)
+///     .with_parallel_cutoff(10_000);
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode(&sequence).unwrap();
+/// let mut roundtrip = [0; 8];
+/// big_lehmer::decode_with_config(&encoded, &mut roundtrip, config).unwrap();
+/// assert_eq!(sequence, roundtrip);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct DecodeConfig {
+    strategy: DecodeStrategy,
+    parallel_cutoff: usize,
+    single_thread: bool,
+    naive_threshold: usize,
+    split_policy: fn(usize) -> usize,
+}
+
+/// Default of `DecodeConfig::with_naive_threshold`
+pub(crate) const NAIVE_THRESHOLD: usize = 4096;
+
+/// The split policies compare by function address
+impl PartialEq for DecodeConfig {
+    fn eq(&self, other: &Self) -> bool {
+        self.strategy == other.strategy
+            && self.parallel_cutoff == other.parallel_cutoff
+            && self.single_thread == other.single_thread
+            && self.naive_threshold == other.naive_threshold
+            && std::ptr::fn_addr_eq(self.split_policy, other.split_policy)
+    }
+}
+
+impl Eq for DecodeConfig {}
+
+impl Default for DecodeConfig {
+    fn default() -> Self {
+        Self {
+            strategy: DecodeStrategy::default(),
+            parallel_cutoff: 1000,
+            single_thread: false,
+            naive_threshold: NAIVE_THRESHOLD,
+            split_policy: DecodeConfig::default_split_policy,
+        }
+    }
+}
+
+impl DecodeConfig {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// How the factoradic digits are computed, see `big_lehmer::DecodeStrategy`
+    #[must_use]
+    pub fn with_strategy(mut self, strategy: DecodeStrategy) -> Self {
+        self.strategy = strategy;
+        self
+    }
+
+    /// Work items with at most this many elements are not split into parallel tasks by `DecodeStrategy::Recursive` (default 1000).
+    ///
+    /// The cutoff made no difference beyond noise, the join overhead itself is negligible.
+    /// The splits cut off only 1/4 or 1/16 of the bits (to keep the divisors small), so the two halves of a join are very unbalanced.
+    /// The large right halves form a serial chain of big number divisions, which bounds the speedup independent of the cutoff.
+    #[must_use]
+    pub fn with_parallel_cutoff(mut self, parallel_cutoff: usize) -> Self {
+        self.parallel_cutoff = parallel_cutoff;
+        self
+    }
+
+    /// Forces all strategies to run on the current thread at runtime, even when the `parallel` feature is enabled (default false).  
+    /// `DecodeStrategy::Recursive` behaves like `DecodeStrategy::Serial`,
+    /// `DecodeStrategy::FlatParallel` and `DecodeStrategy::Hybrid` still split first but divide in sequence.  
+    /// Allows comparing the parallel and serial decode in the same binary, without the scheduling noise of rayon.
+    /// Without the `parallel` feature everything runs on the current thread anyway.
+    #[must_use]
+    pub fn with_single_thread(mut self, single_thread: bool) -> Self {
+        self.single_thread = single_thread;
+        self
+    }
+
+    /// Permutations with at most this many elements are rebuilt from the digits with a sorted `Vec`
+    /// and `Vec::remove`, larger ones with the order statistic tree (default 4096).  
+    /// The `Vec` is O(N^2), but its removals are one contiguous `memmove`, which beats the tree walks for small N.
+    /// Only affects the step after the big number divisions, the output is always the same.
+    ///
+    /// The `Vec` was faster up to 4096 elements and slower from 8192 elements on, which sets the default.
+    ///
+    /// # Examples
+    /// ```
+    /// let config = big_lehmer::DecodeConfig::new().with_naive_threshold(0);
+    /// let encoded = big_lehmer::encode(&[2, 0, 1]).unwrap();
+    /// let mut results = [0; 3];
+    /// big_lehmer::decode_with_config(&encoded, &mut results, config).unwrap();
+    /// assert_eq!(results, [2, 0, 1]);
+    /// ```
+    #[must_use]
+    pub fn with_naive_threshold(mut self, naive_threshold: usize) -> Self {
+        self.naive_threshold = naive_threshold;
+        self
+    }
+
+    /// Bit length of the divisor each split cuts off a work item, given the bit length of its dividend
+    /// (default `DecodeConfig::default_split_policy`).  
+    /// Controls the shape of the recursion of every strategy, for profiling with your own data.
+    /// The digits are the same for every policy, only the amount and size of the big number divisions changes.
+    /// Values outside of `1..bit_length` are allowed, the split still makes progress by at least one element.
+    /// Policies that cut off almost none or almost all of the bits degrade to one big number division per element.  
+    /// Takes a function pointer to keep `DecodeConfig` `Copy`, closures that capture nothing coerce to it.
+    ///
+    /// # Examples
+    /// ```
+    /// let config = big_lehmer::DecodeConfig::new().with_split_policy(|bit_length| bit_length / 2);
+    /// let sequence: Vec<u32> = (0..1000).rev().collect();
+    /// let encoded = big_lehmer::encode(&sequence).unwrap();
+    /// let mut roundtrip = vec![0; 1000];
+    /// big_lehmer::decode_with_config(&encoded, &mut roundtrip, config).unwrap();
+    /// assert_eq!(sequence, roundtrip);
+    /// ```
+    #[must_use]
+    pub fn with_split_policy(mut self, split_policy: fn(usize) -> usize) -> Self {
+        self.split_policy = split_policy;
+        self
+    }
+
+    /// The default split policy: cuts off 1/4 of the bits, 1/16 from 20_000 bits on.  
+    /// Large divisions cost O(M * N), so the divisor is kept small compared to the dividend.
+    ///
+    /// # Examples
+    /// ```
+    /// use big_lehmer::DecodeConfig;
+    /// assert_eq!(DecodeConfig::default_split_policy(1000), 250);
+    /// assert_eq!(DecodeConfig::default_split_policy(32_000), 2000);
+    /// ```
+    #[must_use]
+    pub fn default_split_policy(bit_length: usize) -> usize {
+        if bit_length >= 20_000 {
+            bit_length / 16
+        } else {
+            bit_length / 4
+        }
+    }
+
+    #[must_use]
+    pub fn strategy(&self) -> DecodeStrategy {
+        self.strategy
+    }
+
+    #[must_use]
+    pub fn naive_threshold(&self) -> usize {
+        self.naive_threshold
+    }
+
+    #[must_use]
+    pub fn split_policy(&self) -> fn(usize) -> usize {
+        self.split_policy
+    }
+
+    #[must_use]
+    pub fn parallel_cutoff(&self) -> usize {
+        self.parallel_cutoff
+    }
+
+    #[must_use]
+    pub fn single_thread(&self) -> bool {
+        self.single_thread
+    }
+}
+
+/// Reusable buffers for `big_lehmer::decode_with_scratch`.  
+/// Decoding many codes of the same length with one scratch avoids the per call allocations.
+#[derive(Debug, Default)]
+pub struct DecodeScratch {
+    pub(crate) remainders: Vec<Option<NonZeroU32>>,
+    pub(crate) tree: OrderStatisticTree,
+    /// Pristine tree of the last length, `tree` is refilled from it while the length stays the same
+    template: Option<OrderStatisticTree>,
+}
+
+impl DecodeScratch {
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resets `tree` to `element_count` elements, copies the template instead of recomputing the node counts
+    /// when the length did not change since the last call
+    pub(crate) fn reset_tree(&mut self, element_count: u32) -> Result<(), Error> {
+        let template = match &mut self.template {
+            Some(template) if template.element_count() == element_count => template,
+            template => template.insert(OrderStatisticTree::new(element_count)?),
+        };
+        self.tree.reset_from(template);
+        Ok(())
+    }
+}
+
+/// The tree wipes itself, the remainders are the digits
+#[cfg(feature = "zeroize")]
+impl Drop for DecodeScratch {
+    fn drop(&mut self) {
+        // Also wipes the spare capacity, a shrinking decode leaves older digits behind the length
+        self.remainders.resize(self.remainders.capacity(), None);
+        for remainder in &mut self.remainders {
+            // Safety: `remainder` is a valid, aligned and initialized element of the vector.
+            // Volatile, so the writes are not optimized away before the free
+            unsafe { std::ptr::write_volatile(remainder, None) };
+        }
+        std::sync::atomic::compiler_fence(std::sync::atomic::Ordering::SeqCst);
+    }
+}
+
 #[derive(Debug)]
 pub(crate) struct WorkItem<'a> {
     pub(crate) dividend: UBig,
@@ -9,13 +260,19 @@ pub(crate) struct WorkItem<'a> {
     // None means an error has occurred
     // Every remainder is +1 to fit in NonZero
     pub(crate) remainders: &'a mut [Option<NonZeroU32>],
+    /// Advances by the digits of every finished leaf
+    pub(crate) progress: Option<&'a ProgressPhase<'a>>,
 }
 
 type DivideType = u64;
 
 /// Final step that does the actual divisions on u64
 pub(crate) fn divide(work: WorkItem) {
-    let mut dividend = DivideType::try_from(work.dividend).unwrap();
+    let Ok(mut dividend) = DivideType::try_from(work.dividend) else {
+        // Only the last work item can be left with a big dividend, when the code has more digits than element_count
+        *work.remainders.last_mut().unwrap() = None;
+        return;
+    };
     for (index, r) in work.remainders.iter_mut().enumerate() {
         let divisor = DivideType::from(work.start_index) + DivideType::try_from(index).unwrap();
         *r = NonZeroU32::new(u32::try_from(dividend % divisor).unwrap() + 1);
@@ -28,30 +285,292 @@ pub(crate) fn divide(work: WorkItem) {
     }
 }
 
-/// Splits the work items into two smaller if it makes sense
+/// Same result as `divide`, but extracts two digits per division of the dividend.  
+/// The divisors are consecutive, so `dividend % (d * (d + 1))` holds both digits of `d` and `d + 1`,
+/// they are split by a second division of the small remainder that does not depend on the dividend chain.
+/// Halves the chain of dependent divisions on the dividend.
+///
+/// Pairing divisors whose product exceeds 32 bit was slower, those are divided one at a time like `divide`.
+/// In a whole decode the leaves are a small part next to the big divisions and the tree.
+pub(crate) fn divide_batch(work: WorkItem) {
+    if let Some(progress) = work.progress {
+        if progress.cancelled() {
+            return;
+        }
+        progress.advance(work.remainders.len() as u64);
+    }
+    let Ok(mut dividend) = DivideType::try_from(work.dividend) else {
+        *work.remainders.last_mut().unwrap() = None;
+        return;
+    };
+    let start = DivideType::from(work.start_index);
+    let len = DivideType::try_from(work.remainders.len()).unwrap();
+    let mut pairs = work.remainders.chunks_exact_mut(2);
+    for (pair_index, pair) in pairs.by_ref().enumerate() {
+        let first = start + 2 * DivideType::try_from(pair_index).unwrap();
+        let second = first + 1;
+        let Some(divisor) = first
+            .checked_mul(second)
+            .filter(|&divisor| divisor <= DivideType::from(u32::MAX))
+        else {
+            // Dividing by a 64 bit divisor is slower than two divisions by 32 bit divisors
+            for (r, divisor) in pair.iter_mut().zip([first, second]) {
+                *r = NonZeroU32::new(u32::try_from(dividend % divisor).unwrap() + 1);
+                dividend /= divisor;
+            }
+            continue;
+        };
+        let both = dividend % divisor;
+        dividend /= divisor;
+        pair[0] = NonZeroU32::new(u32::try_from(both % first).unwrap() + 1);
+        pair[1] = NonZeroU32::new(u32::try_from(both / first).unwrap() + 1);
+    }
+    if let [r] = pairs.into_remainder() {
+        let divisor = start + len - 1;
+        *r = NonZeroU32::new(u32::try_from(dividend % divisor).unwrap() + 1);
+        dividend /= divisor;
+    }
+
+    if dividend != 0 {
+        *work.remainders.last_mut().unwrap() = None;
+    }
+}
+
+/// Computes the factoradic digits of `input` for `element_count` elements into `remainders`
+pub(crate) fn compute_remainders(
+    input: UBig,
+    remainders: &mut Vec<Option<NonZeroU32>>,
+    element_count: usize,
+    config: DecodeConfig,
+) {
+    remainders.clear();
+    remainders.resize(element_count, None);
+    compute_remainders_in(input, remainders, config);
+}
+
+/// Same as `compute_remainders`, but into an existing slice of `element_count` slots that has to be all `None`
+pub(crate) fn compute_remainders_in(
+    input: UBig,
+    remainders: &mut [Option<NonZeroU32>],
+    config: DecodeConfig,
+) {
+    compute_remainders_with_progress(input, remainders, config, None);
+}
+
+/// `compute_remainders_in`, `progress` advances by the digits of every finished leaf work item
+pub(crate) fn compute_remainders_with_progress(
+    input: UBig,
+    remainders: &mut [Option<NonZeroU32>],
+    config: DecodeConfig,
+    progress: Option<&ProgressPhase>,
+) {
+    let work = WorkItem {
+        dividend: input,
+        start_index: 2,
+        remainders,
+        progress,
+    };
+    divide_work(work, config);
+}
+
+/// Mixed radix digits of `input` for the ascending radices `start_index..`, one slot per radix, same encoding as the remainders.  
+/// The last slot catches the leftover: it is `Some(NonZeroU32::MIN)` (digit 0) exactly when `input` fits into the other slots
+pub(crate) fn compute_digits_from(
+    input: UBig,
+    start_index: u32,
+    remainders: &mut [Option<NonZeroU32>],
+    config: DecodeConfig,
+) {
+    let work = WorkItem {
+        dividend: input,
+        start_index,
+        remainders,
+        progress: None,
+    };
+    divide_work(work, config);
+}
+
+fn divide_work(work: WorkItem, config: DecodeConfig) {
+    let policy = config.split_policy;
+    match config.strategy {
+        DecodeStrategy::Serial => serial_divide(work, policy),
+        DecodeStrategy::Recursive if config.single_thread => serial_divide(work, policy),
+        DecodeStrategy::Recursive => recursive_divide(work, config.parallel_cutoff, policy),
+        DecodeStrategy::FlatParallel => parallel_divide(work, config.single_thread, policy),
+        DecodeStrategy::Hybrid => hybrid_divide(work, config.single_thread, policy),
+    }
+}
+
+/// Checks that the digits computed by `compute_remainders` cover the whole code.  
+/// `compute_remainders` computes one more digit than needed, it is zero exactly when the code is smaller than `element_count!`.
+/// Otherwise the code was created from more elements.
+/// A code created from fewer elements can not be detected, it is also a valid code of `element_count` elements.
+pub(crate) fn check_element_count(
+    remainders: &[Option<NonZeroU32>],
+    code_bits: usize,
+) -> Result<(), Error> {
+    let got = remainders.len();
+    match remainders.last() {
+        // Only the empty permutation has no digits, its only code is zero
+        None if code_bits == 0 => Ok(()),
+        None => Err(Error::ElementCountMismatch {
+            expected: min_element_count(code_bits).max(2),
+            got,
+        }),
+        Some(&Some(NonZeroU32::MIN)) => Ok(()),
+        // The code is smaller than (element_count + 1)!
+        Some(Some(_)) => Err(Error::ElementCountMismatch {
+            expected: got + 1,
+            got,
+        }),
+        Some(None) => Err(Error::ElementCountMismatch {
+            expected: min_element_count(code_bits).max(got + 2),
+            got,
+        }),
+    }
+}
+
+/// Decode side of the shared shortcut for 0 and 1 elements, see `validation::trivial_permutation`.  
+/// `Some` when `results` is trivial: the only valid code is zero, which decodes to `[]` / `[0]`.
+pub(crate) fn trivial_decode(code_bits: usize, results: &mut [u32]) -> Option<Result<(), Error>> {
+    if results.len() > 1 {
+        return None;
+    }
+    if code_bits != 0 {
+        return Some(Err(Error::ElementCountMismatch {
+            expected: min_element_count(code_bits).max(2),
+            got: results.len(),
+        }));
+    }
+    results.fill(0);
+    Some(Ok(()))
+}
+
+/// Lower bound of the element count of a code with `code_bits` bits.  
+/// A code with `code_bits` bits is at least `2^(code_bits - 1)`, so it needs `log2(N!) > code_bits - 1`
+#[allow(clippy::cast_precision_loss)]
+pub(crate) fn min_element_count(code_bits: usize) -> usize {
+    let target = code_bits.saturating_sub(1) as f64;
+    let mut bits = 0.0;
+    let mut element_count = 1;
+    while bits <= target {
+        element_count += 1;
+        bits += f64::log2(element_count as f64);
+    }
+    // Float inaccuracy, stay on the conservative side
+    element_count - 1
+}
+
+/// Turns the digits of `compute_remainders` into the permutation, `tree` has to be reset to `results.len()` elements.  
+/// Permutations up to `NAIVE_THRESHOLD` elements use the naive `Vec` instead of the tree.
+pub(crate) fn remainders_to_permutation(
+    remainders: &[Option<NonZeroU32>],
+    results: &mut [u32],
+    tree: &mut OrderStatisticTree,
+) -> Result<(), Error> {
+    remainders_to_permutation_with(remainders, results, tree, NAIVE_THRESHOLD)
+}
+
+/// Same as `remainders_to_permutation`, with the threshold of `DecodeConfig::with_naive_threshold`
+pub(crate) fn remainders_to_permutation_with(
+    remainders: &[Option<NonZeroU32>],
+    results: &mut [u32],
+    tree: &mut OrderStatisticTree,
+    naive_threshold: usize,
+) -> Result<(), Error> {
+    if results.len() <= naive_threshold {
+        return remainders_to_permutation_naive(remainders, results);
+    }
+    let Some(last) = results.len().checked_sub(1) else {
+        return Ok(());
+    };
+    for (index, &t) in remainders[0..last].iter().rev().enumerate() {
+        if let Some(t) = t {
+            results[index] = tree.select_and_remove(t.get() - 1);
+        } else {
+            return Err(Error::Decode);
+        }
+    }
+    results[last] = tree.select_and_remove(0);
+
+    Ok(())
+}
+
+/// Same result as the tree walk of `remainders_to_permutation`, with a sorted `Vec` of the numbers left
+fn remainders_to_permutation_naive(
+    remainders: &[Option<NonZeroU32>],
+    results: &mut [u32],
+) -> Result<(), Error> {
+    let Some(last) = results.len().checked_sub(1) else {
+        return Ok(());
+    };
+    // results has at most u32::MAX elements, checked by the callers
+    let mut left: Vec<u32> = (0..=u32::try_from(last).unwrap()).collect();
+    for (index, &t) in remainders[0..last].iter().rev().enumerate() {
+        let digit = t.ok_or(Error::Decode)?.get() - 1;
+        if digit as usize >= left.len() {
+            return Err(Error::Decode);
+        }
+        results[index] = left.remove(digit as usize);
+    }
+    results[last] = left[0];
+    Ok(())
+}
+
+/// Lehmer digits of a code of `element_count` elements, most significant first like `big_lehmer::to_factoradic`
+pub(crate) fn code_digits(input: UBig, element_count: usize) -> Result<Vec<u32>, Error> {
+    let code_bits = input.bit_len();
+    let mut remainders = vec![];
+    compute_remainders(
+        input,
+        &mut remainders,
+        element_count,
+        DecodeConfig::default(),
+    );
+    check_element_count(&remainders, code_bits)?;
+    if element_count == 0 {
+        return Ok(vec![]);
+    }
+    remainders[..element_count - 1]
+        .iter()
+        .rev()
+        .map(|t| t.map(|t| t.get() - 1).ok_or(Error::Decode))
+        .chain(std::iter::once(Ok(0)))
+        .collect()
+}
+
+/// Splits the work items into two smaller if it makes sense, the divisor has about `policy(bit_length)` bits
 /// Second work item is None if the work item can be passed to the final division step
-pub(crate) fn split(work: WorkItem) -> (WorkItem, Option<WorkItem>) {
+pub(crate) fn split(work: WorkItem, policy: fn(usize) -> usize) -> (WorkItem, Option<WorkItem>) {
     let length = work.dividend.bit_len();
-    if length <= usize::try_from(DivideType::BITS).unwrap() {
+    // A cancelled work item goes straight to `divide_batch`, which skips it
+    if length <= usize::try_from(DivideType::BITS).unwrap()
+        || work.remainders.len() == 1
+        || work.progress.is_some_and(ProgressPhase::cancelled)
+    {
         return (work, None);
     }
-    // Since large divisions have MxN cost we split in a way to keep the divisor smaller
-    let split_length = if length >= 20_000 {
-        length / 16
-    } else {
-        length / 4
-    };
+    let split_length = policy(length);
 
     // Compute part factorial until we are larger than length
-    let mut split_index = work.start_index;
-    let mut factorial = UBig::ONE;
-    loop {
-        factorial *= split_index;
-        split_index += 1;
-        if factorial.bit_len() >= split_length {
-            break;
+    // The right work item keeps at least one remainder, which catches the leftover of too large codes
+    let max_split_index = work.start_index + u32::try_from(work.remainders.len() - 1).unwrap();
+    let (split_index, factorial) = if work.start_index == 2 {
+        // The product 2..split_index is a plain factorial, the left work items always start at 2
+        let (n, factorial) = factorial_with_bits(split_length, max_split_index - 1);
+        (n + 1, factorial)
+    } else {
+        let mut split_index = work.start_index;
+        let mut factorial = UBig::ONE;
+        loop {
+            factorial *= split_index;
+            split_index += 1;
+            if factorial.bit_len() >= split_length || split_index == max_split_index {
+                break;
+            }
         }
-    }
+        (split_index, factorial)
+    };
 
     let (quotient, remain) = work.dividend.div_rem(factorial);
 
@@ -64,87 +583,241 @@ pub(crate) fn split(work: WorkItem) -> (WorkItem, Option<WorkItem>) {
             dividend: remain,
             start_index: work.start_index,
             remainders: left,
+            progress: work.progress,
         },
         Some(WorkItem {
             dividend: quotient,
             start_index: split_index,
             remainders: right,
+            progress: work.progress,
         }),
     )
 }
 
 /// Recursively splits the work until the resulting work items can be divided
-pub(crate) fn recursive_split_divide(work: WorkItem) {
-    let len = work.remainders.len();
-    let (left, right) = split(work);
+/// Work items with more than `parallel_cutoff` elements divide both halves in parallel
+#[cfg(all(feature = "parallel", not(feature = "wasm")))]
+pub(crate) fn recursive_divide(work: WorkItem, parallel_cutoff: usize, policy: fn(usize) -> usize) {
+    // Speedup for parallel is abysmal :(
+    // The split is very unbalanced, the right half keeps most of the bits and forms a serial chain of big divisions
+    rayon::scope(|scope| {
+        let mut work = work;
+        // Spawns the smaller half and loops on the larger one instead of joining both,
+        // so a policy that cuts off tiny divisors does not nest one join per split
+        while work.remainders.len() > parallel_cutoff {
+            let (smaller, larger) = match split(work, policy) {
+                (left, None) => return divide_batch(left),
+                (left, Some(right)) => by_len(left, right),
+            };
+            scope.spawn(move |_| recursive_divide(smaller, parallel_cutoff, policy));
+            work = larger;
+        }
+        serial_divide(work, policy);
+    });
+}
 
-    if right.is_none() {
-        divide(left);
-        return;
+/// Without the parallel path the recursive strategy is the serial one
+#[cfg(not(all(feature = "parallel", not(feature = "wasm"))))]
+pub(crate) fn recursive_divide(work: WorkItem, parallel_cutoff: usize, policy: fn(usize) -> usize) {
+    let _ = parallel_cutoff;
+    serial_divide(work, policy);
+}
+
+/// Recursively splits the work until the resulting work items can be divided, all on the current thread.  
+/// Recurses into the half with fewer elements and loops on the other one,
+/// so the depth stays O(log N) for every split policy.
+pub(crate) fn serial_divide(work: WorkItem, policy: fn(usize) -> usize) {
+    let mut work = work;
+    loop {
+        let (smaller, larger) = match split(work, policy) {
+            (left, None) => return divide_batch(left),
+            (left, Some(right)) => by_len(left, right),
+        };
+        serial_divide(smaller, policy);
+        work = larger;
     }
+}
 
-    // Speedup for parallel is abysmal :(
-    if len > 1000 {
-        rayon::join(
-            || recursive_split_divide(left),
-            || recursive_split_divide(right.unwrap()),
-        );
+/// The two work items of a split, the one with fewer elements first
+fn by_len<'a>(left: WorkItem<'a>, right: WorkItem<'a>) -> (WorkItem<'a>, WorkItem<'a>) {
+    if left.remainders.len() <= right.remainders.len() {
+        (left, right)
     } else {
-        recursive_split_divide(right.unwrap());
-        recursive_split_divide(left);
+        (right, left)
     }
 }
 
-/// Naive approach would be to create a list from 0 to N and then repeatedly remove elements from it
-/// Very slightly faster than the naive approach
-/// Basically a tree that stores prim counts and is adjusted while fetching a number
-#[derive(Debug)]
-pub(crate) struct DecodeAS {
-    // Store number of primitives of the left subtree
-    tree: Vec<u32>,
-}
-impl DecodeAS {
-    pub(crate) fn new(element_count: u32) -> Self {
-        let len = element_count.next_power_of_two();
-        let nodes = (0..len)
-            .map(|i| {
-                if i == 0 {
-                    return 1;
-                }
-                let height = i.trailing_zeros();
-                1u32 << height
-            })
-            .collect();
-        Self { tree: nodes }
+/// Splits all work first, then divides the leaves in parallel, or in sequence when `single_thread` is set
+pub(crate) fn parallel_divide(work: WorkItem, single_thread: bool, policy: fn(usize) -> usize) {
+    let mut pending = vec![work];
+    let mut leaves = vec![];
+    while let Some(work) = pending.pop() {
+        match split(work, policy) {
+            (left, None) => leaves.push(left),
+            (left, Some(right)) => {
+                pending.push(left);
+                pending.push(right);
+            }
+        }
     }
 
-    pub(crate) fn remove(&mut self, number: u32) -> u32 {
-        let length = u32::try_from(self.tree.len()).expect("Sequence must fit in u32");
-        let mut left_count = 0;
-        let mut node_id = length / 2;
-        let mut jump = length / 4;
+    #[cfg(all(feature = "parallel", not(feature = "wasm")))]
+    if !single_thread {
+        leaves.into_par_iter().for_each(divide_batch);
+        return;
+    }
+    #[cfg(not(all(feature = "parallel", not(feature = "wasm"))))]
+    let _ = single_thread;
+    leaves.into_iter().for_each(divide_batch);
+}
 
-        loop {
-            let node = &mut self.tree[node_id as usize];
-            if number >= (*node + left_count) {
-                // go right
-                left_count += *node;
-                node_id += jump;
-                if jump == 0 {
-                    break;
-                }
-            } else {
-                // go left
-                *node -= 1;
-                node_id -= jump;
-                if jump == 0 {
-                    node_id -= 1;
-                    break;
+/// Splits the work item with the longest dividend until there are `HYBRID_CHUNKS_PER_THREAD` items per thread,
+/// then divides the items in parallel, or in sequence when `single_thread` is set
+pub(crate) fn hybrid_divide(work: WorkItem, single_thread: bool, policy: fn(usize) -> usize) {
+    #[cfg(all(feature = "parallel", not(feature = "wasm")))]
+    let threads = if single_thread {
+        1
+    } else {
+        rayon::current_num_threads()
+    };
+    #[cfg(not(all(feature = "parallel", not(feature = "wasm"))))]
+    let threads = 1;
+    let target = threads * HYBRID_CHUNKS_PER_THREAD;
+
+    let mut chunks = vec![work];
+    let mut leaves = vec![];
+    while chunks.len() + leaves.len() < target {
+        // The splits are unbalanced, the longest dividend holds most of the remaining big number work
+        let Some((longest, _)) = chunks
+            .iter()
+            .enumerate()
+            .max_by_key(|(_, work)| work.dividend.bit_len())
+        else {
+            break;
+        };
+        match split(chunks.swap_remove(longest), policy) {
+            (left, None) => leaves.push(left),
+            (left, Some(right)) => {
+                chunks.push(left);
+                chunks.push(right);
+            }
+        }
+    }
+    leaves.into_iter().for_each(divide_batch);
+
+    #[cfg(all(feature = "parallel", not(feature = "wasm")))]
+    if !single_thread {
+        chunks
+            .into_par_iter()
+            .for_each(|work| serial_divide(work, policy));
+        return;
+    }
+    #[cfg(not(all(feature = "parallel", not(feature = "wasm"))))]
+    let _ = single_thread;
+    chunks
+        .into_iter()
+        .for_each(|work| serial_divide(work, policy));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::{Rng, SeedableRng};
+
+    fn leaf_digits(
+        divide: fn(WorkItem),
+        dividend: &UBig,
+        start_index: u32,
+        len: usize,
+    ) -> Vec<Option<NonZeroU32>> {
+        let mut remainders = vec![None; len];
+        divide(WorkItem {
+            dividend: dividend.clone(),
+            start_index,
+            remainders: &mut remainders,
+            progress: None,
+        });
+        remainders
+    }
+
+    #[test]
+    fn test_divide_batch_matches_divide() {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(848);
+        for start_index in [2, 3, 20, 1000, 65_534, 65_535, 100_000, u32::MAX - 2] {
+            for len in 0..=21 {
+                for dividend in [0, 1, u64::MAX, rng.gen(), rng.gen::<u64>() >> 40] {
+                    if len == 0 && dividend != 0 {
+                        continue;
+                    }
+                    let dividend = UBig::from(dividend);
+                    assert_eq!(
+                        leaf_digits(divide_batch, &dividend, start_index, len),
+                        leaf_digits(divide, &dividend, start_index, len),
+                        "{start_index} {len} {dividend}"
+                    );
                 }
             }
+        }
+        // Dividend too large for the leaf
+        let big = UBig::from(u64::MAX) * UBig::from(3u8);
+        assert_eq!(leaf_digits(divide_batch, &big, 2, 4).last(), Some(&None));
+    }
 
-            jump /= 2;
+    #[test]
+    fn test_hybrid_divide_matches_serial() {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(886);
+        for len in [1, 2, 3, 30, 700, 5000] {
+            let bytes: Vec<u8> = (0..crate::get_encode_size(len))
+                .map(|_| rng.gen())
+                .collect();
+            let dividend = UBig::from_le_bytes(&bytes);
+            let len = usize::try_from(len).unwrap();
+            let expected = leaf_digits(
+                |work| serial_divide(work, DecodeConfig::default_split_policy),
+                &dividend,
+                2,
+                len,
+            );
+            let parallel = leaf_digits(
+                |work| hybrid_divide(work, false, DecodeConfig::default_split_policy),
+                &dividend,
+                2,
+                len,
+            );
+            assert_eq!(parallel, expected, "{len}");
+            let single = leaf_digits(
+                |work| hybrid_divide(work, true, DecodeConfig::default_split_policy),
+                &dividend,
+                2,
+                len,
+            );
+            assert_eq!(single, expected, "{len}");
+        }
+    }
+
+    #[test]
+    fn test_naive_matches_tree() {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(891);
+        for len in [0u32, 1, 2, 3, 63, 64, 65, 500] {
+            // Digit slot i belongs to the divisor i + 2, the last slot is the leftover
+            let mut remainders: Vec<Option<NonZeroU32>> = (0..len)
+                .map(|i| NonZeroU32::new(rng.gen_range(0..i + 2) + 1))
+                .collect();
+            let mut naive = vec![0; len as usize];
+            let mut tree = vec![0; len as usize];
+            let mut t = OrderStatisticTree::new(len).unwrap();
+            remainders_to_permutation_with(&remainders, &mut naive, &mut t, usize::MAX).unwrap();
+            t.reset(len).unwrap();
+            remainders_to_permutation_with(&remainders, &mut tree, &mut t, 0).unwrap();
+            assert_eq!(naive, tree);
+
+            if len > 1 {
+                remainders[0] = None;
+                assert!(matches!(
+                    remainders_to_permutation_with(&remainders, &mut naive, &mut t, usize::MAX),
+                    Err(Error::Decode)
+                ));
+            }
         }
-        node_id
     }
 }
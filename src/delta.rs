@@ -0,0 +1,115 @@
+use dashu::{
+    base::Sign,
+    integer::{IBig, UBig},
+};
+
+use crate::{decode_from_ubig, encode_to_ubig, error::Error};
+
+const POSITIVE: u8 = 0;
+const NEGATIVE: u8 = 1;
+
+/// Sign byte (`0` positive, `1` negative) followed by the little endian magnitude
+pub(crate) fn signed_to_bytes(value: IBig) -> Box<[u8]> {
+    let (sign, magnitude) = value.into_parts();
+    let mut result = vec![match sign {
+        Sign::Positive => POSITIVE,
+        Sign::Negative => NEGATIVE,
+    }];
+    result.extend_from_slice(&magnitude.to_le_bytes());
+    result.into_boxed_slice()
+}
+
+/// Inverse of `signed_to_bytes`, also accepts a negative zero.  
+/// `Error::Decode` when the sign byte is missing or invalid.
+pub(crate) fn signed_from_bytes(bytes: &[u8]) -> Result<IBig, Error> {
+    let (&sign, magnitude) = bytes.split_first().ok_or(Error::Decode)?;
+    let sign = match sign {
+        POSITIVE => Sign::Positive,
+        NEGATIVE => Sign::Negative,
+        _ => return Err(Error::Decode),
+    };
+    Ok(IBig::from_parts(sign, UBig::from_le_bytes(magnitude)))
+}
+
+/// `rank + delta`, None when it goes below rank 0 instead of wrapping around.  
+/// Shared by the delta codes and the rank navigation, the upper bound depends on the caller.
+pub(crate) fn offset_rank(rank: UBig, delta: IBig) -> Option<UBig> {
+    UBig::try_from(IBig::from(rank) + delta).ok()
+}
+
+/// Encodes `target` relative to `base` as the rank difference `rank(target) - rank(base)`.  
+/// Similar permutations (e.g. successive states of a sorted list) produce small deltas and therefore small outputs.
+///
+/// Layout: one sign byte (`0` positive, `1` negative) followed by the little endian magnitude.
+///
+/// # Examples
+/// ```
+/// let base = [0, 1, 2, 3, 4, 5, 6, 7];
+/// let target = [0, 1, 2, 3, 4, 5, 7, 6];
+/// let delta = big_lehmer::encode_delta(&base, &target).unwrap();
+/// assert_eq!(*delta, [0, 1]);
+/// assert_eq!(big_lehmer::decode_delta(&base, &delta).unwrap(), target);
+/// ```
+///
+/// # Errors
+///
+/// `Error::LengthMismatch` when `base` and `target` have different lengths.  
+/// Same errors as `big_lehmer::encode` for both permutations.
+pub fn encode_delta(base: &[u32], target: &[u32]) -> Result<Box<[u8]>, Error> {
+    if base.len() != target.len() {
+        return Err(Error::LengthMismatch {
+            expected: base.len(),
+            got: target.len(),
+        });
+    }
+    let base = encode_to_ubig(base)?;
+    let target = encode_to_ubig(target)?;
+    Ok(signed_to_bytes(IBig::from(target) - IBig::from(base)))
+}
+
+/// Reconstructs the target permutation from `base` and a delta created by `big_lehmer::encode_delta`.
+///
+/// # Errors
+///
+/// `Error::Decode` when the delta is malformed or moves the rank out of the permutation space.  
+/// Same errors as `big_lehmer::encode` for `base`.
+pub fn decode_delta(base: &[u32], delta: &[u8]) -> Result<Vec<u32>, Error> {
+    let delta = signed_from_bytes(delta)?;
+    let base_rank = encode_to_ubig(base)?;
+    let rank = offset_rank(base_rank, delta).ok_or(Error::Decode)?;
+
+    let mut results = vec![0; base.len()];
+    decode_from_ubig(rank, &mut results)?;
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_signed_bytes_roundtrip() {
+        for value in [0i64, 1, -1, 255, -256, i64::MAX, i64::MIN] {
+            let value = IBig::from(value);
+            let bytes = signed_to_bytes(value.clone());
+            assert_eq!(bytes[0], u8::from(value < IBig::ZERO));
+            assert_eq!(signed_from_bytes(&bytes).unwrap(), value);
+        }
+        assert_eq!(*signed_to_bytes(IBig::ZERO), [POSITIVE]);
+        assert_eq!(signed_from_bytes(&[NEGATIVE]).unwrap(), IBig::ZERO);
+        assert!(matches!(signed_from_bytes(&[]), Err(Error::Decode)));
+        assert!(matches!(signed_from_bytes(&[2, 1]), Err(Error::Decode)));
+    }
+
+    #[test]
+    fn test_offset_rank_crossing_zero() {
+        let rank = UBig::from(3u8);
+        assert_eq!(offset_rank(rank.clone(), IBig::from(-3)), Some(UBig::ZERO));
+        assert_eq!(offset_rank(rank.clone(), IBig::from(-4)), None);
+        assert_eq!(
+            offset_rank(rank.clone(), IBig::from(4)),
+            Some(UBig::from(7u8))
+        );
+        assert_eq!(offset_rank(UBig::ZERO, IBig::ZERO), Some(UBig::ZERO));
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{decode, error::Error};
+
+/// Unsigned integer types a permutation can be decoded into, see `big_lehmer::decode_as`.  
+/// Implemented for `u8`, `u16`, `u32`, `u64` and `usize`.
+pub trait Element: Copy {
+    /// Largest number the type can hold
+    const MAX: u64;
+    /// Only called with numbers up to `Self::MAX`
+    fn from_u32(number: u32) -> Self;
+}
+
+macro_rules! impl_element {
+    ($($ty:ty),*) => {$(
+        impl Element for $ty {
+            const MAX: u64 = <$ty>::MAX as u64;
+
+            #[allow(clippy::cast_possible_truncation)]
+            fn from_u32(number: u32) -> Self {
+                number as $ty
+            }
+        }
+    )*};
+}
+
+impl_element!(u8, u16, u32, u64, usize);
+
+/// Same as `big_lehmer::decode`, but writes the permutation into any `big_lehmer::Element` slice,
+/// without decoding into `u32` and casting on the caller side.  
+/// Internally the tree and the digits stay `u32`, the numbers are converted at the end through a `u32` buffer of `results.len()`.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[7, 2, 0, 6, 5, 1, 4, 3]).unwrap();
+/// let mut small = [0u8; 8];
+/// big_lehmer::decode_as(&encoded, &mut small).unwrap();
+/// assert_eq!(small, [7, 2, 0, 6, 5, 1, 4, 3]);
+///
+/// let mut wide = [0u64; 8];
+/// big_lehmer::decode_as(&encoded, &mut wide).unwrap();
+/// assert_eq!(wide, [7, 2, 0, 6, 5, 1, 4, 3]);
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::ElementOverflow` when the numbers `0..results.len()` do not fit into `E`, for example more than 256 `u8`.  
+/// Same as `big_lehmer::decode`, including `Error::SequenceToLong` for more than `u32::MAX` elements
+pub fn decode_as<E: Element>(encoded: &[u8], results: &mut [E]) -> Result<(), Error> {
+    u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+        element_count: results.len(),
+    })?;
+    if results.len() as u64 > E::MAX.saturating_add(1) {
+        return Err(Error::ElementOverflow {
+            element_count: results.len(),
+            max: E::MAX,
+        });
+    }
+    let mut numbers = vec![0; results.len()];
+    decode(encoded, &mut numbers)?;
+    for (result, number) in results.iter_mut().zip(numbers) {
+        *result = E::from_u32(number);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_as_types() {
+        let sequence: Vec<u32> = crate::tests::shuffled(256, 864);
+        let encoded = crate::encode(&sequence).unwrap();
+        let mut small = vec![0u8; 256];
+        decode_as(&encoded, &mut small).unwrap();
+        assert!(small
+            .iter()
+            .zip(&sequence)
+            .all(|(&a, &b)| u32::from(a) == b));
+        let mut medium = vec![0u16; 256];
+        decode_as(&encoded, &mut medium).unwrap();
+        assert!(medium
+            .iter()
+            .zip(&sequence)
+            .all(|(&a, &b)| u32::from(a) == b));
+        let mut wide = vec![0usize; 256];
+        decode_as(&encoded, &mut wide).unwrap();
+        assert!(wide.iter().zip(&sequence).all(|(&a, &b)| a == b as usize));
+    }
+
+    #[test]
+    fn test_decode_as_overflow() {
+        let sequence: Vec<u32> = (0..257).rev().collect();
+        let encoded = crate::encode(&sequence).unwrap();
+        assert!(matches!(
+            decode_as(&encoded, &mut [0u8; 257]),
+            Err(Error::ElementOverflow {
+                element_count: 257,
+                max: 255
+            })
+        ));
+        let mut medium = [0u16; 257];
+        decode_as(&encoded, &mut medium).unwrap();
+        assert_eq!(medium[0], 256);
+        // Decode errors pass through
+        assert!(matches!(
+            decode_as(&encoded, &mut [0u8; 4]),
+            Err(Error::ElementCountMismatch { got: 4, .. })
+        ));
+    }
+}
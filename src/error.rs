@@ -3,10 +3,33 @@ use std::fmt::{Display, Formatter};
 
 use crate::get_encode_size;
 
-#[derive(Debug)]
+/// `Clone` and `PartialEq` compare the payloads, so tests can `assert_eq!` on the expected error
+/// and errors can be stored and deduplicated.
+///
+/// New variants are added in minor releases, so a `match` outside of this crate needs a `_ =>` arm.
+/// `Error::kind` and the `is_*` predicates cover the common matches without naming every variant.
+///
+/// ```
+/// use big_lehmer::Error;
+/// let message = match big_lehmer::encode(&[0, 0]) {
+///     Ok(_) => "ok",
+///     Err(Error::ValidationDuplicateNumber { .. }) => "duplicate",
+///     Err(error) if error.is_validation() => "invalid",
+///     Err(_) => "other",
+/// };
+/// assert_eq!(message, "duplicate");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
 pub enum Error {
-    ValidationDuplicateNumber,
-    ValidationOutOfRange,
+    ValidationDuplicateNumber {
+        index: usize,
+        number: u32,
+    },
+    ValidationOutOfRange {
+        index: usize,
+        number: u32,
+    },
     SequenceToLong {
         element_count: usize,
     },
@@ -15,33 +38,255 @@ pub enum Error {
         element_count: u32,
     },
     Decode,
+    ElementCountMismatch {
+        expected: usize,
+        got: usize,
+    },
+    PositionOutOfRange {
+        position: u32,
+        element_count: u32,
+    },
+    LengthMismatch {
+        expected: usize,
+        got: usize,
+    },
+    PositionsNotSorted {
+        index: usize,
+    },
+    Io {
+        kind: std::io::ErrorKind,
+        message: String,
+    },
+    InvalidHeader,
+    ChecksumMismatch,
+    UnsupportedVersion {
+        version: u8,
+    },
+    InvalidRadix {
+        radix: u32,
+    },
+    InvalidDigit {
+        index: usize,
+        digit: u32,
+        radix: u32,
+    },
+    Input {
+        index: usize,
+        message: String,
+    },
+    RankOutOfRange {
+        element_count: u32,
+    },
+    ElementOverflow {
+        element_count: usize,
+        max: u64,
+    },
+    MixedRadixOverflow {
+        digit_count: usize,
+    },
+    RoundtripFailed {
+        index: usize,
+        message: String,
+    },
+    Cancelled,
+    IndexOutOfRange {
+        index: usize,
+        len: usize,
+    },
 }
 
 impl Display for Error {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
-            Self::ValidationDuplicateNumber => {
-                f.write_str("Input number sequence contains duplicate numbers")
-            }
-            Self::ValidationOutOfRange => f.write_str(
-                "Input number sequence contains numbers larger than the sequence length",
-            ),
+            Self::ValidationDuplicateNumber { index, number } => f.write_fmt(format_args!(
+                "Input number sequence contains the number {number} a second time at index {index}"
+            )),
+            Self::ValidationOutOfRange { index, number } => f.write_fmt(format_args!(
+                "Input number sequence contains the number {number} at index {index}, which is out of range for the sequence length"
+            )),
             Self::SequenceToLong { element_count } => f.write_fmt(format_args!(
                 "Input sequence contains {element_count} elements, but we only support up to 2^32"
             )),
             Self::Decode => f.write_str("Something failed during decode. Usually happens from invalid input."),
+            Self::ElementCountMismatch { expected, got } => f.write_fmt(format_args!(
+                "The code was created from at least {expected} elements, but decode got {got} elements"
+            )),
+            Self::PositionOutOfRange {
+                position,
+                element_count,
+            } => f.write_fmt(format_args!(
+                "Position {position} is out of range for a sequence of {element_count} elements"
+            )),
+            Self::LengthMismatch { expected, got } => f.write_fmt(format_args!(
+                "Expected a sequence of {expected} elements, but got {got} elements"
+            )),
+            Self::PositionsNotSorted { index } => f.write_fmt(format_args!(
+                "Positions must be sorted ascending, but position {index} is smaller than its predecessor"
+            )),
+            Self::Io { message, .. } => f.write_fmt(format_args!("I/O error: {message}")),
+            Self::InvalidHeader => f.write_str("The header of the self describing code is invalid"),
+            Self::ChecksumMismatch => {
+                f.write_str("The checksum does not match the code, the code is corrupted")
+            }
+            Self::UnsupportedVersion { version } => f.write_fmt(format_args!(
+                "The self describing code has format version {version}, which is not supported"
+            )),
+            Self::InvalidRadix { radix } => f.write_fmt(format_args!(
+                "Radix {radix} is invalid, it is too small for this number system"
+            )),
+            Self::InvalidDigit {
+                index,
+                digit,
+                radix,
+            } => f.write_fmt(format_args!(
+                "Digit {digit} at index {index} is out of range for radix {radix}"
+            )),
+            Self::Input { index, message } => f.write_fmt(format_args!(
+                "Reading the input number at index {index} failed: {message}"
+            )),
+            Self::RankOutOfRange { element_count } => f.write_fmt(format_args!(
+                "The rank offset leaves the range of ranks of {element_count} elements, 0..{element_count}!"
+            )),
+            Self::ElementOverflow { element_count, max } => f.write_fmt(format_args!(
+                "A permutation of {element_count} elements does not fit into an element type with maximum {max}"
+            )),
+            Self::MixedRadixOverflow { digit_count } => f.write_fmt(format_args!(
+                "The rank does not fit into the {digit_count} mixed radix digits, the product of the radices is too small"
+            )),
+            Self::RoundtripFailed { index, message } => f.write_fmt(format_args!(
+                "The roundtrip of the permutation at index {index} failed: {message}"
+            )),
+            Self::Cancelled => f.write_str("The operation was cancelled before it finished"),
+            Self::IndexOutOfRange { index, len } => f.write_fmt(format_args!(
+                "Index {index} is out of range, there are only {len} entries"
+            )),
             Self::OutVectorSize {
                 byte_size,
                 element_count,
             } => f.write_fmt(format_args!(
                 // Breaking up the string because long string causes bugs with the code auto formatting :(
-                "{byte_size} {element_count} byte, but storing its {} elements requires {} byte. {}",
+                "{} {byte_size} byte, but storing its {element_count} elements requires {} byte. {}",
                 "The byte output vector used in encode has",
                 get_encode_size(*element_count),
-                "Make sure to correctly use \"Lehmer::get_encode_size()\""
+                "Make sure to correctly use \"big_lehmer::get_encode_size()\""
             )),
         }
     }
 }
 
 impl std::error::Error for Error {}
+
+/// Stores the kind and message, the `std::io::Error` itself is not `Clone`
+impl From<std::io::Error> for Error {
+    fn from(error: std::io::Error) -> Self {
+        Self::Io {
+            kind: error.kind(),
+            message: error.to_string(),
+        }
+    }
+}
+
+/// Broad category of an `Error`, see `Error::kind`.  
+/// Allows matching on the cause without listing every variant.
+/// Like `Error`, it can grow new kinds in minor releases.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum ErrorKind {
+    /// The input sequence, digits or positions are invalid
+    Validation,
+    /// The code does not decode with the given parameters
+    Decode,
+    /// A length, count or range does not fit
+    Size,
+    /// Reading or writing failed
+    Io,
+    /// The self describing, checksum or stream format is invalid
+    Format,
+    /// The caller cancelled the operation
+    Cancelled,
+}
+
+impl Error {
+    /// Broad category of this error
+    ///
+    /// # Examples
+    /// ```
+    /// use big_lehmer::ErrorKind;
+    /// assert_eq!(big_lehmer::encode(&[0, 0]).unwrap_err().kind(), ErrorKind::Validation);
+    /// assert_eq!(big_lehmer::decode(&[6], &mut [0; 3]).unwrap_err().kind(), ErrorKind::Decode);
+    /// ```
+    #[must_use]
+    pub fn kind(&self) -> ErrorKind {
+        match self {
+            Self::ValidationDuplicateNumber { .. }
+            | Self::ValidationOutOfRange { .. }
+            | Self::PositionOutOfRange { .. }
+            | Self::PositionsNotSorted { .. }
+            | Self::IndexOutOfRange { .. }
+            | Self::InvalidRadix { .. }
+            | Self::InvalidDigit { .. }
+            | Self::Input { .. } => ErrorKind::Validation,
+            Self::Decode | Self::ElementCountMismatch { .. } | Self::RoundtripFailed { .. } => {
+                ErrorKind::Decode
+            }
+            Self::SequenceToLong { .. }
+            | Self::OutVectorSize { .. }
+            | Self::LengthMismatch { .. }
+            | Self::RankOutOfRange { .. }
+            | Self::ElementOverflow { .. }
+            | Self::MixedRadixOverflow { .. } => ErrorKind::Size,
+            Self::Io { .. } => ErrorKind::Io,
+            Self::InvalidHeader | Self::ChecksumMismatch | Self::UnsupportedVersion { .. } => {
+                ErrorKind::Format
+            }
+            Self::Cancelled => ErrorKind::Cancelled,
+        }
+    }
+
+    /// The input sequence, digits or positions are invalid, see `ErrorKind::Validation`
+    ///
+    /// # Examples
+    /// ```
+    /// assert!(big_lehmer::encode(&[0, 0]).unwrap_err().is_validation());
+    /// assert!(!big_lehmer::decode(&[6], &mut [0; 3]).unwrap_err().is_validation());
+    /// ```
+    #[must_use]
+    pub fn is_validation(&self) -> bool {
+        self.kind() == ErrorKind::Validation
+    }
+
+    /// The code does not decode with the given parameters, see `ErrorKind::Decode`
+    ///
+    /// # Examples
+    /// ```
+    /// assert!(big_lehmer::decode(&[6], &mut [0; 3]).unwrap_err().is_decode());
+    /// ```
+    #[must_use]
+    pub fn is_decode(&self) -> bool {
+        self.kind() == ErrorKind::Decode
+    }
+
+    /// A length, count or range does not fit, see `ErrorKind::Size`
+    #[must_use]
+    pub fn is_size(&self) -> bool {
+        self.kind() == ErrorKind::Size
+    }
+
+    /// Reading or writing failed, see `ErrorKind::Io`
+    #[must_use]
+    pub fn is_io(&self) -> bool {
+        self.kind() == ErrorKind::Io
+    }
+
+    /// The self describing, checksum or stream format is invalid, see `ErrorKind::Format`
+    #[must_use]
+    pub fn is_format(&self) -> bool {
+        self.kind() == ErrorKind::Format
+    }
+
+    /// The caller cancelled the operation, see `ErrorKind::Cancelled`
+    #[must_use]
+    pub fn is_cancelled(&self) -> bool {
+        self.kind() == ErrorKind::Cancelled
+    }
+}
@@ -0,0 +1,278 @@
+use dashu::integer::UBig;
+
+use crate::{
+    decode::code_digits,
+    error::Error,
+    ranking::OrderStatisticTree,
+    validation::{visit, Visited},
+};
+
+/// Lazy iterator over the Lehmer (factoradic) digits of a permutation.
+/// Created by `big_lehmer::factoradic_digits`.
+///
+/// Digit `i` is the amount of numbers smaller than `numbers[i]` that appear after position `i`.
+/// Its radix is `numbers.len() - i`, so the first digit is the most significant one.
+///
+/// Also iterates from the back with `next_back`, yielding the least significant digits first (radix 1, 2, ...).
+/// The back uses its own tree, created on the first `next_back`, so both ends can be consumed interleaved.
+///
+/// An invalid element yields its validation error and is skipped, the iteration continues with the next element.
+/// That way the iterator always yields exactly `numbers.len()` items.
+#[derive(Debug, Clone)]
+pub struct FactoradicDigits<'a> {
+    numbers: std::iter::Enumerate<std::slice::Iter<'a, u32>>,
+    tree: OrderStatisticTree,
+    back_tree: Option<OrderStatisticTree>,
+    validation: Visited,
+}
+
+impl Iterator for FactoradicDigits<'_> {
+    type Item = Result<u32, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (index, &number) = self.numbers.next()?;
+        // The tree removed the prefix, the numbers smaller than `number` that are still in it come after `index`
+        Some(visit(&mut self.validation, index, number).map(|()| self.tree.insert_and_rank(number)))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.numbers.size_hint()
+    }
+}
+
+impl DoubleEndedIterator for FactoradicDigits<'_> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        let (index, &number) = self.numbers.next_back()?;
+        if let Err(error) = visit(&mut self.validation, index, number) {
+            return Some(Err(error));
+        }
+        let back_tree = match &mut self.back_tree {
+            Some(back_tree) => back_tree,
+            None => {
+                // Same element count as the front tree, which was created successfully
+                let element_count = u32::try_from(self.validation.len()).unwrap();
+                match OrderStatisticTree::new(element_count) {
+                    Ok(back_tree) => self.back_tree.insert(back_tree),
+                    Err(error) => return Some(Err(error)),
+                }
+            }
+        };
+        // The back tree removed the suffix, the smaller numbers that are gone come after `index`
+        Some(Ok(number - back_tree.insert_and_rank(number)))
+    }
+}
+
+impl ExactSizeIterator for FactoradicDigits<'_> {}
+
+impl std::iter::FusedIterator for FactoradicDigits<'_> {}
+
+/// Lazily yields the Lehmer digits of `numbers`, one per element, driving the same tree as `big_lehmer::encode`.
+/// Allows streaming the digits without allocating the full digit vector and stopping early.
+///
+/// # Examples
+/// ```
+/// let digits = big_lehmer::factoradic_digits(&[7, 2, 0, 6, 5, 1, 4, 3]).unwrap();
+/// assert_eq!(digits.len(), 8);
+/// let digits: Result<Vec<u32>, _> = digits.collect();
+/// assert_eq!(digits.unwrap(), [7, 2, 0, 4, 3, 0, 1, 0]);
+/// ```
+///
+/// # Errors
+///
+/// Creating the iterator errors with `Error::SequenceToLong` when `numbers` has more than `u32::MAX` elements
+/// or the internal tree does not fit into memory.  
+/// Validation errors are yielded on the offending element.
+pub fn factoradic_digits(numbers: &[u32]) -> Result<FactoradicDigits<'_>, Error> {
+    let element_count = u32::try_from(numbers.len()).map_err(|_| Error::SequenceToLong {
+        element_count: numbers.len(),
+    })?;
+    Ok(FactoradicDigits {
+        numbers: numbers.iter().enumerate(),
+        tree: OrderStatisticTree::new(element_count)?,
+        back_tree: None,
+        validation: Visited::new(numbers.len()),
+    })
+}
+
+/// Eager version of `big_lehmer::factoradic_digits`, returns all Lehmer digits of `numbers`.
+///
+/// # Examples
+/// ```
+/// let digits = big_lehmer::to_factoradic(&[7, 2, 0, 6, 5, 1, 4, 3]).unwrap();
+/// assert_eq!(digits, [7, 2, 0, 4, 3, 0, 1, 0]);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn to_factoradic(numbers: &[u32]) -> Result<Vec<u32>, Error> {
+    factoradic_digits(numbers)?.collect()
+}
+
+/// Decode side counterpart of `big_lehmer::to_factoradic`: the Lehmer digits of a code of `element_count` elements,
+/// before they are turned into the permutation.  
+/// Helps to tell apart whether the big number divisions or the permutation reconstruction went wrong.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode(&sequence).unwrap();
+/// let digits = big_lehmer::decode_to_factoradic(&encoded, 8).unwrap();
+/// assert_eq!(digits, big_lehmer::to_factoradic(&sequence).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_to_factoradic(encoded: &[u8], element_count: u32) -> Result<Vec<u32>, Error> {
+    let element_count = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    code_digits(UBig::from_le_bytes(encoded), element_count)
+}
+
+/// Lehmer digit of position `index`, same as `big_lehmer::to_factoradic(numbers)?[index]`.  
+/// The tree is stateful, so the elements up to `index` still have to be inserted.
+/// Costs O(index * log N) after the O(N) tree setup, the elements after `index` are not validated.
+///
+/// # Examples
+/// ```
+/// assert_eq!(big_lehmer::nth_factoradic_digit(&[7, 2, 0, 6, 5, 1, 4, 3], 3).unwrap(), 4);
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::PositionOutOfRange` when `index >= numbers.len()`.  
+/// Validation errors of the elements up to `index`, same as `big_lehmer::encode`
+pub fn nth_factoradic_digit(numbers: &[u32], index: usize) -> Result<u32, Error> {
+    let mut digits = factoradic_digits(numbers)?;
+    if index >= numbers.len() {
+        return Err(Error::PositionOutOfRange {
+            position: u32::try_from(index).unwrap_or(u32::MAX),
+            element_count: u32::try_from(numbers.len()).unwrap(),
+        });
+    }
+    let mut digit = 0;
+    for _ in 0..=index {
+        digit = digits.next().unwrap()?;
+    }
+    Ok(digit)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digits_error_on_offending_element() {
+        let mut digits = factoradic_digits(&[1, 1, 5, 0]).unwrap();
+        assert_eq!(digits.len(), 4);
+        assert!(matches!(digits.next(), Some(Ok(1))));
+        assert!(matches!(
+            digits.next(),
+            Some(Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 1
+            }))
+        ));
+        assert!(matches!(
+            digits.next(),
+            Some(Err(Error::ValidationOutOfRange {
+                index: 2,
+                number: 5
+            }))
+        ));
+        assert_eq!(digits.len(), 1);
+        assert!(matches!(digits.next(), Some(Ok(0))));
+        assert!(digits.next().is_none());
+    }
+
+    #[test]
+    fn test_digits_early_termination() {
+        let sequence: Vec<u32> = (0..1000).rev().collect();
+        let first: Vec<u32> = factoradic_digits(&sequence)
+            .unwrap()
+            .take(3)
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(first, [999, 998, 997]);
+        assert!(to_factoradic(&[]).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_digits_reversed() {
+        let sequence: Vec<u32> = crate::tests::shuffled(1000, 866);
+        let forward = to_factoradic(&sequence).unwrap();
+        let mut reversed: Vec<u32> = factoradic_digits(&sequence)
+            .unwrap()
+            .rev()
+            .map(Result::unwrap)
+            .collect();
+        reversed.reverse();
+        assert_eq!(forward, reversed);
+
+        // Both ends interleaved
+        let mut digits = factoradic_digits(&sequence).unwrap();
+        let mut front = vec![];
+        let mut back = vec![];
+        while let Some(digit) = digits.next() {
+            front.push(digit.unwrap());
+            if let Some(digit) = digits.next_back() {
+                back.push(digit.unwrap());
+            }
+            assert_eq!(digits.len(), 1000 - front.len() - back.len());
+        }
+        back.reverse();
+        front.extend(back);
+        assert_eq!(forward, front);
+        assert!(digits.next_back().is_none());
+    }
+
+    #[test]
+    fn test_digits_reversed_error() {
+        let mut digits = factoradic_digits(&[0, 2, 2]).unwrap();
+        assert!(matches!(digits.next_back(), Some(Ok(0))));
+        assert!(matches!(
+            digits.next_back(),
+            Some(Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 2
+            }))
+        ));
+        assert!(matches!(digits.next_back(), Some(Ok(0))));
+    }
+
+    #[test]
+    fn test_nth_digit() {
+        let sequence: Vec<u32> = crate::tests::shuffled(100, 823);
+        let digits = to_factoradic(&sequence).unwrap();
+        for (index, &digit) in digits.iter().enumerate() {
+            assert_eq!(nth_factoradic_digit(&sequence, index).unwrap(), digit);
+        }
+        assert!(matches!(
+            nth_factoradic_digit(&sequence, 100),
+            Err(Error::PositionOutOfRange {
+                position: 100,
+                element_count: 100
+            })
+        ));
+        // Only the prefix is validated
+        assert_eq!(nth_factoradic_digit(&[1, 0, 0], 1).unwrap(), 0);
+        assert!(nth_factoradic_digit(&[1, 1, 0], 1).is_err());
+    }
+
+    #[test]
+    fn test_decode_to_factoradic() {
+        let sequence: Vec<u32> = crate::tests::shuffled(3000, 830);
+        let encoded = crate::encode(&sequence).unwrap();
+        assert_eq!(
+            decode_to_factoradic(&encoded, 3000).unwrap(),
+            to_factoradic(&sequence).unwrap()
+        );
+        assert!(decode_to_factoradic(&[], 0).unwrap().is_empty());
+        assert!(matches!(
+            decode_to_factoradic(&encoded, 100),
+            Err(Error::ElementCountMismatch { got: 100, .. })
+        ));
+    }
+}
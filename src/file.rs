@@ -0,0 +1,98 @@
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, Read, Write},
+    path::Path,
+};
+
+use crate::{
+    decode,
+    error::Error,
+    format::{encode_self_describing, EncodedLehmer},
+};
+
+/// Encodes the number sequence and writes it to the file at `path`, replacing the file when it exists.  
+/// Uses the self describing format of `big_lehmer::encode_self_describing`, so the file carries its element count.
+/// Read it back with `big_lehmer::decode_from_file`
+///
+/// # Examples
+/// ```
+/// let path = std::env::temp_dir().join("big_lehmer_doc_encode_to_file.lehmer");
+/// big_lehmer::encode_to_file(&[7, 2, 0, 6, 5, 1, 4, 3], &path).unwrap();
+/// let mut results = [0; 8];
+/// big_lehmer::decode_from_file(&path, &mut results).unwrap();
+/// assert_eq!(results, [7, 2, 0, 6, 5, 1, 4, 3]);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`. `Error::Io` when creating or writing the file fails.
+pub fn encode_to_file(numbers: &[u32], path: impl AsRef<Path>) -> Result<(), Error> {
+    let encoded = encode_self_describing(numbers)?;
+    let file = File::create(path)?;
+    let mut writer = BufWriter::new(file);
+    writer
+        .write_all(encoded.as_bytes())
+        .and_then(|()| writer.flush())
+        .map_err(Error::from)
+}
+
+/// Reads a file written by `big_lehmer::encode_to_file` and decodes it into `results`.
+///
+/// # Errors
+///
+/// `Error::Io` when opening or reading the file fails.  
+/// `Error::InvalidHeader` or `Error::UnsupportedVersion` when the file is not a self describing code.  
+/// `Error::LengthMismatch` when `results.len()` differs from the element count stored in the file.  
+/// Same as `big_lehmer::decode`
+pub fn decode_from_file(path: impl AsRef<Path>, results: &mut [u32]) -> Result<(), Error> {
+    let file = File::open(path)?;
+    let mut bytes = Vec::new();
+    BufReader::new(file).read_to_end(&mut bytes)?;
+    let encoded = EncodedLehmer::from_bytes(&bytes)?;
+    if encoded.element_count() as usize != results.len() {
+        return Err(Error::LengthMismatch {
+            expected: encoded.element_count() as usize,
+            got: results.len(),
+        });
+    }
+    decode(encoded.code(), results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_file_roundtrip() {
+        let path = std::env::temp_dir().join("big_lehmer_test_file_roundtrip.lehmer");
+        let sequence: Vec<u32> = crate::tests::shuffled(5000, 843);
+        encode_to_file(&sequence, &path).unwrap();
+
+        let mut results = vec![0; 5000];
+        decode_from_file(&path, &mut results).unwrap();
+        assert_eq!(results, sequence);
+        assert!(matches!(
+            decode_from_file(&path, &mut results[..10]),
+            Err(Error::LengthMismatch {
+                expected: 5000,
+                got: 10
+            })
+        ));
+
+        std::fs::write(&path, [1, 2, 3]).unwrap();
+        assert!(matches!(
+            decode_from_file(&path, &mut results),
+            Err(Error::InvalidHeader)
+        ));
+
+        std::fs::remove_file(&path).unwrap();
+        assert!(matches!(
+            decode_from_file(&path, &mut results),
+            Err(Error::Io {
+                kind: std::io::ErrorKind::NotFound,
+                ..
+            })
+        ));
+    }
+}
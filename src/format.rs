@@ -0,0 +1,270 @@
+use std::io::Read;
+
+use crate::{decode, decode_to_vec, encode, error::Error};
+
+/// Appends `value` as LEB128 varint
+pub(crate) fn write_varint(buf: &mut Vec<u8>, mut value: u64) {
+    loop {
+        // Truncation intended, only the lowest 7 bits are written
+        #[allow(clippy::cast_possible_truncation)]
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            buf.push(byte);
+            return;
+        }
+        buf.push(byte | 0x80);
+    }
+}
+
+/// Reads a LEB128 varint. Returns the value and the amount of bytes read.  
+/// None when the bytes end before the varint or the value does not fit into u64
+pub(crate) fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value = 0u64;
+    for (index, &byte) in bytes.iter().enumerate().take(10) {
+        let part = u64::from(byte & 0x7F);
+        let shift = 7 * u32::try_from(index).unwrap();
+        if shift == 63 && part > 1 {
+            return None;
+        }
+        value |= part << shift;
+        if byte & 0x80 == 0 {
+            return Some((value, index + 1));
+        }
+    }
+    None
+}
+
+/// Same as `read_varint`, but reads the bytes one at a time from `reader`.  
+/// `None` when the reader ends before the first byte, `Error::InvalidHeader` when the value does not fit into u64
+pub(crate) fn read_varint_from<R: Read>(reader: &mut R) -> Result<Option<u64>, Error> {
+    let mut value = 0u64;
+    for index in 0..10 {
+        let mut byte = [0];
+        if let Err(e) = reader.read_exact(&mut byte) {
+            if index == 0 && e.kind() == std::io::ErrorKind::UnexpectedEof {
+                return Ok(None);
+            }
+            return Err(e.into());
+        }
+        let part = u64::from(byte[0] & 0x7F);
+        let shift = 7 * index;
+        if shift == 63 && part > 1 {
+            return Err(Error::InvalidHeader);
+        }
+        value |= part << shift;
+        if byte[0] & 0x80 == 0 {
+            return Ok(Some(value));
+        }
+    }
+    Err(Error::InvalidHeader)
+}
+
+/// Lehmer code that carries its element count.
+///
+/// Layout: `MAGIC` byte, `VERSION` byte, element count as LEB128 varint, followed by the bytes of `big_lehmer::encode`.  
+/// Removes the need to track the element count next to the code, decoding with a wrong length is not possible.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode_self_describing(&sequence).unwrap();
+/// assert_eq!(encoded.element_count(), 8);
+///
+/// let stored = encoded.as_bytes().to_vec();
+/// let read = big_lehmer::EncodedLehmer::from_bytes(&stored).unwrap();
+/// assert_eq!(big_lehmer::decode_self_describing(&read).unwrap(), sequence);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EncodedLehmer {
+    bytes: Box<[u8]>,
+    element_count: u32,
+    header_len: usize,
+}
+
+impl EncodedLehmer {
+    pub const MAGIC: u8 = b'L';
+    pub const VERSION: u8 = 1;
+
+    fn new(code: &[u8], element_count: u32) -> Self {
+        let mut bytes = vec![Self::MAGIC, Self::VERSION];
+        write_varint(&mut bytes, u64::from(element_count));
+        let header_len = bytes.len();
+        bytes.extend_from_slice(code);
+        Self {
+            bytes: bytes.into_boxed_slice(),
+            element_count,
+            header_len,
+        }
+    }
+
+    /// Parses the header of a stored self describing code.
+    ///
+    /// # Errors
+    ///
+    /// `Error::InvalidHeader` when the magic byte or the element count is invalid.  
+    /// `Error::UnsupportedVersion` when the code was written by an unknown format version.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, Error> {
+        let (&magic, rest) = bytes.split_first().ok_or(Error::InvalidHeader)?;
+        if magic != Self::MAGIC {
+            return Err(Error::InvalidHeader);
+        }
+        let (&version, rest) = rest.split_first().ok_or(Error::InvalidHeader)?;
+        if version != Self::VERSION {
+            return Err(Error::UnsupportedVersion { version });
+        }
+        let (element_count, count_len) = read_varint(rest).ok_or(Error::InvalidHeader)?;
+        let element_count = u32::try_from(element_count).map_err(|_| Error::InvalidHeader)?;
+        Ok(Self {
+            bytes: bytes.into(),
+            element_count,
+            header_len: 2 + count_len,
+        })
+    }
+
+    /// Number of elements of the encoded permutation
+    #[must_use]
+    pub fn element_count(&self) -> u32 {
+        self.element_count
+    }
+
+    /// The raw Lehmer code without header, as produced by `big_lehmer::encode`
+    #[must_use]
+    pub fn code(&self) -> &[u8] {
+        &self.bytes[self.header_len..]
+    }
+
+    /// Header and code, this is what should be stored
+    #[must_use]
+    pub fn as_bytes(&self) -> &[u8] {
+        &self.bytes
+    }
+
+    #[must_use]
+    pub fn into_bytes(self) -> Box<[u8]> {
+        self.bytes
+    }
+}
+
+/// Encodes the number sequence into a self describing Lehmer code that also stores the element count.
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_self_describing(numbers: &[u32]) -> Result<EncodedLehmer, Error> {
+    let code = encode(numbers)?;
+    // encode already verified the length fits into u32
+    let element_count = u32::try_from(numbers.len()).unwrap();
+    Ok(EncodedLehmer::new(&code, element_count))
+}
+
+/// Decodes a self describing Lehmer code, the element count is read from the header.
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_self_describing(encoded: &EncodedLehmer) -> Result<Vec<u32>, Error> {
+    decode_to_vec(encoded.code(), encoded.element_count())
+}
+
+/// Same as `big_lehmer::decode_self_describing`, but parses the header from the stored bytes.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let stored = big_lehmer::encode_self_describing(&sequence).unwrap().into_bytes();
+/// assert_eq!(big_lehmer::decode_self_describing_to_vec(&stored).unwrap(), sequence);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::EncodedLehmer::from_bytes` and `big_lehmer::decode`
+pub fn decode_self_describing_to_vec(bytes: &[u8]) -> Result<Vec<u32>, Error> {
+    decode_self_describing(&EncodedLehmer::from_bytes(bytes)?)
+}
+
+/// Encodes the number sequence into a length prefixed code: the byte length of the code as LEB128 varint,
+/// followed by the minimal little endian bytes of the code (without high zero bytes).  
+/// Near identity permutations have small ranks, so their compact code is only a few bytes
+/// instead of the worst case `big_lehmer::get_encode_size`.
+/// The prefix also allows concatenating several codes.
+///
+/// # Examples
+/// ```
+/// let identity: Vec<u32> = (0..1000).collect();
+/// assert_eq!(*big_lehmer::encode_compact(&identity).unwrap(), [0]);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_compact(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
+    let code = encode(numbers)?;
+    let mut result = Vec::with_capacity(code.len() + 10);
+    write_varint(&mut result, code.len() as u64);
+    result.extend_from_slice(&code);
+    Ok(result.into_boxed_slice())
+}
+
+/// Decodes a code created by `big_lehmer::encode_compact`.  
+/// Returns the amount of bytes read, `encoded` may continue with further data after the code.
+///
+/// # Errors
+///
+/// `Error::InvalidHeader` when the length prefix is invalid, `Error::Decode` when `encoded` is shorter than the prefix says.  
+/// Same errors as `big_lehmer::decode`
+pub fn decode_compact(encoded: &[u8], results: &mut [u32]) -> Result<usize, Error> {
+    let (code_len, prefix_len) = read_varint(encoded).ok_or(Error::InvalidHeader)?;
+    let code_len = usize::try_from(code_len).map_err(|_| Error::Decode)?;
+    let end = prefix_len.checked_add(code_len).ok_or(Error::Decode)?;
+    let code = encoded.get(prefix_len..end).ok_or(Error::Decode)?;
+    decode(code, results)?;
+    Ok(end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_roundtrip_compact() {
+        let mut near_identity: Vec<u32> = (0..1000).collect();
+        near_identity.swap(997, 999);
+        let encoded = encode_compact(&near_identity).unwrap();
+        assert!(encoded.len() <= 3);
+
+        // Concatenated codes
+        let reverse: Vec<u32> = (0..1000).rev().collect();
+        let mut stream = encoded.to_vec();
+        stream.extend_from_slice(&encode_compact(&reverse).unwrap());
+
+        let mut roundtrip = vec![0; 1000];
+        let read = decode_compact(&stream, &mut roundtrip).unwrap();
+        assert_eq!(read, encoded.len());
+        assert_eq!(roundtrip, near_identity);
+        let read_second = decode_compact(&stream[read..], &mut roundtrip).unwrap();
+        assert_eq!(read + read_second, stream.len());
+        assert_eq!(roundtrip, reverse);
+
+        assert!(decode_compact(&stream[..stream.len() - 1], &mut roundtrip).is_ok());
+        assert!(matches!(
+            decode_compact(&stream[read..stream.len() - 1], &mut roundtrip),
+            Err(Error::Decode)
+        ));
+        assert!(matches!(
+            decode_compact(&[], &mut roundtrip),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_varint_roundtrip() {
+        for value in [0, 1, 127, 128, 300, u64::from(u32::MAX), u64::MAX] {
+            let mut buf = vec![];
+            write_varint(&mut buf, value);
+            assert_eq!(read_varint(&buf), Some((value, buf.len())));
+        }
+        assert_eq!(read_varint(&[0x80]), None);
+        assert_eq!(read_varint(&[0xFF; 10]), None);
+    }
+}
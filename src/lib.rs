@@ -1,32 +1,141 @@
 #![doc = include_str!("../readme.md")]
 
-use dashu::integer::UBig;
-use rayon::iter::{IntoParallelRefIterator, ParallelIterator};
+use dashu::{base::BitTest, integer::UBig};
+use std::num::NonZeroU32;
 
+mod archive;
+#[cfg(feature = "async")]
+mod async_ops;
+mod backend;
+mod batch;
+mod builder;
+mod byte_order;
+mod chars;
+#[cfg(feature = "checksum")]
+mod checksum;
+mod code;
+mod code_debug;
+mod compose;
 mod decode;
+mod delta;
+mod element;
 mod encode;
 mod error;
+mod factoradic;
+mod factorial;
+mod file;
+mod format;
+mod limbs;
+mod permutation;
+mod progress;
+mod radix;
+mod rank;
+pub mod ranking;
+mod size;
+mod small;
+mod space;
+mod sparse;
+mod stats;
+mod stream;
+#[cfg(feature = "testing")]
+pub mod testing;
+mod validation;
+#[cfg(feature = "wasm")]
+pub mod wasm;
+#[cfg(feature = "zeroize")]
+mod zeroizing;
 
-use decode::{recursive_split_divide, DecodeAS, WorkItem};
-use encode::{BigCache, Cache, EncodeAS};
-use error::Error;
+pub use archive::LehmerArchive;
+#[cfg(feature = "async")]
+pub use async_ops::{decode_async, encode_async};
+pub use backend::{decode_with_backend, encode_with_backend, BigInt};
+pub use batch::{
+    decode_batch, decode_batch_results, encode_batch, encode_batch_results, validate_codes,
+    verify_batch,
+};
+pub use builder::{Backend, LehmerBuilder};
+pub use byte_order::{
+    decode_db, decode_with_byte_order, encode_db, encode_with_byte_order, ByteOrder,
+};
+pub use chars::{rank_chars, unrank_chars};
+#[cfg(feature = "checksum")]
+pub use checksum::{decode_with_checksum, encode_with_checksum};
+pub use code::{compare_codes, LehmerCode};
+pub use code_debug::CodeDebug;
+pub use compose::{compose_codes, embed, pack, unpack};
+use decode::{
+    check_element_count, compute_remainders, compute_remainders_in, min_element_count,
+    remainders_to_permutation, remainders_to_permutation_with, trivial_decode,
+};
+pub use decode::{DecodeConfig, DecodeScratch, DecodeStrategy};
+pub use delta::{decode_delta, encode_delta};
+pub use element::{decode_as, Element};
+pub use encode::{EncodeStats, Encoder};
+pub use error::{Error, ErrorKind};
+pub use factoradic::{
+    decode_to_factoradic, factoradic_digits, nth_factoradic_digit, to_factoradic, FactoradicDigits,
+};
+pub use file::{decode_from_file, encode_to_file};
+pub use format::{
+    decode_compact, decode_self_describing, decode_self_describing_to_vec, encode_compact,
+    encode_self_describing, EncodedLehmer,
+};
+pub use limbs::{decode_from_limbs, encode_to_limbs};
+pub use permutation::Permutation;
+pub use progress::{
+    decode_cancellable, decode_with_progress, encode_cancellable, encode_with_progress,
+};
+pub use radix::{code_from_base, code_to_base, from_mixed_radix, to_mixed_radix};
+pub use rank::{
+    all_combinations, all_permutations, append_element, code_add, code_add_saturating,
+    code_add_wrapping, code_sub, decode_ubig_owned, permutation_count, permutation_rank,
+    permutation_unrank, rank_after_swap, AllCombinations, AllPermutations,
+};
+use ranking::OrderStatisticTree;
+pub use size::{
+    actual_encode_size, code_bit_length, code_bit_length_of, estimate_decode_memory,
+    estimate_encode_memory, factorial_bit_length, get_encode_size_const, verify_encode_size,
+    ENCODE_SIZE_CONST_MAX,
+};
+use size::{SMALL_ENCODE_SIZES, SMALL_ENCODE_SIZE_MAX};
+pub use small::{encode_code_bytes, encode_small, CodeBytes};
+pub use space::RankSpace;
+pub use sparse::{decode_sparse, encode_sparse};
+pub use stats::{from_cycles, inversion_count, kendall_tau, parity, parity_of_code, to_cycles};
+pub use stream::{
+    decode_streaming, encode_to_writer, encode_try_iter, StreamEncoder, StreamReader, StreamWriter,
+};
+pub use validation::is_valid_permutation;
+#[cfg(feature = "zeroize")]
+pub use zeroizing::{decode_zeroizing, encode_zeroizing};
 
 /// Estimate bounded byte size of the Lehmer code.
 /// Bit size = log2(N!)
 /// Byte size = Ceil(log2(N!) / 8)
 ///
+/// Up to 128 elements the size comes from a table computed at compile time,
+/// so sizing a buffer per small permutation is a single lookup.
+///
 /// # Examples
 ///
 /// ```
 /// assert_eq!(big_lehmer::get_encode_size(20), 8);
 /// ```
+#[must_use]
+pub fn get_encode_size(element_count: u32) -> usize {
+    if element_count <= SMALL_ENCODE_SIZE_MAX {
+        return usize::from(SMALL_ENCODE_SIZES[element_count as usize]);
+    }
+    encode_size_summation(element_count)
+}
+
+/// `big_lehmer::get_encode_size` without the table, sums the logarithms of the factors
 #[allow(
     clippy::cast_possible_truncation,
     clippy::cast_precision_loss,
     clippy::cast_sign_loss
 )]
-#[must_use]
-pub fn get_encode_size(element_count: u32) -> usize {
+fn encode_size_summation(element_count: u32) -> usize {
     if element_count == 0 {
         return 0;
     }
@@ -50,10 +159,57 @@ pub fn get_encode_size(element_count: u32) -> usize {
     (result / 8.0).ceil() as usize + byte_padding
 }
 
+/// Same as `big_lehmer::get_encode_size`, but for `u64` element counts.  
+/// Agrees with `big_lehmer::get_encode_size` up to `u32::MAX`.
+/// Above, the log2 summation would take too long, so it uses the Stirling upper bound of `log2(N!)`
+/// with a padding that grows with the size (float inaccuracy grows with it).
+/// Saturates at `usize::MAX` when the size does not fit.
+///
+/// # Examples
+///
+/// ```
+/// assert_eq!(big_lehmer::get_encode_size_u64(20), big_lehmer::get_encode_size(20));
+/// // About 31.6 bit per element
+/// assert!(big_lehmer::get_encode_size_u64(1 << 33) > (1 << 33) * 3);
+/// ```
+#[must_use]
+pub fn get_encode_size_u64(element_count: u64) -> usize {
+    match u32::try_from(element_count) {
+        Ok(element_count) => get_encode_size(element_count),
+        Err(_) => stirling_encode_size(element_count),
+    }
+}
+
+/// Upper bound of the code size from `ln(N!) <= N ln(N) - N + ln(2 pi N) / 2 + 1 / (12 N)`
+#[allow(
+    clippy::cast_possible_truncation,
+    clippy::cast_precision_loss,
+    clippy::cast_sign_loss
+)]
+fn stirling_encode_size(element_count: u64) -> usize {
+    let n = element_count as f64;
+    let ln_factorial =
+        n * n.ln() - n + 0.5 * (2.0 * std::f64::consts::PI * n).ln() + 1.0 / (12.0 * n);
+    let bytes = (ln_factorial / std::f64::consts::LN_2 / 8.0).ceil();
+    let byte_padding = (bytes * 1e-12).ceil() + 32.0;
+    let size = bytes + byte_padding;
+    if size >= usize::MAX as f64 {
+        usize::MAX
+    } else {
+        size as usize
+    }
+}
+
 /// Encodes the number sequence into a Lehmer code.  
 /// Approximate size of the code can be computed with `big_lehmer::get_encode_size`
 /// The code can later be decoded with `big_lehmer::decode`
 ///
+/// The code is the little endian bytes of the rank, without high zero bytes.
+/// It only depends on the permutation, never on how it was computed:
+/// the thread count, `big_lehmer::Encoder` settings, cache word, big number backend
+/// and `big_lehmer::DecodeConfig` strategies and split settings all produce and read the same bytes.
+/// Stored codes stay portable as those internals evolve.
+///
 /// # Examples
 /// ```
 /// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
@@ -65,58 +221,350 @@ pub fn get_encode_size(element_count: u32) -> usize {
 ///
 /// # Errors
 ///
-/// Will also error when the input number sequence is not valid.  
+/// Will also error when the input number sequence is not valid.
+/// The error contains the index and the number of the first invalid element.  
+/// The numbers must be exactly `0..numbers.len()`, the length of the slice and not of a larger buffer it points into.
+/// A window of a larger permutation holds numbers up to the parent length and fails with `Error::ValidationOutOfRange`,
+/// use `big_lehmer::encode_range` or `big_lehmer::encode_subrange` for those.  
 /// Examples for invalid input:  
 ///  `[0, 0]` (Duplicate number)  
 ///  `[1, 3, 2]` (Number 3 is out of range)  
-/// This automatically means it errors on sequences longer than `u32::Max`
+/// This automatically means it errors on sequences longer than `u32::Max`  
+/// Errors with `Error::SequenceToLong` when the internal tree does not fit into memory (e.g. on 16 bit systems)
+pub fn encode(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
+    Encoder::new().encode(numbers)
+}
+
+/// Same as `big_lehmer::encode`, with the guarantee that the code is the shortest byte slice of the rank:
+/// it never ends with a zero byte, and the identity permutation (rank zero) is the empty slice.  
+/// `big_lehmer::encode` produces the same bytes, this function states the guarantee as part of its contract.
 ///
-/// # Panics
+/// A trimmed code does not store the element count, it has to be tracked separately and passed to `big_lehmer::decode`.
+/// Decoding with too few elements errors with `Error::ElementCountMismatch`,
+/// but decoding with too many elements silently produces a different permutation
+/// (the one with the same rank in the larger space).
+/// When the element count is not tracked, use `big_lehmer::encode_self_describing` instead.
 ///
-/// Generally it should not panic. There might be panics on 16 bit systems.
-pub fn encode(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
-    if numbers.is_empty() {
-        return Ok(Box::new([]));
-    }
-    // supports up to u32::MAX elements
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode_trimmed(&[1, 0]).unwrap();
+/// assert_eq!(*encoded, [1]);
+/// assert!(big_lehmer::encode_trimmed(&[0, 1, 2]).unwrap().is_empty());
+///
+/// // The same code decodes to a different permutation with a wrong element count
+/// let mut wrong = [0; 3];
+/// big_lehmer::decode(&encoded, &mut wrong).unwrap();
+/// assert_eq!(wrong, [0, 2, 1]);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_trimmed(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
+    let encoded = encode(numbers)?;
+    debug_assert_ne!(encoded.last(), Some(&0));
+    Ok(encoded)
+}
+
+/// Same as `big_lehmer::encode`, but also returns statistics of the encode, see `big_lehmer::EncodeStats`.  
+/// Shows how well the `u64` caches reduce the big number work on a specific input. The code is the same as of `big_lehmer::encode`.
+///
+/// # Examples
+/// ```
+/// let sequence: Vec<u32> = (0..1000).rev().collect();
+/// let (encoded, stats) = big_lehmer::encode_with_stats(&sequence).unwrap();
+/// assert_eq!(encoded, big_lehmer::encode(&sequence).unwrap());
+/// assert_eq!(stats.bit_length(), big_lehmer::code_bit_length(&sequence).unwrap());
+/// // Many small steps share one cache segment
+/// assert!(stats.cache_count() < 1000 / 4);
+/// assert_eq!(stats.combine_count(), stats.cache_count() - 1);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_with_stats(numbers: &[u32]) -> Result<(Box<[u8]>, EncodeStats), Error> {
+    Encoder::new().encode_with_stats(numbers)
+}
+
+/// Same as `big_lehmer::encode`, but validates the whole input before the encode work.  
+/// Fails fast on invalid input, see `big_lehmer::Encoder::with_validate_first`.
+///
+/// # Examples
+/// ```
+/// let mut sequence: Vec<u32> = (0..1000).collect();
+/// sequence[999] = 3;
+/// assert!(matches!(
+///     big_lehmer::encode_validate_first(&sequence),
+///     Err(big_lehmer::Error::ValidationDuplicateNumber { index: 999, number: 3 })
+/// ));
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_validate_first(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
+    Encoder::new().with_validate_first(true).encode(numbers)
+}
+
+/// Whether `numbers` is the identity permutation `[0, 1, ..., n - 1]`, the only permutation with rank zero.
+///
+/// # Examples
+/// ```
+/// assert!(big_lehmer::is_identity(&[0, 1, 2]));
+/// assert!(big_lehmer::is_identity(&[]));
+/// assert!(!big_lehmer::is_identity(&[1, 0, 2]));
+/// ```
+#[must_use]
+pub fn is_identity(numbers: &[u32]) -> bool {
+    numbers
+        .iter()
+        .enumerate()
+        .all(|(index, &number)| u32::try_from(index) == Ok(number))
+}
+
+/// Code of the identity permutation of `element_count` elements, without encoding it.  
+/// All its Lehmer digits are zero, so the code is the empty slice for every `element_count`,
+/// the same bytes `big_lehmer::encode` produces. Decoding it reconstructs the identity.
+///
+/// # Examples
+/// ```
+/// let code = big_lehmer::identity_code(1000);
+/// assert_eq!(code, big_lehmer::encode(&(0..1000).collect::<Vec<u32>>()).unwrap());
+/// let mut results = [7; 4];
+/// big_lehmer::decode(&big_lehmer::identity_code(4), &mut results).unwrap();
+/// assert_eq!(results, [0, 1, 2, 3]);
+/// ```
+#[must_use]
+pub fn identity_code(_element_count: u32) -> Box<[u8]> {
+    Box::default()
+}
+
+/// Whether `numbers` is the reverse permutation `[n - 1, ..., 1, 0]`, the only permutation with the largest rank
+pub(crate) fn is_reverse(numbers: &[u32]) -> bool {
+    numbers
+        .iter()
+        .rev()
+        .enumerate()
+        .all(|(index, &number)| u32::try_from(index) == Ok(number))
+}
+
+/// Code of the largest rank `element_count! - 1`, without encoding.  
+/// Every Lehmer digit has its maximum value, so it is the code of the reverse permutation `[n - 1, ..., 1, 0]`
+/// and the longest code of `element_count` elements, `big_lehmer::get_encode_size` is an upper bound of its length.
+/// Computes the factorial, which is cached up to 1024 elements.
+///
+/// `big_lehmer::encode` and `big_lehmer::decode` recognize the reverse permutation and this code
+/// and skip the tree passes and the big number divisions.
+///
+/// # Examples
+/// ```
+/// let mut results = [0; 8];
+/// big_lehmer::decode(&big_lehmer::max_code(8), &mut results).unwrap();
+/// assert_eq!(results, [7, 6, 5, 4, 3, 2, 1, 0]);
+/// assert_eq!(big_lehmer::max_code(20).len(), 8);
+/// ```
+#[must_use]
+pub fn max_code(element_count: u32) -> Box<[u8]> {
+    max_rank(element_count).to_le_bytes()
+}
+
+/// Code of the reverse permutation `[n - 1, ..., 1, 0]` of `element_count` elements, same as `big_lehmer::max_code`.
+/// Counterpart of `big_lehmer::identity_code`.
+///
+/// # Examples
+/// ```
+/// let reverse: Vec<u32> = (0..1000).rev().collect();
+/// assert_eq!(big_lehmer::reverse_code(1000), big_lehmer::encode(&reverse).unwrap());
+/// ```
+#[must_use]
+pub fn reverse_code(element_count: u32) -> Box<[u8]> {
+    max_code(element_count)
+}
+
+/// Whether `rank` is `element_count! - 1`, the rank of the reverse permutation.  
+/// `element_count!` is divisible by `2^(element_count - popcount(element_count))`, so the largest rank ends with that many one bits.
+/// Other codes almost always fail that check on the lowest 64 bits, only then the factorial is computed.
+fn is_max_rank(rank: &UBig, element_count: usize) -> bool {
+    let Ok(element_count) = u32::try_from(element_count) else {
+        return false;
+    };
+    let low_ones = (element_count - element_count.count_ones()).min(64);
+    (0..low_ones as usize).all(|bit| rank.bit(bit)) && *rank == max_rank(element_count)
+}
+
+fn max_rank(element_count: u32) -> UBig {
+    // 0! - 1 and 1! - 1 are both 0
+    permutation_count(element_count) - UBig::ONE
+}
+
+/// Same as `big_lehmer::encode`, but returns a `Vec<u8>`.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode_to_vec(&sequence).unwrap();
+/// assert_eq!(*encoded, *big_lehmer::encode(&sequence).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_to_vec(numbers: &[u32]) -> Result<Vec<u8>, Error> {
+    encode(numbers).map(Vec::from)
+}
+
+/// Same as `big_lehmer::encode`, but appends the code to `buf`.  
+/// Useful for packing several codes into one buffer, the caller has to store the code lengths to split them again.
+/// `buf` is unchanged on error.
+///
+/// # Examples
+/// ```
+/// let mut buf = vec![0xFF];
+/// big_lehmer::encode_append(&[3, 2, 1, 0], &mut buf).unwrap();
+/// assert_eq!(buf, [0xFF, 23]);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_append(numbers: &[u32], buf: &mut Vec<u8>) -> Result<(), Error> {
+    buf.extend_from_slice(&encode(numbers)?);
+    Ok(())
+}
+
+/// Same as `big_lehmer::encode`, but writes the code into uninitialized memory and returns the written part of `out`.  
+/// Avoids zero initializing the output buffer, e.g. when writing into a preallocated arena.
+/// Only the returned bytes are initialized, the rest of `out` is left untouched.
+///
+/// # Examples
+/// ```
+/// use std::mem::MaybeUninit;
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let mut out = [MaybeUninit::uninit(); 16];
+/// let encoded = big_lehmer::encode_into_uninit(&sequence, &mut out).unwrap();
+/// assert_eq!(*encoded, *big_lehmer::encode(&sequence).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// `Error::OutVectorSize` when `out` is smaller than `big_lehmer::get_encode_size(numbers.len())`.  
+/// Same as `big_lehmer::encode`
+pub fn encode_into_uninit<'a>(
+    numbers: &[u32],
+    out: &'a mut [std::mem::MaybeUninit<u8>],
+) -> Result<&'a mut [u8], Error> {
     let element_count = u32::try_from(numbers.len()).map_err(|_| Error::SequenceToLong {
         element_count: numbers.len(),
     })?;
+    let size_error = Error::OutVectorSize {
+        byte_size: out.len(),
+        element_count,
+    };
+    if out.len() < get_encode_size(element_count) {
+        return Err(size_error);
+    }
+    let code = encode(numbers)?;
+    let out = out.get_mut(..code.len()).ok_or(size_error)?;
+    for (byte, &value) in out.iter_mut().zip(code.iter()) {
+        byte.write(value);
+    }
+    // Safety: every byte of out was written above, MaybeUninit<u8> has the same layout as u8
+    Ok(unsafe { &mut *(std::ptr::from_mut(out) as *mut [u8]) })
+}
 
-    let mut encode_as = EncodeAS::new(element_count);
-    let mut validation = vec![false; numbers.len()];
-    let mut cache = Cache::default();
-    let mut caches = vec![];
-    for (index, &number) in numbers[..numbers.len() - 1].iter().enumerate() {
-        // Validation is basically free
-        let visited = validation
-            .get_mut(number as usize)
-            .ok_or(Error::ValidationOutOfRange)?;
-        if *visited {
-            return Err(Error::ValidationDuplicateNumber);
-        }
-        *visited = true;
-
-        let add = u64::from(encode_as.insert(number));
-        let mul = u64::try_from(numbers.len() - (index + 1)).unwrap();
+/// Same as `big_lehmer::encode`, but skips the validation of the input.  
+/// Saves the validation pass and its `numbers.len() / 8` bytes of memory, when the input is known to be valid.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// // Safety: sequence is a permutation of 0..8
+/// let encoded = unsafe { big_lehmer::encode_unchecked(&sequence) };
+/// assert_eq!(encoded, big_lehmer::encode(&sequence).unwrap());
+/// ```
+///
+/// # Safety
+///
+/// `numbers` must be a permutation of `0..numbers.len()`, every number exactly once.  
+/// Invalid input is undefined behavior. The current implementation produces a wrong code or panics,
+/// but this is not guaranteed to stay that way.
+///
+/// # Panics
+///
+/// Panics when `numbers` has more than `u32::MAX` elements
+/// or when the internal tree does not fit into memory (e.g. on 16 bit systems).
+#[must_use]
+pub unsafe fn encode_unchecked(numbers: &[u32]) -> Box<[u8]> {
+    Encoder::new().encode_unchecked(numbers)
+}
 
-        // Naive approach would now do result += add and result *= mul
-        // with the cache we reduce the big number interactions
-        if cache.add(add, mul).is_none() {
-            caches.push(cache);
-            cache = Cache::new(add, mul);
-        }
-    }
-    caches.push(cache);
+/// Same as `big_lehmer::encode`, but for permutations of `1..=N` instead of `0..N`.  
+/// Decode with `big_lehmer::decode_one_based`
+///
+/// # Examples
+/// ```
+/// let sequence = [3, 1, 2];
+/// let encoded = big_lehmer::encode_one_based(&sequence).unwrap();
+/// assert_eq!(encoded, big_lehmer::encode(&[2, 0, 1]).unwrap());
+/// let mut roundtrip = [0; 3];
+/// big_lehmer::decode_one_based(&encoded, &mut roundtrip).unwrap();
+/// assert_eq!(sequence, roundtrip);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`. `0` and `N + 1` are out of range.
+pub fn encode_one_based(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
+    encode_range(numbers, 1)
+}
 
-    // Combine the smaller caches into final result
-    // Besides parallelism, the reduce also keeps the UBig small for the majority of the steps
-    let result = caches
-        .par_iter()
-        .map(BigCache::new)
-        .reduce(BigCache::identity, BigCache::combine);
+/// Same as `big_lehmer::encode`, but for permutations of the range `start..start + N` instead of `0..N`.  
+/// The numbers are mapped onto `0..N` by subtracting `start`, so the code is the same as for the shifted sequence.
+/// Decode with `big_lehmer::decode_range`
+///
+/// # Examples
+/// ```
+/// let sequence = [102, 100, 101];
+/// let encoded = big_lehmer::encode_range(&sequence, 100).unwrap();
+/// assert_eq!(encoded, big_lehmer::encode(&[2, 0, 1]).unwrap());
+/// let mut roundtrip = [0; 3];
+/// big_lehmer::decode_range(&encoded, 100, &mut roundtrip).unwrap();
+/// assert_eq!(sequence, roundtrip);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`. Numbers outside of `start..start + N` are out of range,
+/// the errors report the original number.
+pub fn encode_range(numbers: &[u32], start: u32) -> Result<Box<[u8]>, Error> {
+    Encoder::new().encode_offset(numbers, start)
+}
 
-    Ok(result.add.to_le_bytes())
+/// Encodes a window of a larger permutation whose numbers form a contiguous range `start..start + N`,
+/// `start` being the smallest number of the window.  
+/// Same as `big_lehmer::encode_range(numbers, start)`, returns the code and `start`, decode with `big_lehmer::decode_range`.
+///
+/// # Examples
+/// ```
+/// let arena = [5, 3, 9, 7, 8, 6, 0, 1];
+/// let (encoded, start) = big_lehmer::encode_subrange(&arena[2..6]).unwrap();
+/// assert_eq!(start, 6);
+/// assert_eq!(encoded, big_lehmer::encode(&[3, 1, 2, 0]).unwrap());
+/// let mut roundtrip = [0; 4];
+/// big_lehmer::decode_range(&encoded, start, &mut roundtrip).unwrap();
+/// assert_eq!(roundtrip, arena[2..6]);
+/// // 3 and 5 leave a gap for 4
+/// assert!(big_lehmer::encode_subrange(&arena[..2]).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode_range`. When the numbers are not contiguous,
+/// the first number that does not fit into `start..start + N` is reported with its original value.
+pub fn encode_subrange(numbers: &[u32]) -> Result<(Box<[u8]>, u32), Error> {
+    let start = numbers.iter().copied().min().unwrap_or(0);
+    Ok((encode_range(numbers, start)?, start))
 }
 
 /// Decodes a Lehmer code generated by `big_lehmer::encode`  
@@ -124,9 +572,13 @@ pub fn encode(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
 ///
 /// # Errors
 ///
-/// Can error when the code was created with more elements than you are trying to decode.  
+/// Errors with `Error::ElementCountMismatch` when the code was created with more elements than you are trying to decode.  
+/// A code created with fewer elements can not be detected, since it is also a valid code for more elements.
+/// It decodes to a different permutation. Use `big_lehmer::encode_self_describing` when the element count needs to be checked,
+/// or `big_lehmer::decode_prefix_fill` to decode into a longer (padded) slice.  
 /// Can error when `encoded` is not a valid lehmer code  
 /// Will error when `results` has more than `u32::MAX` elements.  
+/// Will error when the internal tree does not fit into memory (e.g. on 16 bit systems)  
 ///
 /// # Examples
 /// ```
@@ -137,38 +589,714 @@ pub fn encode(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
 /// big_lehmer::decode(&encoded, &mut roundtrip).unwrap();
 /// assert_eq!(sequence, *roundtrip);
 /// ```
+pub fn decode(encoded: &[u8], results: &mut [u32]) -> Result<(), Error> {
+    let element_count = u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+        element_count: results.len(),
+    })?;
+    let mut remainders = vec![0; results.len()];
+    let mut tree = OrderStatisticTree::new(element_count)?;
+    decode_in(encoded, results, &mut remainders, &mut tree)
+}
+
+/// Same as `big_lehmer::decode`, but allocates and returns the permutation of `element_count` elements.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode(&sequence).unwrap();
+/// assert_eq!(big_lehmer::decode_to_vec(&encoded, 8).unwrap(), sequence);
+/// ```
 ///
-/// # Panics
+/// # Errors
 ///
-/// Generally it should not panic. There might be panics on 16 bit systems.
-pub fn decode(encoded: &[u8], results: &mut [u32]) -> Result<(), Error> {
-    if results.is_empty() {
+/// Same as `big_lehmer::decode`
+pub fn decode_to_vec(encoded: &[u8], element_count: u32) -> Result<Vec<u32>, Error> {
+    let len = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    let mut results = vec![0; len];
+    decode(encoded, &mut results)?;
+    Ok(results)
+}
+
+/// Same as `big_lehmer::decode`, but calls `f(position, element)` for every element in position order
+/// instead of writing them into a `results` slice.  
+/// For consumers that stream the elements away (writing to disk, updating other structures) without storing them.
+/// The factoradic digits (4 byte per element) and the tree are still allocated, only the results slice is saved.
+///
+/// The code is fully checked before the first call: on error `f` is never called.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[2, 0, 1]).unwrap();
+/// let mut pairs = vec![];
+/// big_lehmer::decode_for_each(&encoded, 3, |position, element| pairs.push((position, element))).unwrap();
+/// assert_eq!(pairs, [(0, 2), (1, 0), (2, 1)]);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_for_each<F: FnMut(u32, u32)>(
+    encoded: &[u8],
+    element_count: u32,
+    mut f: F,
+) -> Result<(), Error> {
+    try_decode_for_each(encoded, element_count, |position, element| {
+        f(position, element);
+        Ok(())
+    })
+}
+
+/// Decodes the inverse permutation into `inverse`: afterwards `inverse[element] == position`,
+/// where `big_lehmer::decode` would have written `results[position] == element`.  
+/// Saves decoding into a temporary `results` slice and inverting it,
+/// the inverse is the table to gather by value, e.g. with `big_lehmer::Permutation::apply`.
+///
+/// For a sparse inverse, e.g. a `HashMap` of some values only, use `big_lehmer::decode_for_each` directly:
+/// ```
+/// use std::collections::HashMap;
+/// let encoded = big_lehmer::encode(&[3, 0, 2, 1]).unwrap();
+/// let mut positions = HashMap::new();
+/// big_lehmer::decode_for_each(&encoded, 4, |position, element| {
+///     if element % 2 == 1 {
+///         positions.insert(element, position);
+///     }
+/// })
+/// .unwrap();
+/// assert_eq!(positions, HashMap::from([(3, 0), (1, 3)]));
+/// ```
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[3, 0, 2, 1]).unwrap();
+/// let mut inverse = [0; 4];
+/// big_lehmer::decode_inverse_into(&encoded, &mut inverse).unwrap();
+/// assert_eq!(inverse, [1, 3, 2, 0]);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`, on error `inverse` is not modified.
+pub fn decode_inverse_into(encoded: &[u8], inverse: &mut [u32]) -> Result<(), Error> {
+    let element_count = u32::try_from(inverse.len()).map_err(|_| Error::SequenceToLong {
+        element_count: inverse.len(),
+    })?;
+    decode_for_each(encoded, element_count, |position, element| {
+        inverse[element as usize] = position;
+    })
+}
+
+/// `big_lehmer::decode_for_each` with a fallible `f`, stops at the first error of `f`
+pub(crate) fn try_decode_for_each<F: FnMut(u32, u32) -> Result<(), Error>>(
+    encoded: &[u8],
+    element_count: u32,
+    mut f: F,
+) -> Result<(), Error> {
+    let input = UBig::from_le_bytes(encoded);
+    let code_bits = input.bit_len();
+    let len = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    let mut remainders = vec![];
+    compute_remainders(input, &mut remainders, len, DecodeConfig::default());
+    check_element_count(&remainders, code_bits)?;
+    let Some(last) = len.checked_sub(1) else {
         return Ok(());
+    };
+    if remainders[..last].iter().any(Option::is_none) {
+        return Err(Error::Decode);
     }
-    // supports up to u32::MAX elements
-    let element_count = u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+
+    let mut tree = OrderStatisticTree::new(element_count)?;
+    // The digits of the first positions are at the end of the remainders, the last element takes digit 0
+    let digits = remainders[..last]
+        .iter()
+        .rev()
+        .map(|digit| digit.unwrap().get() - 1)
+        .chain(std::iter::once(0));
+    for (position, digit) in (0..element_count).zip(digits) {
+        f(position, tree.select_and_remove(digit))?;
+    }
+    Ok(())
+}
+
+/// Decodes a code of `element_count` elements into the first `element_count` positions of a longer (padded) `results` slice.  
+/// The positions after `element_count` are filled with the identity tail `element_count..results.len()`,
+/// so `results` is always a permutation of `0..results.len()`, the one `big_lehmer::embed` encodes.  
+/// `big_lehmer::decode` with the longer slice instead decodes the same rank in the larger space, a different permutation.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[2, 0, 1]).unwrap();
+/// let mut results = [9; 5];
+/// big_lehmer::decode_prefix_fill(&encoded, 3, &mut results).unwrap();
+/// assert_eq!(results, [2, 0, 1, 3, 4]);
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::LengthMismatch` when `results` is shorter than `element_count`, `results` is unchanged then.  
+/// Errors with `Error::SequenceToLong` when `results` has more than `u32::MAX` elements.  
+/// Same as `big_lehmer::decode` for the first `element_count` positions.
+pub fn decode_prefix_fill(
+    encoded: &[u8],
+    element_count: u32,
+    results: &mut [u32],
+) -> Result<(), Error> {
+    let total = u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
         element_count: results.len(),
     })?;
+    let prefix_len = usize::try_from(element_count)
+        .ok()
+        .filter(|&prefix_len| prefix_len <= results.len())
+        .ok_or(Error::LengthMismatch {
+            expected: usize::try_from(element_count).unwrap_or(usize::MAX),
+            got: results.len(),
+        })?;
+    let (prefix, tail) = results.split_at_mut(prefix_len);
+    decode(encoded, prefix)?;
+    for (number, result) in (element_count..total).zip(tail) {
+        *result = number;
+    }
+    Ok(())
+}
 
+/// Same as `big_lehmer::decode`, for untrusted input.  
+/// After the divisions it explicitly confirms that the leftover of the code is zero
+/// (the code is smaller than `results.len()!`) and that every factoradic digit slot was set,
+/// and finally validates the decoded permutation like `big_lehmer::is_valid_permutation`.  
+/// Out of range codes are rejected with `Error::Decode` instead of `Error::ElementCountMismatch`,
+/// exactly the codes that `big_lehmer::is_canonical_code` rejects once the high zero bytes are dropped.
+/// The leftover digit is the range check, so it costs no additional `results.len()!`.
+///
+/// `big_lehmer::decode` already reports a leftover and unset slots as errors instead of panicking,
+/// so the results are the same for every input. The additional permutation validation is a defense in depth,
+/// it costs one pass over `results` and `results.len()` bytes, small next to the divisions and the tree.
+///
+/// A truncated code (missing high bytes) is still a valid smaller code and decodes to a different permutation,
+/// same as a corrupted code that stays below `results.len()!`. Use `big_lehmer::encode_with_checksum` to detect those.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[2, 0, 1]).unwrap();
+/// let mut results = [0; 3];
+/// big_lehmer::decode_checked(&encoded, &mut results).unwrap();
+/// assert_eq!(results, [2, 0, 1]);
+/// // 6 is not a valid code of 3 elements
+/// assert!(big_lehmer::decode_checked(&[6], &mut results).is_err());
+/// ```
+///
+/// # Errors
+///
+/// `Error::Decode` when the code is not smaller than `results.len()!`,
+/// a digit slot is unset or the result is not a valid permutation.  
+/// Same as `big_lehmer::decode`
+pub fn decode_checked(encoded: &[u8], results: &mut [u32]) -> Result<(), Error> {
+    let element_count = u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+        element_count: results.len(),
+    })?;
+    let input = UBig::from_le_bytes(encoded);
+    let code_bits = input.bit_len();
+    if let Some(result) = trivial_decode(code_bits, results) {
+        return result.map_err(|_| Error::Decode);
+    }
     let mut remainders = vec![None; results.len()];
+    compute_remainders_in(input, &mut remainders, DecodeConfig::default());
+    check_element_count(&remainders, code_bits).map_err(|_| Error::Decode)?;
+    // The last slot is the leftover, the digit of the last element is always 0
+    if remainders[..results.len().saturating_sub(1)]
+        .iter()
+        .any(Option::is_none)
+    {
+        return Err(Error::Decode);
+    }
+    let mut tree = OrderStatisticTree::new(element_count)?;
+    remainders_to_permutation(&remainders, results, &mut tree)?;
+    is_valid_permutation(results).map_err(|_| Error::Decode)
+}
 
-    let input: UBig = UBig::from_le_bytes(encoded);
-    let work = WorkItem {
-        dividend: input,
-        start_index: 2,
-        remainders: &mut remainders,
+/// Whether `encoded` is the canonical code of a permutation of `element_count` elements,
+/// the exact bytes `big_lehmer::encode` produces.  
+/// False when the rank is not smaller than `element_count!` (out of range)
+/// or when the code has superfluous high zero bytes, which represent the same rank as the shorter code.
+/// Costs the big number product `element_count!` when the code is long enough to be out of range, but no decode.
+///
+/// # Examples
+/// ```
+/// assert!(big_lehmer::is_canonical_code(&[5], 3));
+/// assert!(big_lehmer::is_canonical_code(&[], 3));
+/// // 6 is not smaller than 3!
+/// assert!(!big_lehmer::is_canonical_code(&[6], 3));
+/// // Same rank as [5]
+/// assert!(!big_lehmer::is_canonical_code(&[5, 0], 3));
+/// ```
+#[must_use]
+pub fn is_canonical_code(encoded: &[u8], element_count: u32) -> bool {
+    let Some(code_bits) = canonical_code_bits(encoded) else {
+        return false;
+    };
+    element_count >= element_count_bound(code_bits)
+        || UBig::from_le_bytes(encoded) < permutation_count(element_count)
+}
+
+/// Bit length of the rank of `encoded`, `None` when the code has high zero bytes
+fn canonical_code_bits(encoded: &[u8]) -> Option<usize> {
+    match encoded.last() {
+        None => Some(0),
+        Some(0) => None,
+        Some(last) => Some(encoded.len() * 8 - last.leading_zeros() as usize),
+    }
+}
+
+/// Element count `m` with `m! >= 2^code_bits`, every code with `code_bits` bits is a code of `m` or more elements.  
+/// Three elements above the lower bound of `min_element_count`
+fn element_count_bound(code_bits: usize) -> u32 {
+    u32::try_from(min_element_count(code_bits) + 3).unwrap_or(u32::MAX)
+}
+
+/// Fuzz entry point: decodes arbitrary bytes as a code of `element_count` elements, never panics on any byte input.  
+/// Only accepts canonical codes, the exact bytes `big_lehmer::encode` produces:
+/// the rank is smaller than `element_count!` and the code has no high zero bytes.
+/// So every `Ok` result encodes back to `encoded`.
+///
+/// This is the function a `cargo fuzz` target would call.
+/// It allocates O(`element_count`) memory, a fuzz target should bound `element_count`.
+///
+/// # Examples
+/// ```
+/// assert_eq!(big_lehmer::try_decode_any(&[3], 3).unwrap(), [1, 2, 0]);
+/// // Not canonical: high zero byte, rank 6 is not smaller than 3!
+/// assert!(big_lehmer::try_decode_any(&[3, 0], 3).is_err());
+/// assert!(big_lehmer::try_decode_any(&[6], 3).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::Decode` for non-canonical codes.  
+/// Errors with `Error::SequenceToLong` when the results or the internal tree do not fit into memory.
+pub fn try_decode_any(encoded: &[u8], element_count: u32) -> Result<Vec<u32>, Error> {
+    if encoded.last() == Some(&0) {
+        return Err(Error::Decode);
+    }
+    let len = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    let mut results = Vec::new();
+    results
+        .try_reserve_exact(len)
+        .map_err(|_| Error::SequenceToLong { element_count: len })?;
+    results.resize(len, 0);
+    decode_checked(encoded, &mut results)?;
+    Ok(results)
+}
+
+/// Same checks as `big_lehmer::try_decode_any`, with work and memory bounded by the length of `encoded`
+/// instead of `element_count`, for element counts from untrusted input.  
+/// A code that is smaller than `m!` decodes to the identity on the first `element_count - m` positions,
+/// so only the permutation of the last `m` elements is decoded.
+pub(crate) fn validate_code(encoded: &[u8], element_count: u32) -> Result<(), Error> {
+    let code_bits = canonical_code_bits(encoded).ok_or(Error::Decode)?;
+    let bound = element_count_bound(code_bits);
+    try_decode_any(encoded, element_count.min(bound)).map(drop)
+}
+
+/// Allocation free primitive behind `big_lehmer::decode`, the caller provides all buffers.  
+/// `remainders` holds the factoradic digits, it needs at least `results.len()` slots, its content is overwritten.
+/// `tree` has to be created (or reset) for `results.len()` elements, it is reset before use,
+/// so it can be passed to the next call with the same length as is.
+///
+/// # Examples
+/// ```
+/// use big_lehmer::ranking::OrderStatisticTree;
+/// let mut remainders = [0; 8];
+/// let mut tree = OrderStatisticTree::new(8).unwrap();
+/// let mut roundtrip = [0; 8];
+/// for sequence in [[7, 2, 0, 6, 5, 1, 4, 3], [0, 1, 2, 3, 4, 5, 6, 7]] {
+///     let encoded = big_lehmer::encode(&sequence).unwrap();
+///     big_lehmer::decode_in(&encoded, &mut roundtrip, &mut remainders, &mut tree).unwrap();
+///     assert_eq!(sequence, roundtrip);
+/// }
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::LengthMismatch` when `remainders` is shorter than `results`
+/// or `tree` was not created for `results.len()` elements.  
+/// Same as `big_lehmer::decode`
+pub fn decode_in(
+    encoded: &[u8],
+    results: &mut [u32],
+    remainders: &mut [u32],
+    tree: &mut OrderStatisticTree,
+) -> Result<(), Error> {
+    let element_count = u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+        element_count: results.len(),
+    })?;
+    let Some(remainders) = remainders.get_mut(..results.len()) else {
+        return Err(Error::LengthMismatch {
+            expected: results.len(),
+            got: remainders.len(),
+        });
     };
-    recursive_split_divide(work);
-
-    let mut decode_as = DecodeAS::new(element_count);
-    for (index, &t) in remainders[0..results.len() - 1].iter().rev().enumerate() {
-        if let Some(t) = t {
-            results[index] = decode_as.remove(t.get() - 1);
-        } else {
-            return Err(Error::Decode);
+    if tree.element_count() != element_count {
+        return Err(Error::LengthMismatch {
+            expected: results.len(),
+            got: tree.element_count() as usize,
+        });
+    }
+    // Safety: Option<NonZeroU32> is guaranteed to have the same layout as u32, with None as 0.
+    // Every u32 is a valid Option<NonZeroU32> and the length stays the same.
+    let remainders = unsafe { &mut *(std::ptr::from_mut(remainders) as *mut [Option<NonZeroU32>]) };
+    remainders.fill(None);
+    // Same size as before, does not allocate
+    tree.reset(element_count)?;
+    decode_ubig_in(
+        UBig::from_le_bytes(encoded),
+        results,
+        remainders,
+        tree,
+        DecodeConfig::default(),
+    )
+}
+
+/// Same as `big_lehmer::decode`, but reuses the buffers of `scratch` across calls.  
+/// The buffers are only reallocated when `results` is longer than in any previous call.
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+///
+/// # Examples
+/// ```
+/// let mut scratch = big_lehmer::DecodeScratch::new();
+/// let mut roundtrip = [0; 4];
+/// for sequence in [[0, 1, 2, 3], [3, 1, 0, 2]] {
+///     let encoded = big_lehmer::encode(&sequence).unwrap();
+///     big_lehmer::decode_with_scratch(&encoded, &mut roundtrip, &mut scratch).unwrap();
+///     assert_eq!(sequence, roundtrip);
+/// }
+/// ```
+pub fn decode_with_scratch(
+    encoded: &[u8],
+    results: &mut [u32],
+    scratch: &mut DecodeScratch,
+) -> Result<(), Error> {
+    decode_ubig_with_scratch(
+        UBig::from_le_bytes(encoded),
+        results,
+        scratch,
+        DecodeConfig::default(),
+    )
+}
+
+/// Same as `big_lehmer::decode`, but with an explicit strategy for computing the factoradic digits.  
+/// All strategies produce identical results.
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+///
+/// # Examples
+/// ```
+/// use big_lehmer::DecodeStrategy;
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode(&sequence).unwrap();
+/// let mut roundtrip = [0; 8];
+/// big_lehmer::decode_with_strategy(&encoded, &mut roundtrip, DecodeStrategy::Serial).unwrap();
+/// assert_eq!(sequence, roundtrip);
+/// ```
+pub fn decode_with_strategy(
+    encoded: &[u8],
+    results: &mut [u32],
+    strategy: DecodeStrategy,
+) -> Result<(), Error> {
+    decode_ubig_with_scratch(
+        UBig::from_le_bytes(encoded),
+        results,
+        &mut DecodeScratch::default(),
+        DecodeConfig::default().with_strategy(strategy),
+    )
+}
+
+/// Same as `big_lehmer::decode`, but with explicit decode settings, see `big_lehmer::DecodeConfig`.  
+/// All settings produce identical results.
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_with_config(
+    encoded: &[u8],
+    results: &mut [u32],
+    config: DecodeConfig,
+) -> Result<(), Error> {
+    decode_ubig_with_scratch(
+        UBig::from_le_bytes(encoded),
+        results,
+        &mut DecodeScratch::default(),
+        config,
+    )
+}
+
+/// The rank of `numbers` as big number, `big_lehmer::encode` without the conversion to bytes
+pub(crate) fn encode_to_ubig(numbers: &[u32]) -> Result<UBig, Error> {
+    Encoder::new().encode_to_ubig(numbers)
+}
+
+/// Decodes the permutation of the given rank, `big_lehmer::decode` without the conversion from bytes
+pub(crate) fn decode_from_ubig(rank: UBig, results: &mut [u32]) -> Result<(), Error> {
+    decode_ubig_with_scratch(
+        rank,
+        results,
+        &mut DecodeScratch::default(),
+        DecodeConfig::default(),
+    )
+}
+
+fn decode_ubig_with_scratch(
+    input: UBig,
+    results: &mut [u32],
+    scratch: &mut DecodeScratch,
+    config: DecodeConfig,
+) -> Result<(), Error> {
+    // supports up to u32::MAX elements
+    let element_count = u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+        element_count: results.len(),
+    })?;
+    scratch.remainders.clear();
+    scratch.remainders.resize(results.len(), None);
+    scratch.reset_tree(element_count)?;
+    decode_ubig_in(
+        input,
+        results,
+        &mut scratch.remainders,
+        &mut scratch.tree,
+        config,
+    )
+}
+
+/// `remainders` has `results.len()` slots that are all `None`, `tree` is reset to `results.len()` elements
+fn decode_ubig_in(
+    input: UBig,
+    results: &mut [u32],
+    remainders: &mut [Option<NonZeroU32>],
+    tree: &mut OrderStatisticTree,
+    config: DecodeConfig,
+) -> Result<(), Error> {
+    let code_bits = input.bit_len();
+    if let Some(result) = trivial_decode(code_bits, results) {
+        return result;
+    }
+    if is_max_rank(&input, results.len()) {
+        for (number, result) in (0..).zip(results.iter_mut().rev()) {
+            *result = number;
         }
+        return Ok(());
     }
-    *results.last_mut().unwrap() = decode_as.remove(0);
+    compute_remainders_in(input, remainders, config);
+    check_element_count(remainders, code_bits)?;
+    remainders_to_permutation_with(remainders, results, tree, config.naive_threshold())
+}
+
+/// Same as `big_lehmer::decode`, but produces a permutation of `1..=N` instead of `0..N`.  
+/// Counterpart of `big_lehmer::encode_one_based`
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_one_based(encoded: &[u8], results: &mut [u32]) -> Result<(), Error> {
+    decode_range(encoded, 1, results)
+}
 
+/// Same as `big_lehmer::decode`, but produces a permutation of `start..start + N` instead of `0..N`.  
+/// Counterpart of `big_lehmer::encode_range`
+///
+/// # Errors
+///
+/// Errors with `Error::SequenceToLong` when `start + N - 1` does not fit into a `u32`.  
+/// Otherwise same as `big_lehmer::decode`
+pub fn decode_range(encoded: &[u8], start: u32, results: &mut [u32]) -> Result<(), Error> {
+    let last = u32::try_from(results.len().saturating_sub(1))
+        .ok()
+        .and_then(|last| start.checked_add(last));
+    if last.is_none() {
+        return Err(Error::SequenceToLong {
+            element_count: results.len(),
+        });
+    }
+    decode(encoded, results)?;
+    // Can not overflow, checked above
+    for result in results {
+        *result += start;
+    }
     Ok(())
 }
+
+/// Decodes a code of `element_count` elements into `(position, element)` pairs, in position order.  
+/// Saves the zip at call sites building scatter / gather tables.
+/// For the other direction, element to position, see `big_lehmer::Permutation::inverse`.
+///
+/// Needs twice the memory of the plain permutation,
+/// iterate `big_lehmer::decode` output with `enumerate` to avoid the pair vector.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[2, 0, 1]).unwrap();
+/// let pairs = big_lehmer::decode_enumerated(&encoded, 3).unwrap();
+/// assert_eq!(pairs, [(0, 2), (1, 0), (2, 1)]);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_enumerated(encoded: &[u8], element_count: u32) -> Result<Vec<(u32, u32)>, Error> {
+    let results = decode_to_vec(encoded, element_count)?;
+    Ok((0..element_count).zip(results).collect())
+}
+
+/// Decodes only the element at `position` of a Lehmer code of `element_count` elements.  
+/// Avoids materializing the full permutation.
+///
+/// This is not sublinear: all factoradic digits still have to be computed (same cost as the big number part of `decode`),
+/// only the tree walk stops after `position`, costing O(position * log N).
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode(&sequence).unwrap();
+/// assert_eq!(big_lehmer::decode_at(&encoded, 8, 3).unwrap(), 6);
+/// ```
+///
+/// # Errors
+///
+/// Will error when `position >= element_count`.  
+/// Same errors as `big_lehmer::decode`
+pub fn decode_at(encoded: &[u8], element_count: u32, position: u32) -> Result<u32, Error> {
+    let mut result = [0];
+    decode_at_many(encoded, element_count, &[position], &mut result)?;
+    Ok(result[0])
+}
+
+/// Decodes the elements at the ascending `positions` of a Lehmer code of `element_count` elements into `out`.  
+/// Computes the factoradic digits once and services all positions with a single tree walk,
+/// instead of one `big_lehmer::decode_at` per position. The walk stops after the last position.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode(&sequence).unwrap();
+/// let mut out = [0; 3];
+/// big_lehmer::decode_at_many(&encoded, 8, &[1, 3, 6], &mut out).unwrap();
+/// assert_eq!(out, [2, 6, 4]);
+/// ```
+///
+/// # Errors
+///
+/// Will error when `out` and `positions` have different lengths.  
+/// Will error when `positions` is not sorted ascending or a position is `>= element_count`.  
+/// Same errors as `big_lehmer::decode`
+pub fn decode_at_many(
+    encoded: &[u8],
+    element_count: u32,
+    positions: &[u32],
+    out: &mut [u32],
+) -> Result<(), Error> {
+    if positions.len() != out.len() {
+        return Err(Error::LengthMismatch {
+            expected: positions.len(),
+            got: out.len(),
+        });
+    }
+    if let Some(index) = positions.windows(2).position(|pair| pair[0] > pair[1]) {
+        return Err(Error::PositionsNotSorted { index: index + 1 });
+    }
+    let Some(&last) = positions.last() else {
+        return Ok(());
+    };
+    if last >= element_count {
+        return Err(Error::PositionOutOfRange {
+            position: last,
+            element_count,
+        });
+    }
+    let len = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    let mut tree = OrderStatisticTree::new(element_count)?;
+
+    let input = UBig::from_le_bytes(encoded);
+    let code_bits = input.bit_len();
+    let mut remainders = vec![];
+    compute_remainders(input, &mut remainders, len, DecodeConfig::default());
+    check_element_count(&remainders, code_bits)?;
+
+    let digits = remainders[..len - 1]
+        .iter()
+        .rev()
+        .map(|t| t.map(|t| t.get() - 1))
+        .chain(std::iter::once(Some(0)));
+    let mut requests = positions.iter().zip(out.iter_mut()).peekable();
+    for (position, digit) in (0..=last).zip(digits) {
+        let number = tree.select_and_remove(digit.ok_or(Error::Decode)?);
+        while let Some((_, result)) = requests.next_if(|(&requested, _)| requested == position) {
+            *result = number;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Seeded random permutation of `0..len`, shared by the unit tests
+    pub(crate) fn shuffled(len: u32, seed: u64) -> Vec<u32> {
+        use rand::{seq::SliceRandom, SeedableRng};
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(seed);
+        let mut sequence: Vec<u32> = (0..len).collect();
+        sequence.shuffle(&mut rng);
+        sequence
+    }
+
+    #[test]
+    fn test_stirling_encode_size() {
+        for element_count in [2u32, 10, 1000, 100_000] {
+            let exact = permutation_count(element_count).bit_len().div_ceil(8);
+            let estimate = stirling_encode_size(u64::from(element_count));
+            assert!(estimate >= exact);
+            assert!(estimate <= exact + 40);
+        }
+        assert_eq!(get_encode_size_u64(u64::MAX), usize::MAX);
+        assert!(get_encode_size_u64(u64::from(u32::MAX) + 1) > stirling_encode_size(1 << 31));
+    }
+
+    #[test]
+    fn test_element_count_bound() {
+        // Every code with code_bits bits is smaller than the factorial of the bound
+        let mut factorial = UBig::ONE;
+        let mut factorial_of = 0;
+        for code_bits in 0..20_000 {
+            let bound = element_count_bound(code_bits);
+            while factorial_of < bound {
+                factorial_of += 1;
+                factorial *= factorial_of;
+            }
+            assert!(factorial.bit_len() > code_bits, "{code_bits}");
+        }
+    }
+
+    #[test]
+    fn test_small_encode_size_table() {
+        for element_count in 0..=SMALL_ENCODE_SIZE_MAX {
+            assert_eq!(
+                get_encode_size(element_count),
+                encode_size_summation(element_count),
+                "{element_count}"
+            );
+        }
+        assert_eq!(
+            get_encode_size(SMALL_ENCODE_SIZE_MAX + 1),
+            encode_size_summation(SMALL_ENCODE_SIZE_MAX + 1)
+        );
+    }
+}
@@ -0,0 +1,130 @@
+use dashu::integer::{UBig, Word};
+
+use crate::{decode_from_ubig, encode_to_ubig, error::Error};
+
+/// Same as `big_lehmer::encode`, but returns the code as little endian `u64` limbs (least significant limb first)
+/// instead of bytes, read directly from the words of the big number.  
+/// Returns the minimal number of limbs, the identity permutation (rank 0) has no limbs.
+/// Decode with `big_lehmer::decode_from_limbs`.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// // Rank 36835
+/// assert_eq!(big_lehmer::encode_to_limbs(&sequence).unwrap(), [36835]);
+/// assert!(big_lehmer::encode_to_limbs(&[0, 1, 2]).unwrap().is_empty());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_to_limbs(numbers: &[u32]) -> Result<Vec<u64>, Error> {
+    Ok(limbs_from_ubig(&encode_to_ubig(numbers)?))
+}
+
+/// Same as `big_lehmer::decode`, but the code is given as little endian `u64` limbs (least significant limb first)
+/// instead of bytes.  
+/// Trailing zero limbs are allowed.
+///
+/// `dashu` always owns the words of a `UBig`, it can not borrow the input.
+/// So the limbs are still copied once, but the byte interpretation of `big_lehmer::decode` is skipped.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode(&sequence).unwrap();
+/// let mut bytes = [0; 8];
+/// bytes[..encoded.len()].copy_from_slice(&encoded);
+///
+/// let mut roundtrip = [0; 8];
+/// big_lehmer::decode_from_limbs(&[u64::from_le_bytes(bytes), 0], &mut roundtrip).unwrap();
+/// assert_eq!(sequence, roundtrip);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_from_limbs(limbs: &[u64], results: &mut [u32]) -> Result<(), Error> {
+    decode_from_ubig(ubig_from_limbs(limbs), results)
+}
+
+/// `dashu::integer::Word` depends on the target, it is `u64` on most 64 bit targets
+#[allow(clippy::cast_possible_truncation, clippy::unnecessary_cast)]
+pub(crate) fn ubig_from_limbs(limbs: &[u64]) -> UBig {
+    const WORDS_PER_LIMB: u32 = u64::BITS / Word::BITS;
+    let words: Vec<Word> = limbs
+        .iter()
+        .flat_map(|&limb| (0..WORDS_PER_LIMB).map(move |i| (limb >> (i * Word::BITS)) as Word))
+        .collect();
+    UBig::from_words(&words)
+}
+
+/// Inverse of `ubig_from_limbs`, without trailing zero limbs
+#[allow(clippy::unnecessary_cast)]
+pub(crate) fn limbs_from_ubig(number: &UBig) -> Vec<u64> {
+    const WORDS_PER_LIMB: usize = (u64::BITS / Word::BITS) as usize;
+    let mut limbs: Vec<u64> = number
+        .as_words()
+        .chunks(WORDS_PER_LIMB)
+        .map(|words| {
+            words.iter().enumerate().fold(0, |limb, (i, &word)| {
+                limb | (word as u64) << (i as u32 * Word::BITS)
+            })
+        })
+        .collect();
+    while limbs.last() == Some(&0) {
+        limbs.pop();
+    }
+    limbs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{decode, encode};
+
+    #[test]
+    fn test_decode_from_limbs() {
+        let sequence: Vec<u32> = crate::tests::shuffled(500, 828);
+        let encoded = encode(&sequence).unwrap();
+        let mut limbs: Vec<u64> = encoded
+            .chunks(8)
+            .map(|chunk| {
+                let mut bytes = [0; 8];
+                bytes[..chunk.len()].copy_from_slice(chunk);
+                u64::from_le_bytes(bytes)
+            })
+            .collect();
+        limbs.extend([0, 0]);
+
+        let mut expected = vec![0; sequence.len()];
+        decode(&encoded, &mut expected).unwrap();
+        let mut roundtrip = vec![0; sequence.len()];
+        decode_from_limbs(&limbs, &mut roundtrip).unwrap();
+        assert_eq!(roundtrip, expected);
+        assert_eq!(ubig_from_limbs(&[]), UBig::ZERO);
+    }
+
+    #[test]
+    fn test_encode_to_limbs_roundtrip() {
+        let sequence: Vec<u32> = crate::tests::shuffled(500, 853);
+        let limbs = encode_to_limbs(&sequence).unwrap();
+        let encoded = encode(&sequence).unwrap();
+        assert_eq!(limbs.len(), encoded.len().div_ceil(8));
+        assert_eq!(limbs[0].to_le_bytes(), encoded[..8]);
+
+        let mut roundtrip = vec![0; sequence.len()];
+        decode_from_limbs(&limbs, &mut roundtrip).unwrap();
+        assert_eq!(roundtrip, sequence);
+
+        for number in [
+            UBig::ZERO,
+            UBig::ONE,
+            UBig::from(u64::MAX),
+            UBig::from(u128::MAX),
+        ] {
+            assert_eq!(ubig_from_limbs(&limbs_from_ubig(&number)), number);
+        }
+        assert!(limbs_from_ubig(&UBig::ZERO).is_empty());
+    }
+}
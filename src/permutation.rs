@@ -0,0 +1,360 @@
+use std::ops::Deref;
+
+use crate::{
+    decode, encode_unchecked,
+    error::Error,
+    format::{decode_self_describing, EncodedLehmer},
+    validation::is_valid_permutation,
+};
+
+/// Decoded permutation of `0..element_count`, always valid.  
+/// Object oriented entry point over the free functions: it converts from and to Lehmer codes
+/// and derefs to `[u32]` for indexing and iteration.
+///
+/// # Examples
+/// ```
+/// use big_lehmer::Permutation;
+/// let code = big_lehmer::encode(&[2, 0, 1]).unwrap();
+/// let permutation = Permutation::try_from_code(&code, 3).unwrap();
+/// assert_eq!(*permutation, [2, 0, 1]);
+/// assert_eq!(permutation[0], 2);
+/// assert_eq!(*permutation.inverse(), [1, 2, 0]);
+/// assert!(!permutation.parity());
+///
+/// let mut letters = ['a', 'b', 'c'];
+/// permutation.apply(&mut letters).unwrap();
+/// assert_eq!(letters, ['c', 'a', 'b']);
+/// assert_eq!(permutation.to_code(), code);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Default)]
+pub struct Permutation {
+    numbers: Vec<u32>,
+}
+
+impl Permutation {
+    /// Decodes a code of `element_count` elements, same as `big_lehmer::decode`
+    ///
+    /// # Errors
+    ///
+    /// Same as `big_lehmer::decode`
+    pub fn try_from_code(code: &[u8], element_count: u32) -> Result<Self, Error> {
+        let len = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+            element_count: usize::MAX,
+        })?;
+        let mut numbers = vec![0; len];
+        decode(code, &mut numbers)?;
+        Ok(Self { numbers })
+    }
+
+    /// The identity permutation `0..element_count`
+    #[must_use]
+    pub fn identity(element_count: u32) -> Self {
+        Self {
+            numbers: (0..element_count).collect(),
+        }
+    }
+
+    /// The Lehmer code, same as `big_lehmer::encode`
+    ///
+    /// # Panics
+    ///
+    /// Panics when the internal tree does not fit into memory, same as `big_lehmer::encode_unchecked`
+    #[must_use]
+    pub fn to_code(&self) -> Box<[u8]> {
+        // Safety: a Permutation is always a valid permutation
+        unsafe { encode_unchecked(&self.numbers) }
+    }
+
+    /// Number of elements
+    #[must_use]
+    pub fn element_count(&self) -> u32 {
+        // Validated on construction
+        u32::try_from(self.numbers.len()).unwrap()
+    }
+
+    /// The inverse permutation, `inverse[self[i]] == i`
+    #[must_use]
+    pub fn inverse(&self) -> Self {
+        let mut numbers = vec![0; self.numbers.len()];
+        for (index, &number) in (0u32..).zip(&self.numbers) {
+            numbers[number as usize] = index;
+        }
+        Self { numbers }
+    }
+
+    /// Sign of the permutation, `true` when it is odd (an odd number of swaps away from the identity).  
+    /// A cycle of length `k` is `k - 1` swaps, so this walks the cycles in O(N).
+    #[must_use]
+    pub fn parity(&self) -> bool {
+        let mut visited = vec![false; self.numbers.len()];
+        let mut swaps = 0usize;
+        for start in 0..self.numbers.len() {
+            let mut position = start;
+            let mut cycle_len = 0usize;
+            while !visited[position] {
+                visited[position] = true;
+                position = self.numbers[position] as usize;
+                cycle_len += 1;
+            }
+            swaps += cycle_len.saturating_sub(1);
+        }
+        swaps % 2 == 1
+    }
+
+    /// Reorders `data` in place by the permutation: afterwards `data[i]` is the old `data[self[i]]`.  
+    /// Follows the cycles and swaps, so no copy of `data` is needed.
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::LengthMismatch` when `data.len()` differs from the element count.
+    pub fn apply<T>(&self, data: &mut [T]) -> Result<(), Error> {
+        if data.len() != self.numbers.len() {
+            return Err(Error::LengthMismatch {
+                expected: self.numbers.len(),
+                got: data.len(),
+            });
+        }
+        let mut visited = vec![false; self.numbers.len()];
+        for start in 0..self.numbers.len() {
+            if visited[start] {
+                continue;
+            }
+            visited[start] = true;
+            let mut position = start;
+            let mut next = self.numbers[position] as usize;
+            while next != start {
+                data.swap(position, next);
+                visited[next] = true;
+                position = next;
+                next = self.numbers[position] as usize;
+            }
+        }
+        Ok(())
+    }
+
+    /// Collects and validates the numbers, the non panicking version of `collect::<Permutation>()`
+    ///
+    /// # Examples
+    /// ```
+    /// use big_lehmer::Permutation;
+    /// let permutation = Permutation::try_from_iter((0..4).rev()).unwrap();
+    /// assert_eq!(*permutation, [3, 2, 1, 0]);
+    /// assert!(Permutation::try_from_iter([1, 2]).is_err());
+    /// ```
+    ///
+    /// # Errors
+    ///
+    /// Same as `TryFrom<Vec<u32>>`, the validation errors of `big_lehmer::is_valid_permutation`
+    pub fn try_from_iter<I: IntoIterator<Item = u32>>(numbers: I) -> Result<Self, Error> {
+        Self::try_from(numbers.into_iter().collect::<Vec<u32>>())
+    }
+
+    #[must_use]
+    pub fn as_slice(&self) -> &[u32] {
+        &self.numbers
+    }
+
+    #[must_use]
+    pub fn into_vec(self) -> Vec<u32> {
+        self.numbers
+    }
+}
+
+impl Deref for Permutation {
+    type Target = [u32];
+
+    fn deref(&self) -> &Self::Target {
+        &self.numbers
+    }
+}
+
+impl AsRef<[u32]> for Permutation {
+    fn as_ref(&self) -> &[u32] {
+        &self.numbers
+    }
+}
+
+impl TryFrom<Vec<u32>> for Permutation {
+    type Error = Error;
+
+    /// Validates the numbers like `big_lehmer::is_valid_permutation`
+    fn try_from(numbers: Vec<u32>) -> Result<Self, Self::Error> {
+        if u32::try_from(numbers.len()).is_err() {
+            return Err(Error::SequenceToLong {
+                element_count: numbers.len(),
+            });
+        }
+        is_valid_permutation(&numbers)?;
+        Ok(Self { numbers })
+    }
+}
+
+impl IntoIterator for Permutation {
+    type Item = u32;
+    type IntoIter = std::vec::IntoIter<u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.numbers.into_iter()
+    }
+}
+
+impl<'a> IntoIterator for &'a Permutation {
+    type Item = &'a u32;
+    type IntoIter = std::slice::Iter<'a, u32>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.numbers.iter()
+    }
+}
+
+/// Validates the numbers like `big_lehmer::is_valid_permutation`.  
+/// `FromIterator` can not return an error, use `Permutation::try_from_iter` to handle invalid input.
+///
+/// # Examples
+/// ```
+/// use big_lehmer::Permutation;
+/// let permutation: Permutation = [2, 0, 1].into_iter().collect();
+/// let doubled: Vec<u32> = permutation.iter().map(|n| n * 2).collect();
+/// assert_eq!(doubled, [4, 0, 2]);
+/// for (index, number) in permutation.inverse().into_iter().enumerate() {
+///     assert_eq!(permutation[number as usize], index as u32);
+/// }
+/// ```
+///
+/// # Panics
+///
+/// Panics when the numbers are not a permutation of `0..N`
+impl FromIterator<u32> for Permutation {
+    fn from_iter<I: IntoIterator<Item = u32>>(numbers: I) -> Self {
+        match Self::try_from_iter(numbers) {
+            Ok(permutation) => permutation,
+            Err(error) => panic!("{error}"),
+        }
+    }
+}
+
+impl TryFrom<&[u8]> for Permutation {
+    type Error = Error;
+
+    /// Parses a self describing code of `big_lehmer::encode_self_describing`, it carries the element count
+    fn try_from(bytes: &[u8]) -> Result<Self, Self::Error> {
+        let encoded = EncodedLehmer::from_bytes(bytes)?;
+        Ok(Self {
+            numbers: decode_self_describing(&encoded)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{compose_codes, encode, encode_self_describing};
+
+    #[test]
+    fn test_code_roundtrip() {
+        let sequence: Vec<u32> = crate::tests::shuffled(500, 851);
+        let code = encode(&sequence).unwrap();
+        let permutation = Permutation::try_from_code(&code, 500).unwrap();
+        assert_eq!(*permutation, *sequence);
+        assert_eq!(permutation.element_count(), 500);
+        assert_eq!(permutation.to_code(), code);
+
+        let stored = encode_self_describing(&sequence).unwrap();
+        assert_eq!(
+            Permutation::try_from(stored.as_bytes()).unwrap(),
+            permutation
+        );
+        assert_eq!(Permutation::try_from(sequence).unwrap(), permutation);
+        assert!(matches!(
+            Permutation::try_from(vec![0, 0]),
+            Err(Error::ValidationDuplicateNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn test_inverse_and_apply() {
+        let permutation = Permutation::try_from(vec![3, 0, 4, 1, 2]).unwrap();
+        let inverse = permutation.inverse();
+        assert_eq!(*inverse, [1, 3, 4, 0, 2]);
+        let mut data = permutation.to_vec();
+        inverse.apply(&mut data).unwrap();
+        // Gather p[inverse[i]] is the identity
+        assert_eq!(data, [0, 1, 2, 3, 4]);
+
+        // apply gathers like compose_codes
+        let other = Permutation::try_from(vec![1, 4, 0, 3, 2]).unwrap();
+        let mut composed = permutation.to_vec();
+        other.apply(&mut composed).unwrap();
+        let code = compose_codes(&permutation.to_code(), &other.to_code(), 5).unwrap();
+        assert_eq!(
+            Permutation::try_from_code(&code, 5).unwrap().to_vec(),
+            composed
+        );
+
+        assert!(matches!(
+            permutation.apply(&mut [0; 4]),
+            Err(Error::LengthMismatch {
+                expected: 5,
+                got: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn test_iterators() {
+        let sequence: Vec<u32> = crate::tests::shuffled(300, 900);
+        let permutation: Permutation = sequence.iter().copied().collect();
+        assert_eq!(*permutation, *sequence);
+
+        let mut borrowed = vec![];
+        for &number in &permutation {
+            borrowed.push(number);
+        }
+        assert_eq!(borrowed, sequence);
+        let owned: Vec<u32> = permutation.clone().into_iter().collect();
+        assert_eq!(owned, sequence);
+        // Both directions roundtrip
+        assert_eq!(
+            permutation.clone().into_iter().collect::<Permutation>(),
+            permutation
+        );
+        assert_eq!(
+            Permutation::try_from_iter(permutation.iter().copied()).unwrap(),
+            permutation
+        );
+
+        assert!(Permutation::from_iter(std::iter::empty()).is_empty());
+        assert!(matches!(
+            Permutation::try_from_iter([0, 2]),
+            Err(Error::ValidationOutOfRange {
+                index: 1,
+                number: 2
+            })
+        ));
+    }
+
+    #[test]
+    #[should_panic(expected = "contains the number 0 a second time at index 1")]
+    fn test_collect_invalid() {
+        let _: Permutation = [0, 0].into_iter().collect();
+    }
+
+    #[test]
+    fn test_parity() {
+        for (numbers, odd) in [
+            (vec![], false),
+            (vec![0], false),
+            (vec![1, 0], true),
+            (vec![0, 1, 2], false),
+            (vec![1, 2, 0], false),
+            (vec![0, 2, 1], true),
+            (vec![2, 1, 0], true),
+            (vec![1, 0, 3, 2], false),
+            (vec![1, 2, 3, 0], true),
+        ] {
+            let permutation = Permutation::try_from(numbers).unwrap();
+            assert_eq!(permutation.parity(), odd, "{permutation:?}");
+        }
+        assert!(!Permutation::identity(100).parity());
+    }
+}
@@ -0,0 +1,299 @@
+use std::sync::atomic::{AtomicBool, AtomicU32, AtomicU64, Ordering};
+
+use dashu::{base::BitTest, integer::UBig};
+
+use crate::{
+    decode::{
+        check_element_count, compute_remainders_with_progress, remainders_to_permutation,
+        trivial_decode, DecodeConfig,
+    },
+    encode::Encoder,
+    error::Error,
+    ranking::OrderStatisticTree,
+};
+
+/// The callback runs at most once per step, so at most 101 times per call
+const STEPS: u32 = 100;
+
+/// Share of the encode progress given to the tree pass, the reduce of the big numbers gets the rest
+pub(crate) const FILL_SHARE: f32 = 0.05;
+
+/// Share of the decode progress given to the big number divisions, the rebuild of the permutation gets the rest
+const DIGITS_SHARE: f32 = 0.95;
+
+/// Elements of the encode tree pass between two progress updates
+pub(crate) const FILL_BATCH: usize = 4096;
+
+/// Throttled progress callback and cancel flag shared by all phases of one encode or decode
+pub(crate) struct Progress<'a> {
+    callback: Option<&'a (dyn Fn(f32) + Sync)>,
+    cancel: Option<&'a AtomicBool>,
+    /// Last reported step
+    reported: AtomicU32,
+}
+
+impl<'a> Progress<'a> {
+    pub(crate) fn new(callback: &'a (dyn Fn(f32) + Sync)) -> Self {
+        callback(0.0);
+        Self {
+            callback: Some(callback),
+            cancel: None,
+            reported: AtomicU32::new(0),
+        }
+    }
+
+    /// No callback, the work stops at the next checkpoint once `cancel` is set
+    pub(crate) fn cancellable(cancel: &'a AtomicBool) -> Self {
+        Self {
+            callback: None,
+            cancel: Some(cancel),
+            reported: AtomicU32::new(0),
+        }
+    }
+
+    /// Phase covering `start..end` of the progress, split into `total` units of work
+    pub(crate) fn phase(&self, start: f32, end: f32, total: u64) -> ProgressPhase<'_> {
+        ProgressPhase {
+            progress: self,
+            start,
+            end,
+            total: total.max(1),
+            done: AtomicU64::new(0),
+        }
+    }
+
+    /// Reports `step` if no later step was reported yet
+    #[allow(clippy::cast_precision_loss)]
+    fn report(&self, step: u32) {
+        let Some(callback) = self.callback else {
+            return;
+        };
+        // Cheap check first, only one thread wins the fetch_max of a step
+        if step > self.reported.load(Ordering::Relaxed)
+            && self.reported.fetch_max(step, Ordering::Relaxed) < step
+        {
+            callback(step as f32 / STEPS as f32);
+        }
+    }
+
+    /// Reports completion
+    pub(crate) fn finish(&self) {
+        self.report(STEPS);
+    }
+
+    pub(crate) fn cancelled(&self) -> bool {
+        self.cancel
+            .is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+
+    /// `Error::Cancelled` when the cancel flag is set
+    pub(crate) fn check(&self) -> Result<(), Error> {
+        if self.cancelled() {
+            return Err(Error::Cancelled);
+        }
+        Ok(())
+    }
+}
+
+/// Part of a `Progress`, counts the finished units of work of one phase
+pub(crate) struct ProgressPhase<'a> {
+    progress: &'a Progress<'a>,
+    start: f32,
+    end: f32,
+    total: u64,
+    done: AtomicU64,
+}
+
+impl ProgressPhase<'_> {
+    /// Marks `units` more units of work as done
+    #[allow(
+        clippy::cast_possible_truncation,
+        clippy::cast_precision_loss,
+        clippy::cast_sign_loss
+    )]
+    pub(crate) fn advance(&self, units: u64) {
+        let done = self.done.fetch_add(units, Ordering::Relaxed) + units;
+        // Rayon's reduce combines a few more times than there are caches
+        let fraction = (done as f32 / self.total as f32).min(1.0);
+        let position = self.start + (self.end - self.start) * fraction;
+        self.progress.report((position * STEPS as f32) as u32);
+    }
+
+    /// Whether the work should stop at this checkpoint
+    pub(crate) fn cancelled(&self) -> bool {
+        self.progress.cancelled()
+    }
+}
+
+impl std::fmt::Debug for ProgressPhase<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ProgressPhase")
+            .field("start", &self.start)
+            .field("end", &self.end)
+            .field("total", &self.total)
+            .field("done", &self.done)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Same as `big_lehmer::encode`, but reports the progress as a fraction from 0.0 to 1.0 to `callback`.
+/// Meant for CLI tools and GUIs wrapping the encode of very large permutations.
+///
+/// The progress is approximate. The tree pass counts its elements and gets the first 5%,
+/// the reduce counts the combines of the big numbers and gets the rest.
+/// The last combines multiply the largest numbers, so the progress runs ahead of the time towards the end,
+/// and with the `parallel` feature the combines finish out of order.
+/// The callback is throttled to once per percent, it starts with 0.0 and ends with 1.0 on success.
+/// With the `parallel` feature it runs on the rayon threads, two reports close together can arrive out of order.
+///
+/// The counting is one atomic add per 4096 elements and per combine, its overhead is within noise.
+///
+/// # Examples
+/// ```
+/// use std::sync::Mutex;
+/// let reports = Mutex::new(vec![]);
+/// let sequence: Vec<u32> = (0..20_000).rev().collect();
+/// let encoded = big_lehmer::encode_with_progress(&sequence, |fraction| reports.lock().unwrap().push(fraction)).unwrap();
+/// assert_eq!(encoded, big_lehmer::encode(&sequence).unwrap());
+/// let reports = reports.into_inner().unwrap();
+/// assert_eq!((reports[0], *reports.last().unwrap()), (0.0, 1.0));
+/// assert!(reports.len() <= 101);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_with_progress(
+    numbers: &[u32],
+    callback: impl Fn(f32) + Sync,
+) -> Result<Box<[u8]>, Error> {
+    let progress = Progress::new(&callback);
+    let code = Encoder::new().encode_with_progress(numbers, &progress)?;
+    progress.finish();
+    Ok(code)
+}
+
+/// Same as `big_lehmer::encode`, but stops early with `Error::Cancelled` once `cancel` is set.
+/// Allows interactive applications to abort a huge encode the user no longer wants.
+///
+/// The flag is checked every 4096 elements of the tree pass and before every combine of the reduce.
+/// The parallel reduce skips the remaining combines once the flag is set,
+/// so the time to return is about the longest combine that is already running.
+///
+/// # Examples
+/// ```
+/// use std::sync::atomic::AtomicBool;
+/// let sequence: Vec<u32> = (0..1000).rev().collect();
+/// let encoded = big_lehmer::encode_cancellable(&sequence, &AtomicBool::new(false)).unwrap();
+/// assert_eq!(encoded, big_lehmer::encode(&sequence).unwrap());
+/// assert!(matches!(
+///     big_lehmer::encode_cancellable(&sequence, &AtomicBool::new(true)),
+///     Err(big_lehmer::Error::Cancelled)
+/// ));
+/// ```
+///
+/// # Errors
+///
+/// `Error::Cancelled` when `cancel` was set before the encode finished.  
+/// Same as `big_lehmer::encode`
+pub fn encode_cancellable(numbers: &[u32], cancel: &AtomicBool) -> Result<Box<[u8]>, Error> {
+    let progress = Progress::cancellable(cancel);
+    progress.check()?;
+    Encoder::new().encode_with_progress(numbers, &progress)
+}
+
+/// Same as `big_lehmer::decode`, but reports the progress as a fraction from 0.0 to 1.0 to `callback`.
+/// Decode counterpart of `big_lehmer::encode_with_progress`, with the same throttling.
+///
+/// The progress is approximate. The divisions count the digits of the finished leaf work items and get the first 95%,
+/// the rebuild of the permutation gets the rest.
+/// The first splits are the largest divisions and finish no digits, so the progress lags behind at the start.
+///
+/// # Examples
+/// ```
+/// use std::sync::atomic::{AtomicU32, Ordering};
+/// let calls = AtomicU32::new(0);
+/// let sequence: Vec<u32> = (0..20_000).rev().collect();
+/// let encoded = big_lehmer::encode(&sequence).unwrap();
+/// let mut results = vec![0; 20_000];
+/// big_lehmer::decode_with_progress(&encoded, &mut results, |_| {
+///     calls.fetch_add(1, Ordering::Relaxed);
+/// })
+/// .unwrap();
+/// assert_eq!(results, sequence);
+/// assert!((2..=101).contains(&calls.into_inner()));
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_with_progress(
+    encoded: &[u8],
+    results: &mut [u32],
+    callback: impl Fn(f32) + Sync,
+) -> Result<(), Error> {
+    decode_progress(encoded, results, &Progress::new(&callback))
+}
+
+/// Same as `big_lehmer::decode`, but stops early with `Error::Cancelled` once `cancel` is set.
+/// Decode counterpart of `big_lehmer::encode_cancellable`.
+///
+/// The flag is checked before every split and every leaf division,
+/// so the time to return is about the longest big number division that is already running.
+/// The rebuild of the permutation is not interrupted.
+///
+/// # Examples
+/// ```
+/// use std::sync::atomic::AtomicBool;
+/// let sequence: Vec<u32> = (0..1000).rev().collect();
+/// let encoded = big_lehmer::encode(&sequence).unwrap();
+/// let mut results = vec![0; 1000];
+/// assert!(matches!(
+///     big_lehmer::decode_cancellable(&encoded, &mut results, &AtomicBool::new(true)),
+///     Err(big_lehmer::Error::Cancelled)
+/// ));
+/// big_lehmer::decode_cancellable(&encoded, &mut results, &AtomicBool::new(false)).unwrap();
+/// assert_eq!(results, sequence);
+/// ```
+///
+/// # Errors
+///
+/// `Error::Cancelled` when `cancel` was set before the divisions finished.  
+/// Same as `big_lehmer::decode`
+pub fn decode_cancellable(
+    encoded: &[u8],
+    results: &mut [u32],
+    cancel: &AtomicBool,
+) -> Result<(), Error> {
+    let progress = Progress::cancellable(cancel);
+    progress.check()?;
+    decode_progress(encoded, results, &progress)
+}
+
+fn decode_progress(encoded: &[u8], results: &mut [u32], progress: &Progress) -> Result<(), Error> {
+    let element_count = u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+        element_count: results.len(),
+    })?;
+    let input = UBig::from_le_bytes(encoded);
+    let code_bits = input.bit_len();
+    if let Some(result) = trivial_decode(code_bits, results) {
+        result?;
+        progress.finish();
+        return Ok(());
+    }
+    let mut remainders = vec![None; results.len()];
+    let digits = progress.phase(0.0, DIGITS_SHARE, results.len() as u64);
+    compute_remainders_with_progress(
+        input,
+        &mut remainders,
+        DecodeConfig::default(),
+        Some(&digits),
+    );
+    // The skipped work items left their digits unset
+    progress.check()?;
+    check_element_count(&remainders, code_bits)?;
+    let mut tree = OrderStatisticTree::new(element_count)?;
+    remainders_to_permutation(&remainders, results, &mut tree)?;
+    progress.finish();
+    Ok(())
+}
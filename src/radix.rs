@@ -0,0 +1,179 @@
+use dashu::{base::DivRem, integer::UBig};
+
+use crate::{
+    error::Error, permutation_rank, permutation_unrank, ranking::OrderStatisticTree, to_factoradic,
+};
+
+/// Converts a Lehmer code into its digits in the given `radix`.  
+/// Digits are ordered from least to most significant, matching the little endian byte order of the code.
+/// A zero code has no digits.
+///
+/// Costs O(N^2) in the size of the code, since it repeatedly divides the whole big number.
+///
+/// # Examples
+///
+/// ```
+/// let encoded = big_lehmer::encode(&[7, 2, 0, 6, 5, 1, 4, 3]).unwrap();
+/// let digits = big_lehmer::code_to_base(&encoded, 10).unwrap();
+/// // Rank 36835
+/// assert_eq!(digits, [5, 3, 8, 6, 3]);
+/// assert_eq!(big_lehmer::code_from_base(&digits, 10).unwrap(), encoded);
+/// ```
+///
+/// # Errors
+///
+/// Will error when `radix < 2`.
+pub fn code_to_base(encoded: &[u8], radix: u32) -> Result<Vec<u32>, Error> {
+    if radix < 2 {
+        return Err(Error::InvalidRadix { radix });
+    }
+    let mut number = UBig::from_le_bytes(encoded);
+    let mut digits = vec![];
+    while !number.is_zero() {
+        let (quotient, digit) = number.div_rem(radix);
+        digits.push(digit);
+        number = quotient;
+    }
+    Ok(digits)
+}
+
+/// Converts digits in the given `radix` (least significant first) back into a Lehmer code.  
+/// Inverse of `big_lehmer::code_to_base`.
+///
+/// # Errors
+///
+/// Will error when `radix < 2` or when a digit is not smaller than `radix`.
+pub fn code_from_base(digits: &[u32], radix: u32) -> Result<Box<[u8]>, Error> {
+    if radix < 2 {
+        return Err(Error::InvalidRadix { radix });
+    }
+    let mut number = UBig::ZERO;
+    for (index, &digit) in digits.iter().enumerate().rev() {
+        if digit >= radix {
+            return Err(Error::InvalidDigit {
+                index,
+                digit,
+                radix,
+            });
+        }
+        number = number * radix + digit;
+    }
+    Ok(number.to_le_bytes())
+}
+
+/// Rank of the permutation `numbers` written in the mixed radix number system `radices`.  
+/// Digit `i` is smaller than `radices[i]`, digits and radices are ordered from least to most significant,
+/// same as `big_lehmer::code_to_base`. Every radix gets one digit, so the result has `radices.len()` digits.
+///
+/// The Lehmer code is the special case of the ascending radices `[1, 2, 3, ..., N]`,
+/// the factorial number system: the digits are then `big_lehmer::to_factoradic(numbers)` in reverse.
+/// That case is detected and takes the O(N log N) factoradic path,
+/// any other radices cost O(N^2) like `big_lehmer::code_to_base`.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let lehmer = big_lehmer::to_mixed_radix(&sequence, &[1, 2, 3, 4, 5, 6, 7, 8]).unwrap();
+/// assert_eq!(lehmer, [0, 1, 0, 3, 4, 0, 2, 7]);
+///
+/// // Rank 36835 in hours, minutes and seconds
+/// let time = big_lehmer::to_mixed_radix(&sequence, &[60, 60, 24]).unwrap();
+/// assert_eq!(time, [55, 13, 10]);
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::InvalidRadix` for a zero radix.  
+/// Errors with `Error::MixedRadixOverflow` when the rank is not smaller than the product of `radices`.  
+/// Validation errors of `numbers`, same as `big_lehmer::encode`
+pub fn to_mixed_radix(numbers: &[u32], radices: &[u32]) -> Result<Vec<u32>, Error> {
+    check_radices(radices)?;
+    if is_factorial_radices(radices, numbers.len()) {
+        let mut digits = to_factoradic(numbers)?;
+        digits.reverse();
+        return Ok(digits);
+    }
+    let mut rank = permutation_rank(numbers)?;
+    let mut digits = Vec::with_capacity(radices.len());
+    for &radix in radices {
+        let (quotient, digit) = rank.div_rem(radix);
+        digits.push(digit);
+        rank = quotient;
+    }
+    if !rank.is_zero() {
+        return Err(Error::MixedRadixOverflow {
+            digit_count: radices.len(),
+        });
+    }
+    Ok(digits)
+}
+
+/// Permutation of `0..element_count` whose rank has the mixed radix `digits`, inverse of `big_lehmer::to_mixed_radix`.  
+/// The factorial radices `[1, 2, 3, ..., element_count]` skip the big number and select the digits directly.
+///
+/// # Examples
+/// ```
+/// let sequence = big_lehmer::from_mixed_radix(&[55, 13, 10], &[60, 60, 24], 8).unwrap();
+/// assert_eq!(sequence, [7, 2, 0, 6, 5, 1, 4, 3]);
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::LengthMismatch` when `digits` and `radices` have different lengths.  
+/// Errors with `Error::InvalidRadix` for a zero radix and `Error::InvalidDigit` when a digit is not smaller than its radix.  
+/// Errors with `Error::ElementCountMismatch` when the rank is not smaller than `element_count!`
+pub fn from_mixed_radix(
+    digits: &[u32],
+    radices: &[u32],
+    element_count: u32,
+) -> Result<Vec<u32>, Error> {
+    if digits.len() != radices.len() {
+        return Err(Error::LengthMismatch {
+            expected: radices.len(),
+            got: digits.len(),
+        });
+    }
+    check_radices(radices)?;
+    for (index, (&digit, &radix)) in digits.iter().zip(radices).enumerate() {
+        if digit >= radix {
+            return Err(Error::InvalidDigit {
+                index,
+                digit,
+                radix,
+            });
+        }
+    }
+    let len = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    if is_factorial_radices(radices, len) {
+        let mut tree = OrderStatisticTree::new(element_count)?;
+        return Ok(digits
+            .iter()
+            .rev()
+            .map(|&digit| tree.select_and_remove(digit))
+            .collect());
+    }
+    let mut rank = UBig::ZERO;
+    for (&digit, &radix) in digits.iter().zip(radices).rev() {
+        rank = rank * radix + digit;
+    }
+    permutation_unrank(&rank, element_count)
+}
+
+/// Mixed radices only need to be positive, a radix of 1 always has the digit 0
+fn check_radices(radices: &[u32]) -> Result<(), Error> {
+    match radices.iter().find(|&&radix| radix == 0) {
+        Some(&radix) => Err(Error::InvalidRadix { radix }),
+        None => Ok(()),
+    }
+}
+
+/// Whether `radices` are `[1, 2, 3, ..., element_count]`, the factorial number system of the Lehmer code
+fn is_factorial_radices(radices: &[u32], element_count: usize) -> bool {
+    radices.len() == element_count
+        && radices
+            .iter()
+            .enumerate()
+            .all(|(index, &radix)| radix as usize == index + 1)
+}
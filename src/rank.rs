@@ -0,0 +1,771 @@
+use std::num::NonZeroU32;
+
+use dashu::{
+    base::{BitTest, DivRem},
+    integer::{IBig, UBig},
+};
+
+use crate::{
+    decode::{compute_digits_from, min_element_count, DecodeConfig},
+    decode_from_ubig,
+    delta::offset_rank,
+    encode::reduce_steps,
+    encode_to_ubig,
+    error::Error,
+    factorial::factorial,
+    validation::is_valid_permutation,
+};
+
+/// Lexicographic rank of the permutation `numbers` of `0..numbers.len()`.  
+/// This is the number behind `big_lehmer::encode`, the code is its little endian bytes.
+///
+/// # Examples
+/// ```
+/// use dashu::integer::UBig;
+/// assert_eq!(big_lehmer::permutation_rank(&[0, 1, 2]).unwrap(), UBig::ZERO);
+/// assert_eq!(big_lehmer::permutation_rank(&[2, 1, 0]).unwrap(), UBig::from(5u8));
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn permutation_rank(numbers: &[u32]) -> Result<UBig, Error> {
+    encode_to_ubig(numbers)
+}
+
+/// Permutation of `0..element_count` with the lexicographic rank `rank`, inverse of `big_lehmer::permutation_rank`.
+///
+/// # Examples
+/// ```
+/// use dashu::integer::UBig;
+/// assert_eq!(big_lehmer::permutation_unrank(&UBig::from(5u8), 3).unwrap(), [2, 1, 0]);
+/// assert!(big_lehmer::permutation_unrank(&UBig::from(6u8), 3).is_err());
+/// ```
+///
+/// # Errors
+///
+/// `rank` must be smaller than `big_lehmer::permutation_count(element_count)`,
+/// otherwise errors with `Error::ElementCountMismatch`.  
+/// Same as `big_lehmer::decode`
+pub fn permutation_unrank(rank: &UBig, element_count: u32) -> Result<Vec<u32>, Error> {
+    let len = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    let mut results = vec![0; len];
+    decode_from_ubig(rank.clone(), &mut results)?;
+    Ok(results)
+}
+
+/// Same as `big_lehmer::decode`, but takes the rank as big number by value, e.g. from `big_lehmer::permutation_rank`.  
+/// The rank becomes the dividend of the first division as is, without the conversion from bytes
+/// and without the copy of `big_lehmer::permutation_unrank`, which borrows the rank and allocates the results.
+///
+/// Prefer this when the rank is not needed afterwards and a `results` buffer exists,
+/// `big_lehmer::permutation_unrank` when the rank is kept, e.g. to step through ranks.
+///
+/// # Examples
+/// ```
+/// use dashu::integer::UBig;
+/// let mut results = [0; 3];
+/// big_lehmer::decode_ubig_owned(UBig::from(5u8), &mut results).unwrap();
+/// assert_eq!(results, [2, 1, 0]);
+/// assert!(big_lehmer::decode_ubig_owned(UBig::from(6u8), &mut results).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_ubig_owned(code: UBig, results: &mut [u32]) -> Result<(), Error> {
+    decode_from_ubig(code, results)
+}
+
+/// Number of permutations of `element_count` elements, `element_count!`.  
+/// Every valid rank is smaller than this.
+/// Up to 1024 elements the factorial comes from a per thread cache, larger ones start from the cached `1024!`.
+///
+/// # Examples
+/// ```
+/// use dashu::integer::UBig;
+/// assert_eq!(big_lehmer::permutation_count(0), UBig::ONE);
+/// assert_eq!(big_lehmer::permutation_count(5), UBig::from(120u8));
+/// ```
+#[must_use]
+pub fn permutation_count(element_count: u32) -> UBig {
+    factorial(element_count)
+}
+
+/// Iterator over all permutations of `0..element_count` in lexicographic order.
+/// Created by `big_lehmer::all_permutations`.
+#[derive(Debug, Clone)]
+pub struct AllPermutations {
+    rank: UBig,
+    count: UBig,
+    element_count: u32,
+}
+
+impl Iterator for AllPermutations {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.rank >= self.count {
+            return None;
+        }
+        // The rank is smaller than element_count!, so the unrank can not fail
+        let permutation = permutation_unrank(&self.rank, self.element_count).unwrap();
+        self.rank += UBig::ONE;
+        Some(permutation)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(&self.count - &self.rank) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+impl std::iter::FusedIterator for AllPermutations {}
+
+/// All `element_count!` permutations of `0..element_count` in lexicographic order, rank 0 first.  
+/// Each item unranks its rank with `big_lehmer::permutation_unrank`, so the iterator also exercises the decode path.
+/// Meant for tests and exhaustive search over small element counts:
+/// 10 elements are already 3_628_800 items, 13 elements do not fit into a `u32` count anymore.
+///
+/// # Examples
+/// ```
+/// let all: Vec<Vec<u32>> = big_lehmer::all_permutations(3).collect();
+/// assert_eq!(all, [[0, 1, 2], [0, 2, 1], [1, 0, 2], [1, 2, 0], [2, 0, 1], [2, 1, 0]]);
+/// assert_eq!(big_lehmer::all_permutations(0).count(), 1);
+/// ```
+#[must_use]
+pub fn all_permutations(element_count: u32) -> AllPermutations {
+    AllPermutations {
+        rank: UBig::ZERO,
+        count: permutation_count(element_count),
+        element_count,
+    }
+}
+
+/// Iterator over all `k` element subsets of `0..n` in lexicographic order.
+/// Created by `big_lehmer::all_combinations`.
+#[derive(Debug, Clone)]
+pub struct AllCombinations {
+    next: Option<Vec<u32>>,
+    remaining: UBig,
+    n: u32,
+}
+
+impl Iterator for AllCombinations {
+    type Item = Vec<u32>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let combination = self.next.take()?;
+        self.remaining -= UBig::ONE;
+        // Increments the last number that is not at its maximum and restarts the numbers after it
+        let k = combination.len();
+        let mut next = combination.clone();
+        if let Some(i) = (0..k).rev().find(|&i| next[i] < self.n - (k - i) as u32) {
+            next[i] += 1;
+            for j in i + 1..k {
+                next[j] = next[j - 1] + 1;
+            }
+            self.next = Some(next);
+        }
+        Some(combination)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        match usize::try_from(&self.remaining) {
+            Ok(remaining) => (remaining, Some(remaining)),
+            Err(_) => (usize::MAX, None),
+        }
+    }
+}
+
+impl std::iter::FusedIterator for AllCombinations {}
+
+/// Number of `k` element subsets of `n` elements, the binomial coefficient `C(n, k)`.  
+/// 0 when `k > n`.
+fn combination_count(n: u32, k: u32) -> UBig {
+    if k > n {
+        return UBig::ZERO;
+    }
+    // Every prefix of the product is itself a binomial coefficient, so the divisions are exact
+    let k = k.min(n - k);
+    (0..k).fold(UBig::ONE, |count, i| count * (n - i) / (i + 1))
+}
+
+/// All `C(n, k)` subsets of `k` numbers of `0..n`, each sorted ascending, in lexicographic order.  
+/// The companion of `big_lehmer::all_permutations` for exhaustive search over combinations.
+/// There is no subset when `k > n`, and exactly one (empty) subset when `k == 0`.
+///
+/// The items are produced by a successor step in O(k) each, there is no big number work apart from the count.
+/// The count grows fast: `C(32, 16)` is 601_080_390 items, `C(68, 34)` does not fit into a `u64` anymore.
+///
+/// # Examples
+/// ```
+/// let all: Vec<Vec<u32>> = big_lehmer::all_combinations(4, 2).collect();
+/// assert_eq!(all, [[0, 1], [0, 2], [0, 3], [1, 2], [1, 3], [2, 3]]);
+/// assert_eq!(big_lehmer::all_combinations(5, 0).count(), 1);
+/// assert_eq!(big_lehmer::all_combinations(2, 3).count(), 0);
+/// ```
+#[must_use]
+pub fn all_combinations(n: u32, k: u32) -> AllCombinations {
+    AllCombinations {
+        next: (k <= n).then(|| (0..k).collect()),
+        remaining: combination_count(n, k),
+        n,
+    }
+}
+
+/// Rank of `numbers` after swapping the positions `i` and `j`, given `old_rank`, the rank of `numbers` before the swap.  
+/// Only the Lehmer digits of the positions `i..=j` change, so only their difference is added to `old_rank`
+/// instead of ranking the whole permutation again.
+/// Useful for interactive reordering, where the permutation changes by a single swap at a time.
+///
+/// Counting the changed digits is O(N) without big number work,
+/// adding them up costs O(|j - i|) multiplications of a small number with a number of O(|j - i| * log N) bits.
+///
+/// # Examples
+/// ```
+/// let mut sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let rank = big_lehmer::permutation_rank(&sequence).unwrap();
+/// let new_rank = big_lehmer::rank_after_swap(&sequence, &rank, 1, 5).unwrap();
+/// sequence.swap(1, 5);
+/// assert_eq!(new_rank, big_lehmer::permutation_rank(&sequence).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::PositionOutOfRange` when `i` or `j` is not smaller than `numbers.len()`.  
+/// Validation errors of `numbers`, same as `big_lehmer::encode`.  
+/// `old_rank` is not checked, when it is not the rank of `numbers` the result is meaningless.
+pub fn rank_after_swap(
+    numbers: &[u32],
+    old_rank: &UBig,
+    i: usize,
+    j: usize,
+) -> Result<UBig, Error> {
+    let element_count = u32::try_from(numbers.len()).map_err(|_| Error::SequenceToLong {
+        element_count: numbers.len(),
+    })?;
+    for position in [i, j] {
+        if position >= numbers.len() {
+            return Err(Error::PositionOutOfRange {
+                position: u32::try_from(position).unwrap_or(u32::MAX),
+                element_count,
+            });
+        }
+    }
+    is_valid_permutation(numbers)?;
+    let (i, j) = (i.min(j), i.max(j));
+    if i == j {
+        return Ok(old_rank.clone());
+    }
+    let (a, b) = (numbers[i], numbers[j]);
+    let count_smaller = |range: &[u32], limit: u32| -> u64 {
+        range.iter().filter(|&&number| number < limit).count() as u64
+    };
+
+    // Digit k counts the smaller numbers after position k.
+    // Digit i: the numbers after i stay the same, only b is replaced by a.
+    let old_i = count_smaller(&numbers[i + 1..], a);
+    let new_i = count_smaller(&numbers[i + 1..], b) + u64::from(a < b);
+    // Digit j: same numbers after j, compared with a instead of b.
+    let old_j = count_smaller(&numbers[j + 1..], b);
+    let new_j = count_smaller(&numbers[j + 1..], a);
+
+    // The digit differences are weighted with (N - 1 - k)!, factor out (N - 1 - j)!
+    // and sum them with Horner's scheme. Positive and negative parts are kept apart, UBig is unsigned.
+    let mut increase = UBig::ZERO;
+    let mut decrease = UBig::ZERO;
+    let mut add_digit = |k: usize, old: u64, new: u64| {
+        // k > i, so the factor is at most N - 1
+        let factor = (numbers.len() - k) as u64;
+        if k > i {
+            increase *= factor;
+            decrease *= factor;
+        }
+        if new > old {
+            increase += new - old;
+        } else {
+            decrease += old - new;
+        }
+    };
+    add_digit(i, old_i, new_i);
+    for (k, &number) in numbers.iter().enumerate().take(j).skip(i + 1) {
+        // Digit k loses b and gains a after it
+        let new = u64::from(a < number);
+        let old = u64::from(b < number);
+        add_digit(k, old, new);
+    }
+    add_digit(j, old_j, new_j);
+
+    let weight = permutation_count(element_count - 1 - u32::try_from(j).unwrap());
+    Ok(old_rank + increase * &weight - decrease * &weight)
+}
+
+/// Rank of the permutation of `element_count + 1` elements that inserts the new largest number `element_count`
+/// at position `new_position` into the permutation of rank `code`.  
+/// `new_position == element_count` appends it at the end, 0 puts it in front.
+/// The numbers of the old permutation keep their order, so their Lehmer digits stay the same,
+/// only the weights of the digits in front of `new_position` grow.
+///
+/// The rank is split at `(element_count - new_position)!`: the low part is unchanged,
+/// the `new_position` digits of the high part are extracted with the divide and conquer of decode,
+/// and put back with the new weights by the reduce of encode.
+/// This skips the tree passes, but it is not O(log N): every digit in front of `new_position` changes its weight,
+/// so the cost grows with `new_position` and approaches a decode plus encode of the prefix near the end.
+///
+/// # Examples
+/// ```
+/// use dashu::integer::UBig;
+/// let rank = big_lehmer::permutation_rank(&[2, 0, 1]).unwrap();
+/// let extended = big_lehmer::append_element(&rank, 3, 1).unwrap();
+/// assert_eq!(extended, big_lehmer::permutation_rank(&[2, 3, 0, 1]).unwrap());
+/// let appended = big_lehmer::append_element(&rank, 3, 3).unwrap();
+/// assert_eq!(appended, big_lehmer::permutation_rank(&[2, 0, 1, 3]).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::PositionOutOfRange` when `new_position > element_count`.  
+/// Errors with `Error::SequenceToLong` when `element_count + 1` does not fit into a `u32`.  
+/// Errors with `Error::ElementCountMismatch` when `code` is not smaller than `element_count!`.
+pub fn append_element(code: &UBig, element_count: u32, new_position: u32) -> Result<UBig, Error> {
+    if new_position > element_count {
+        return Err(Error::PositionOutOfRange {
+            position: new_position,
+            element_count,
+        });
+    }
+    if element_count == u32::MAX {
+        return Err(Error::SequenceToLong {
+            element_count: usize::try_from(element_count).unwrap_or(usize::MAX) + 1,
+        });
+    }
+    let tail_count = element_count - new_position;
+    let tail_weight = permutation_count(tail_count);
+    let (prefix, tail) = code.div_rem(&tail_weight);
+    // Digit of position i has the radix element_count - i, computed for the ascending radices from the last prefix position.
+    // The one extra digit of radix element_count + 1 is zero exactly when the code fits into element_count elements
+    let mut digits = vec![None; new_position as usize + 1];
+    compute_digits_from(prefix, tail_count + 1, &mut digits, DecodeConfig::default());
+    if digits.last() != Some(&Some(NonZeroU32::MIN)) {
+        let got = usize::try_from(element_count).unwrap_or(usize::MAX);
+        return Err(Error::ElementCountMismatch {
+            expected: min_element_count(code.bit_len()).max(got + 1),
+            got,
+        });
+    }
+    // Every digit of the new permutation has a radix one larger, the new number is larger than all numbers after it
+    let prefix_steps = (0..new_position).zip(digits[..new_position as usize].iter().rev());
+    let steps = prefix_steps.map(|(position, digit)| {
+        let digit = digit.map_or(0, |digit| digit.get() - 1);
+        (u64::from(digit), u64::from(element_count - position))
+    });
+    let extended = reduce_steps(steps.chain([(u64::from(tail_count), 1)]));
+    Ok(extended * tail_weight + tail)
+}
+
+/// Code of the permutation `delta` ranks after the permutation of `encoded`.  
+/// Generalizes stepping to the next permutation to arbitrary jumps, for example to paginate through the permutations.
+/// Only big number arithmetic, the permutation itself is never decoded.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[0, 1, 2]).unwrap();
+/// let jumped = big_lehmer::code_add(&encoded, 4, 3).unwrap();
+/// let mut decoded = [0; 3];
+/// big_lehmer::decode(&jumped, &mut decoded).unwrap();
+/// assert_eq!(decoded, [2, 0, 1]);
+/// // 3! = 6 permutations, there is no rank 6
+/// assert!(big_lehmer::code_add(&encoded, 6, 3).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::RankOutOfRange` when the new rank is not smaller than `element_count!`, there is no wrap around.  
+/// Errors with `Error::ElementCountMismatch` when `encoded` is not a code of `element_count` elements.
+pub fn code_add(encoded: &[u8], delta: u64, element_count: u32) -> Result<Box<[u8]>, Error> {
+    offset_code(encoded, IBig::from(delta), element_count)
+}
+
+/// Code of the permutation `delta` ranks before the permutation of `encoded`, the inverse of `big_lehmer::code_add`.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[2, 0, 1]).unwrap();
+/// let jumped = big_lehmer::code_sub(&encoded, 4, 3).unwrap();
+/// let mut decoded = [0; 3];
+/// big_lehmer::decode(&jumped, &mut decoded).unwrap();
+/// assert_eq!(decoded, [0, 1, 2]);
+/// assert!(big_lehmer::code_sub(&encoded, 5, 3).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::RankOutOfRange` when `delta` is larger than the rank of `encoded`, there is no wrap around.  
+/// Errors with `Error::ElementCountMismatch` when `encoded` is not a code of `element_count` elements.
+pub fn code_sub(encoded: &[u8], delta: u64, element_count: u32) -> Result<Box<[u8]>, Error> {
+    offset_code(encoded, -IBig::from(delta), element_count)
+}
+
+/// Code of the permutation `delta` ranks after (or before, when negative) the permutation of `encoded`,
+/// wrapping around modulo `element_count!`.  
+/// Going past the last permutation continues at the identity, going before the identity continues at the last one,
+/// so repeated calls cycle through all permutations.
+///
+/// # Examples
+/// ```
+/// let last = big_lehmer::encode(&[2, 1, 0]).unwrap();
+/// let identity = big_lehmer::code_add_wrapping(&last, 1, 3).unwrap();
+/// assert!(identity.is_empty());
+/// assert_eq!(big_lehmer::code_add_wrapping(&identity, -1, 3).unwrap(), last);
+/// // 3! = 6 permutations, 13 steps are two full cycles and one step
+/// assert_eq!(big_lehmer::code_add_wrapping(&last, 13, 3).unwrap(), identity);
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::ElementCountMismatch` when `encoded` is not a code of `element_count` elements.
+pub fn code_add_wrapping(
+    encoded: &[u8],
+    delta: i64,
+    element_count: u32,
+) -> Result<Box<[u8]>, Error> {
+    let (rank, count) = checked_rank(encoded, element_count)?;
+    let count = IBig::from(count);
+    let mut rank = (IBig::from(rank) + delta) % &count;
+    if rank < IBig::ZERO {
+        rank += count;
+    }
+    // Within 0..count after the correction
+    Ok(UBig::try_from(rank).unwrap().to_le_bytes())
+}
+
+/// Code of the permutation `delta` ranks after (or before, when negative) the permutation of `encoded`,
+/// clamped to the identity (rank 0) and the last permutation (rank `element_count! - 1`).  
+/// For bounded scrubbing, where overshooting should stop at the ends instead of erroring like `big_lehmer::code_add`.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[1, 0, 2]).unwrap();
+/// let last = big_lehmer::code_add_saturating(&encoded, 1000, 3).unwrap();
+/// assert_eq!(last, big_lehmer::encode(&[2, 1, 0]).unwrap());
+/// let first = big_lehmer::code_add_saturating(&encoded, -1000, 3).unwrap();
+/// assert!(first.is_empty());
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::ElementCountMismatch` when `encoded` is not a code of `element_count` elements.
+pub fn code_add_saturating(
+    encoded: &[u8],
+    delta: i64,
+    element_count: u32,
+) -> Result<Box<[u8]>, Error> {
+    let (rank, count) = checked_rank(encoded, element_count)?;
+    // count is at least 1, 0! = 1
+    let rank =
+        offset_rank(rank, IBig::from(delta)).map_or(UBig::ZERO, |rank| rank.min(count - UBig::ONE));
+    Ok(rank.to_le_bytes())
+}
+
+/// Code of the rank of `encoded` moved by the signed `delta`, neither end wraps around
+fn offset_code(encoded: &[u8], delta: IBig, element_count: u32) -> Result<Box<[u8]>, Error> {
+    let (rank, count) = checked_rank(encoded, element_count)?;
+    offset_rank(rank, delta)
+        .filter(|rank| *rank < count)
+        .map(|rank| rank.to_le_bytes())
+        .ok_or(Error::RankOutOfRange { element_count })
+}
+
+/// Rank of `encoded` together with `element_count!`, errors when the rank is not smaller than it
+fn checked_rank(encoded: &[u8], element_count: u32) -> Result<(UBig, UBig), Error> {
+    let rank = UBig::from_le_bytes(encoded);
+    let count = permutation_count(element_count);
+    if rank >= count {
+        let got = usize::try_from(element_count).unwrap_or(usize::MAX);
+        return Err(Error::ElementCountMismatch {
+            expected: min_element_count(rank.bit_len()).max(got + 1),
+            got,
+        });
+    }
+    Ok((rank, count))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_permutation_count() {
+        let mut factorial = UBig::ONE;
+        for element_count in 1..200u32 {
+            factorial *= element_count;
+            assert_eq!(permutation_count(element_count), factorial);
+        }
+    }
+
+    #[test]
+    fn test_code_add_sub() {
+        let sequence: Vec<u32> = crate::tests::shuffled(300, 861);
+        let encoded = crate::encode(&sequence).unwrap();
+        let rank = permutation_rank(&sequence).unwrap();
+        for delta in [0, 1, 1000, u64::MAX] {
+            let added = code_add(&encoded, delta, 300).unwrap();
+            assert_eq!(UBig::from_le_bytes(&added), &rank + delta);
+            assert_eq!(&*code_sub(&added, delta, 300).unwrap(), &*encoded);
+        }
+
+        // No wrap around at 0 and N!
+        let first = crate::encode(&[0, 1, 2, 3]).unwrap();
+        let last = crate::encode(&[3, 2, 1, 0]).unwrap();
+        assert_eq!(&*code_add(&first, 23, 4).unwrap(), &*last);
+        assert_eq!(&*code_sub(&last, 23, 4).unwrap(), &*first);
+        assert!(code_sub(&first, 0, 4).unwrap().is_empty());
+        assert!(matches!(
+            code_add(&last, 1, 4),
+            Err(Error::RankOutOfRange { element_count: 4 })
+        ));
+        assert!(matches!(
+            code_sub(&first, 1, 4),
+            Err(Error::RankOutOfRange { element_count: 4 })
+        ));
+        assert!(matches!(
+            code_add(&crate::encode(&[]).unwrap(), 1, 0),
+            Err(Error::RankOutOfRange { element_count: 0 })
+        ));
+
+        // The input code itself is checked
+        assert!(matches!(
+            code_add(&last, 0, 3),
+            Err(Error::ElementCountMismatch { got: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_code_add_wrapping_saturating() {
+        let first = crate::encode(&[0, 1, 2, 3]).unwrap();
+        let last = crate::encode(&[3, 2, 1, 0]).unwrap();
+        let rank_of = |code: &[u8]| UBig::from_le_bytes(code);
+
+        // Wrapping is modulo 4! = 24
+        assert_eq!(&*code_add_wrapping(&last, 1, 4).unwrap(), &*first);
+        assert_eq!(&*code_add_wrapping(&first, -1, 4).unwrap(), &*last);
+        assert_eq!(&*code_add_wrapping(&first, 24, 4).unwrap(), &*first);
+        assert_eq!(&*code_add_wrapping(&first, -24 * 5, 4).unwrap(), &*first);
+        assert_eq!(
+            rank_of(&code_add_wrapping(&first, 24 * 3 + 5, 4).unwrap()),
+            UBig::from(5u8)
+        );
+        assert_eq!(
+            rank_of(&code_add_wrapping(&last, -25, 4).unwrap()),
+            UBig::from(22u8)
+        );
+        assert_eq!(
+            rank_of(&code_add_wrapping(&first, i64::MIN, 4).unwrap()),
+            UBig::from(i64::MIN.rem_euclid(24).unsigned_abs())
+        );
+
+        // Saturating clamps at both ends
+        assert_eq!(&*code_add_saturating(&last, 1, 4).unwrap(), &*last);
+        assert_eq!(&*code_add_saturating(&first, -1, 4).unwrap(), &*first);
+        assert_eq!(&*code_add_saturating(&first, i64::MAX, 4).unwrap(), &*last);
+        assert_eq!(&*code_add_saturating(&last, i64::MIN, 4).unwrap(), &*first);
+        assert_eq!(&*code_add_saturating(&first, 23, 4).unwrap(), &*last);
+        assert_eq!(
+            rank_of(&code_add_saturating(&last, -3, 4).unwrap()),
+            UBig::from(20u8)
+        );
+
+        // Within the range both agree with code_add
+        let sequence: Vec<u32> = crate::tests::shuffled(300, 911);
+        let encoded = crate::encode(&sequence).unwrap();
+        for delta in [0, 1, 1000, i64::MAX] {
+            let added = code_add(&encoded, delta.unsigned_abs(), 300).unwrap();
+            assert_eq!(code_add_wrapping(&encoded, delta, 300).unwrap(), added);
+            assert_eq!(code_add_saturating(&encoded, delta, 300).unwrap(), added);
+        }
+
+        // The only permutation of 0 elements
+        let empty = crate::encode(&[]).unwrap();
+        assert!(code_add_wrapping(&empty, 7, 0).unwrap().is_empty());
+        assert!(code_add_saturating(&empty, -7, 0).unwrap().is_empty());
+        assert!(matches!(
+            code_add_wrapping(&last, 0, 3),
+            Err(Error::ElementCountMismatch { got: 3, .. })
+        ));
+        assert!(matches!(
+            code_add_saturating(&last, 0, 3),
+            Err(Error::ElementCountMismatch { got: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_append_element() {
+        // Exhaustive for small permutations
+        for element_count in 0..=5 {
+            for sequence in all_permutations(element_count) {
+                let rank = permutation_rank(&sequence).unwrap();
+                for new_position in 0..=element_count {
+                    let mut extended = sequence.clone();
+                    extended.insert(new_position as usize, element_count);
+                    assert_eq!(
+                        append_element(&rank, element_count, new_position).unwrap(),
+                        permutation_rank(&extended).unwrap()
+                    );
+                }
+            }
+        }
+
+        let mut sequence: Vec<u32> = crate::tests::shuffled(2000, 915);
+        let mut rank = permutation_rank(&sequence).unwrap();
+        for (element_count, new_position) in [(2000, 0), (2001, 2001), (2002, 1000), (2003, 1)] {
+            rank = append_element(&rank, element_count, new_position).unwrap();
+            sequence.insert(new_position as usize, element_count);
+            assert_eq!(rank, permutation_rank(&sequence).unwrap());
+        }
+
+        assert!(matches!(
+            append_element(&UBig::ZERO, 3, 4),
+            Err(Error::PositionOutOfRange {
+                position: 4,
+                element_count: 3
+            })
+        ));
+        assert!(matches!(
+            append_element(&UBig::ZERO, u32::MAX, 0),
+            Err(Error::SequenceToLong { .. })
+        ));
+        for new_position in 0..=3 {
+            assert!(matches!(
+                append_element(&permutation_count(3), 3, new_position),
+                Err(Error::ElementCountMismatch { got: 3, .. })
+            ));
+        }
+    }
+
+    #[test]
+    fn test_rank_after_swap() {
+        use rand::{seq::SliceRandom, Rng, SeedableRng};
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(842);
+        let mut sequence: Vec<u32> = (0..500).collect();
+        sequence.shuffle(&mut rng);
+        let mut rank = permutation_rank(&sequence).unwrap();
+        for _ in 0..200 {
+            let i = rng.gen_range(0..sequence.len());
+            let j = rng.gen_range(0..sequence.len());
+            rank = rank_after_swap(&sequence, &rank, i, j).unwrap();
+            sequence.swap(i, j);
+            assert_eq!(rank, permutation_rank(&sequence).unwrap());
+        }
+
+        // Adjacent positions and the ends
+        for (i, j) in [(0, 1), (498, 499), (0, 499), (499, 0), (7, 7)] {
+            rank = rank_after_swap(&sequence, &rank, i, j).unwrap();
+            sequence.swap(i, j);
+            assert_eq!(rank, permutation_rank(&sequence).unwrap());
+        }
+
+        assert!(matches!(
+            rank_after_swap(&sequence, &rank, 0, 500),
+            Err(Error::PositionOutOfRange {
+                position: 500,
+                element_count: 500
+            })
+        ));
+        assert!(matches!(
+            rank_after_swap(&[0, 0], &UBig::ZERO, 0, 1),
+            Err(Error::ValidationDuplicateNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn test_all_permutations_exhaustive() {
+        for element_count in 0..=8u32 {
+            let mut all = all_permutations(element_count);
+            let count = usize::try_from(permutation_count(element_count)).unwrap();
+            assert_eq!(all.size_hint(), (count, Some(count)));
+            let mut previous: Option<Vec<u32>> = None;
+            let mut seen = 0;
+            for (rank, permutation) in all.by_ref().enumerate() {
+                if let Some(previous) = &previous {
+                    assert!(*previous < permutation);
+                }
+                let encoded = crate::encode(&permutation).unwrap();
+                assert_eq!(UBig::from_le_bytes(&encoded), UBig::from(rank));
+                let mut decoded = vec![0; permutation.len()];
+                crate::decode(&encoded, &mut decoded).unwrap();
+                assert_eq!(decoded, permutation);
+                previous = Some(permutation);
+                seen += 1;
+            }
+            assert_eq!(seen, count);
+            assert!(all.next().is_none());
+        }
+        assert_eq!(all_permutations(30).size_hint(), (usize::MAX, None));
+    }
+
+    #[test]
+    fn test_all_combinations() {
+        for n in 0..=10u32 {
+            for k in 0..=n + 1 {
+                let mut all = all_combinations(n, k);
+                let count = usize::try_from(combination_count(n, k)).unwrap();
+                assert_eq!(all.size_hint(), (count, Some(count)));
+                let combinations: Vec<Vec<u32>> = all.by_ref().collect();
+                assert_eq!(combinations.len(), count, "{n} {k}");
+                assert!(combinations.windows(2).all(|pair| pair[0] < pair[1]));
+                for combination in &combinations {
+                    assert_eq!(combination.len(), k as usize);
+                    assert!(combination.windows(2).all(|pair| pair[0] < pair[1]));
+                    assert!(combination.iter().all(|&number| number < n));
+                }
+                assert!(all.next().is_none());
+                assert_eq!(all.size_hint(), (0, Some(0)));
+            }
+        }
+        // Pascal's rule
+        for n in 1..40 {
+            for k in 1..n {
+                assert_eq!(
+                    combination_count(n, k),
+                    combination_count(n - 1, k - 1) + combination_count(n - 1, k)
+                );
+            }
+        }
+        assert_eq!(combination_count(32, 16), UBig::from(601_080_390u32));
+        assert_eq!(all_combinations(200, 100).size_hint(), (usize::MAX, None));
+    }
+
+    #[test]
+    fn test_rank_unrank_roundtrip() {
+        let sequence: Vec<u32> = crate::tests::shuffled(300, 831);
+        let rank = permutation_rank(&sequence).unwrap();
+        assert!(rank < permutation_count(300));
+        assert_eq!(permutation_unrank(&rank, 300).unwrap(), sequence);
+
+        let largest = permutation_count(300) - UBig::ONE;
+        let reversed: Vec<u32> = (0..300).rev().collect();
+        assert_eq!(permutation_unrank(&largest, 300).unwrap(), reversed);
+        assert!(matches!(
+            permutation_unrank(&permutation_count(300), 300),
+            Err(Error::ElementCountMismatch { .. })
+        ));
+
+        let mut results = vec![0; 300];
+        decode_ubig_owned(rank, &mut results).unwrap();
+        assert_eq!(results, sequence);
+        decode_ubig_owned(largest, &mut results).unwrap();
+        assert_eq!(results, reversed);
+        assert_eq!(
+            decode_ubig_owned(permutation_count(300), &mut results),
+            Err(Error::ElementCountMismatch {
+                expected: 301,
+                got: 300
+            })
+        );
+    }
+}
@@ -0,0 +1,234 @@
+use dashu::base::BitTest;
+
+use crate::{
+    encode_to_ubig, error::Error, get_encode_size, permutation_count, ranking::tree_bytes,
+};
+
+/// Bit length of the code of this specific permutation (not the worst case of `big_lehmer::get_encode_size`).  
+/// This is the information-theoretic size of the permutation, it measures how "compressible" it is.
+/// Costs a full encode.
+///
+/// # Examples
+/// ```
+/// assert_eq!(big_lehmer::code_bit_length(&[0, 1, 2, 3]).unwrap(), 0);
+/// // Rank 1
+/// assert_eq!(big_lehmer::code_bit_length(&[0, 1, 3, 2]).unwrap(), 1);
+/// // Rank 23
+/// assert_eq!(big_lehmer::code_bit_length(&[3, 2, 1, 0]).unwrap(), 5);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn code_bit_length(numbers: &[u32]) -> Result<u64, Error> {
+    Ok(encode_to_ubig(numbers)?.bit_len() as u64)
+}
+
+/// Byte length of the code of this specific permutation, `ceil(code_bit_length / 8)`.  
+/// This is the exact length of `big_lehmer::encode_trimmed`, so a trimmed buffer can be sized before writing it,
+/// or the caller can decide between a fixed size and a trimmed output.
+/// Costs a full encode, `big_lehmer::get_encode_size` remains the cheap worst case bound for the element count.
+///
+/// # Examples
+/// ```
+/// let sequence = [0, 1, 2, 3, 4, 5, 6, 7, 9, 8];
+/// assert_eq!(big_lehmer::actual_encode_size(&sequence).unwrap(), 1);
+/// assert_eq!(big_lehmer::get_encode_size(10), 3);
+/// assert_eq!(big_lehmer::actual_encode_size(&sequence).unwrap(), big_lehmer::encode_trimmed(&sequence).unwrap().len());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn actual_encode_size(numbers: &[u32]) -> Result<usize, Error> {
+    Ok(encode_to_ubig(numbers)?.bit_len().div_ceil(8))
+}
+
+/// Bit length of an already encoded code, ignoring superfluous high zero bytes.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[3, 2, 1, 0]).unwrap();
+/// assert_eq!(big_lehmer::code_bit_length_of(&encoded), 5);
+/// assert_eq!(big_lehmer::code_bit_length_of(&[23, 0, 0]), 5);
+/// ```
+#[must_use]
+pub fn code_bit_length_of(encoded: &[u8]) -> u64 {
+    encoded
+        .iter()
+        .rposition(|&byte| byte != 0)
+        .map_or(0, |index| {
+            index as u64 * 8 + u64::from(u8::BITS - encoded[index].leading_zeros())
+        })
+}
+
+/// Exact bit length of `element_count!`, the information content of the permutation space.  
+/// The largest code (`element_count! - 1`) has the same bit length,
+/// except for 0, 1 and 2 elements where `element_count!` is a power of two.
+/// Costs a big number product of all `element_count` numbers.
+///
+/// # Examples
+/// ```
+/// // 20! = 2432902008176640000
+/// assert_eq!(big_lehmer::factorial_bit_length(20), 62);
+/// let largest = big_lehmer::encode(&(0..20).rev().collect::<Vec<u32>>()).unwrap();
+/// assert_eq!(big_lehmer::code_bit_length_of(&largest), 62);
+/// ```
+#[must_use]
+pub fn factorial_bit_length(element_count: u32) -> u64 {
+    permutation_count(element_count).bit_len() as u64
+}
+
+/// Checks that `big_lehmer::get_encode_size` fits the largest code of `element_count` elements,
+/// against the exact byte size `ceil(bit_len(element_count! - 1) / 8)`.  
+/// `get_encode_size` sums float logarithms and adds a hand tuned padding, this turns the padding into a tested invariant.
+/// Costs a big number product of all `element_count` numbers, like `big_lehmer::factorial_bit_length`.
+///
+/// # Examples
+/// ```
+/// assert!(big_lehmer::verify_encode_size(4000));
+/// assert!(big_lehmer::verify_encode_size(0));
+/// ```
+#[must_use]
+pub fn verify_encode_size(element_count: u32) -> bool {
+    // Below 3 elements, element_count! is a power of two and the largest code is one bit shorter
+    let bits = if element_count <= 2 {
+        u64::from(element_count.saturating_sub(1))
+    } else {
+        factorial_bit_length(element_count)
+    };
+    get_encode_size(element_count) as u64 >= bits.div_ceil(8)
+}
+
+/// Largest element count supported by `big_lehmer::get_encode_size_const`
+pub const ENCODE_SIZE_CONST_MAX: u32 = 256;
+
+/// `const` version of `big_lehmer::get_encode_size` for up to `big_lehmer::ENCODE_SIZE_CONST_MAX` (256) elements.
+/// Returns the same values, the byte size of the largest code `element_count! - 1`.  
+/// Allows sizing buffers of small fixed permutations at compile time.
+///
+/// Computes `element_count!` exactly with fixed size integer math, instead of the float sum of logarithms.
+///
+/// # Examples
+/// ```
+/// use std::mem::MaybeUninit;
+/// let mut buffer = [MaybeUninit::uninit(); big_lehmer::get_encode_size_const(12)];
+/// let sequence = [11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0];
+/// let encoded = big_lehmer::encode_into_uninit(&sequence, &mut buffer).unwrap();
+/// assert_eq!(*encoded, *big_lehmer::encode(&sequence).unwrap());
+/// ```
+///
+/// # Panics
+///
+/// Panics when `element_count` is larger than `big_lehmer::ENCODE_SIZE_CONST_MAX`,
+/// at compile time when used in a const context.
+#[must_use]
+pub const fn get_encode_size_const(element_count: u32) -> usize {
+    assert!(
+        element_count <= ENCODE_SIZE_CONST_MAX,
+        "get_encode_size_const supports at most 256 elements"
+    );
+    // 256! has 1684 bits
+    const LIMBS: usize = 27;
+    let mut factorial = [0u64; LIMBS];
+    factorial[0] = 1;
+    let mut factor = 2;
+    while factor <= element_count {
+        let mut carry = 0u128;
+        let mut i = 0;
+        while i < LIMBS {
+            let product = factorial[i] as u128 * factor as u128 + carry;
+            // Truncation intended, the high part is the carry
+            factorial[i] = product as u64;
+            carry = product >> 64;
+            i += 1;
+        }
+        factor += 1;
+    }
+
+    // Bit length of element_count! - 1. element_count! > 2 is not a power of two,
+    // so that is the bit length of element_count! itself.
+    let bits = if element_count <= 2 {
+        element_count.saturating_sub(1) as usize
+    } else {
+        let mut top = LIMBS - 1;
+        while factorial[top] == 0 {
+            top -= 1;
+        }
+        top * 64 + (64 - factorial[top].leading_zeros() as usize)
+    };
+    bits.div_ceil(8)
+}
+
+/// Largest element count answered by `SMALL_ENCODE_SIZES`
+pub(crate) const SMALL_ENCODE_SIZE_MAX: u32 = 128;
+
+/// `big_lehmer::get_encode_size` of `0..=SMALL_ENCODE_SIZE_MAX` elements, computed at compile time.  
+/// 128 elements need 90 byte, so the sizes fit into a byte.
+#[allow(clippy::cast_possible_truncation)]
+pub(crate) static SMALL_ENCODE_SIZES: [u8; SMALL_ENCODE_SIZE_MAX as usize + 1] = {
+    let mut sizes = [0; SMALL_ENCODE_SIZE_MAX as usize + 1];
+    let mut element_count = 0;
+    while element_count <= SMALL_ENCODE_SIZE_MAX {
+        sizes[element_count as usize] = get_encode_size_const(element_count) as u8;
+        element_count += 1;
+    }
+    sizes
+};
+
+/// Bytes of the rank tree, saturates when it does not fit into memory
+fn saturating_tree_bytes(element_count: u32) -> usize {
+    tree_bytes(element_count).unwrap_or(usize::MAX)
+}
+
+/// Conservative estimate of the peak memory (in bytes) `big_lehmer::encode` uses for `element_count` elements,
+/// not counting the input slice itself.  
+/// Saturates at `usize::MAX` when the encode can not fit into memory on this target.
+///
+/// Consists of the rank tree (about 1.5 bit per element), the validation (1 bit per element),
+/// the caches (16 byte per ~32 bit of code) and the big numbers of the reduce and the output (a few times the code size).
+///
+/// # Examples
+/// ```
+/// let estimate = big_lehmer::estimate_encode_memory(1_000_000);
+/// // Dominated by the big numbers and the caches
+/// assert!(estimate > 6 * big_lehmer::get_encode_size(1_000_000));
+/// assert!(estimate < 64 * 1024 * 1024);
+/// ```
+#[must_use]
+pub fn estimate_encode_memory(element_count: u32) -> usize {
+    let code = get_encode_size(element_count);
+    // A step multiplies by at most 2^32, so a cache is only pushed once its mul uses all but 4 byte of its word
+    let cache_bytes = std::mem::size_of::<crate::encode::CacheWord>() - 4;
+    let caches =
+        (code / cache_bytes + 2).saturating_mul(std::mem::size_of::<crate::encode::Cache>());
+    // add + mul of the reduce, the intermediate products and the output bytes
+    let big_numbers = code.saturating_mul(6);
+    saturating_tree_bytes(element_count)
+        .saturating_add((element_count as usize).div_ceil(8))
+        .saturating_add(caches)
+        .saturating_add(big_numbers)
+}
+
+/// Conservative estimate of the peak memory (in bytes) `big_lehmer::decode` uses for `element_count` elements,
+/// not counting the encoded input and the `results` slice.  
+/// Saturates at `usize::MAX` when the decode can not fit into memory on this target.
+///
+/// Consists of the rank tree (about 1.5 bit per element), the factoradic digits (4 byte per element)
+/// and the big numbers of the divisions (a few times the code size).
+///
+/// # Examples
+/// ```
+/// let estimate = big_lehmer::estimate_decode_memory(1_000_000);
+/// assert!(estimate > 1_000_000 * 4);
+/// assert!(estimate < 64 * 1024 * 1024);
+/// ```
+#[must_use]
+pub fn estimate_decode_memory(element_count: u32) -> usize {
+    let code = get_encode_size(element_count);
+    // The input, the quotient and remainder of the split currently being divided
+    let big_numbers = code.saturating_mul(4);
+    saturating_tree_bytes(element_count)
+        .saturating_add((element_count as usize).saturating_mul(4))
+        .saturating_add(big_numbers)
+}
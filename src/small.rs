@@ -0,0 +1,235 @@
+use crate::{encode, error::Error, get_encode_size};
+
+/// 34! < 2^128, so the rank of up to 34 elements fits into an u128
+const SMALL_LIMIT: usize = 34;
+
+/// Same as `big_lehmer::encode`, but writes the code into a stack array of compile time size `N`.  
+/// Returns the array and the length of the code in it, the bytes after the code are zero.
+///
+/// Up to 34 elements no heap allocation happens at all,
+/// which matters in tight loops ranking tiny permutations (card hands, dice orders).
+/// Larger permutations fall back to `big_lehmer::encode`.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let (code, len) = big_lehmer::encode_small::<8>(&sequence).unwrap();
+/// assert_eq!(code[..len], *big_lehmer::encode(&sequence).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// `Error::OutVectorSize` when `N` is smaller than `big_lehmer::get_encode_size(numbers.len())`.  
+/// Same errors as `big_lehmer::encode`
+pub fn encode_small<const N: usize>(numbers: &[u32]) -> Result<([u8; N], usize), Error> {
+    let element_count = u32::try_from(numbers.len()).map_err(|_| Error::SequenceToLong {
+        element_count: numbers.len(),
+    })?;
+    if N < get_encode_size(element_count) {
+        return Err(Error::OutVectorSize {
+            byte_size: N,
+            element_count,
+        });
+    }
+
+    let mut out = [0u8; N];
+    if numbers.len() <= SMALL_LIMIT {
+        let rank = small_rank(numbers)?;
+        let len = (u128::BITS - rank.leading_zeros()).div_ceil(8) as usize;
+        out[..len].copy_from_slice(&rank.to_le_bytes()[..len]);
+        Ok((out, len))
+    } else {
+        let code = encode(numbers)?;
+        out[..code.len()].copy_from_slice(&code);
+        Ok((out, code.len()))
+    }
+}
+
+/// Bytes of the largest inline `CodeBytes`, the code of up to 34 elements
+const INLINE_BYTES: usize = 16;
+
+/// Lehmer code that stores up to 16 bytes inline and only allocates for larger codes.
+/// Created by `big_lehmer::encode_code_bytes`, the bytes are the same as `big_lehmer::encode`.
+///
+/// # Examples
+/// ```
+/// let code = big_lehmer::encode_code_bytes(&[7, 2, 0, 6, 5, 1, 4, 3]).unwrap();
+/// assert!(code.is_inline());
+/// assert_eq!(code.as_slice(), &*big_lehmer::encode(&[7, 2, 0, 6, 5, 1, 4, 3]).unwrap());
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CodeBytes(Repr);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Repr {
+    Inline { bytes: [u8; INLINE_BYTES], len: u8 },
+    Heap(Box<[u8]>),
+}
+
+impl CodeBytes {
+    #[must_use]
+    pub fn as_slice(&self) -> &[u8] {
+        match &self.0 {
+            Repr::Inline { bytes, len } => &bytes[..usize::from(*len)],
+            Repr::Heap(bytes) => bytes,
+        }
+    }
+
+    /// Allocates for inline codes
+    #[must_use]
+    pub fn into_vec(self) -> Vec<u8> {
+        match self.0 {
+            Repr::Inline { .. } => self.as_slice().to_vec(),
+            Repr::Heap(bytes) => bytes.into_vec(),
+        }
+    }
+
+    /// Whether the code is stored without heap allocation
+    #[must_use]
+    pub fn is_inline(&self) -> bool {
+        matches!(self.0, Repr::Inline { .. })
+    }
+}
+
+impl std::ops::Deref for CodeBytes {
+    type Target = [u8];
+
+    fn deref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+impl AsRef<[u8]> for CodeBytes {
+    fn as_ref(&self) -> &[u8] {
+        self.as_slice()
+    }
+}
+
+/// Same as `big_lehmer::encode`, but returns a `big_lehmer::CodeBytes`,
+/// which keeps codes of up to 34 elements (at most 16 bytes) inline without any heap allocation.  
+/// Larger permutations allocate like `big_lehmer::encode`, the return type stays the same.
+///
+/// Encoding permutations of 12 elements, `encode_code_bytes` makes no allocation where `encode` makes six.
+///
+/// # Examples
+/// ```
+/// let small = big_lehmer::encode_code_bytes(&[2, 0, 1]).unwrap();
+/// assert!(small.is_inline());
+/// assert_eq!(*small, [4]);
+///
+/// let large: Vec<u32> = (0..100).rev().collect();
+/// let large = big_lehmer::encode_code_bytes(&large).unwrap();
+/// assert!(!large.is_inline());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_code_bytes(numbers: &[u32]) -> Result<CodeBytes, Error> {
+    if numbers.len() > SMALL_LIMIT {
+        return encode(numbers).map(|bytes| CodeBytes(Repr::Heap(bytes)));
+    }
+    let rank = small_rank(numbers)?;
+    // Truncation is fine, the rank has at most 16 bytes
+    #[allow(clippy::cast_possible_truncation)]
+    let len = (u128::BITS - rank.leading_zeros()).div_ceil(8) as u8;
+    Ok(CodeBytes(Repr::Inline {
+        bytes: rank.to_le_bytes(),
+        len,
+    }))
+}
+
+/// Rank of up to `SMALL_LIMIT` elements. A bit mask replaces both the validation and the tree.
+fn small_rank(numbers: &[u32]) -> Result<u128, Error> {
+    let mut visited = 0u64;
+    let mut rank = 0u128;
+    for (index, &number) in numbers.iter().enumerate() {
+        if number as usize >= numbers.len() {
+            return Err(Error::ValidationOutOfRange { index, number });
+        }
+        let bit = 1u64 << number;
+        if visited & bit != 0 {
+            return Err(Error::ValidationDuplicateNumber { index, number });
+        }
+        // Numbers smaller than `number` that are not used yet
+        let digit = (!visited & (bit - 1)).count_ones();
+        visited |= bit;
+        rank = rank * (numbers.len() - index) as u128 + u128::from(digit);
+    }
+    Ok(rank)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_small_matches_encode() {
+        let sequences: [&[u32]; 6] = [
+            &[],
+            &[0],
+            &[1, 0],
+            &[0, 1, 2, 3],
+            &[7, 2, 0, 6, 5, 1, 4, 3],
+            &[
+                33, 32, 31, 30, 29, 28, 27, 26, 25, 24, 23, 22, 21, 20, 19, 18, 17, 16, 15, 14, 13,
+                12, 11, 10, 9, 8, 7, 6, 5, 4, 3, 2, 1, 0,
+            ],
+        ];
+        for sequence in sequences {
+            let (code, len) = encode_small::<16>(sequence).unwrap();
+            assert_eq!(code[..len], *encode(sequence).unwrap());
+            assert!(code[len..].iter().all(|&b| b == 0));
+        }
+
+        let large: Vec<u32> = (0..40).rev().collect();
+        let (code, len) = encode_small::<64>(&large).unwrap();
+        assert_eq!(code[..len], *encode(&large).unwrap());
+    }
+
+    #[test]
+    fn test_code_bytes() {
+        for len in [0, 1, 2, 12, 20, 33, 34, 35, 100] {
+            let sequence: Vec<u32> = (0..len).rev().collect();
+            let code = encode_code_bytes(&sequence).unwrap();
+            assert_eq!(code.is_inline(), len <= 34);
+            assert_eq!(*code, *encode(&sequence).unwrap());
+            assert_eq!(code.clone().into_vec(), code.as_slice());
+        }
+        // The inline bytes after the code do not matter for equality, they are always zero
+        assert_eq!(
+            encode_code_bytes(&[1, 0]).unwrap(),
+            encode_code_bytes(&[1, 0]).unwrap()
+        );
+        assert!(encode_code_bytes(&[0, 1]).unwrap().is_empty());
+        assert!(matches!(
+            encode_code_bytes(&[0, 0]),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn test_small_errors() {
+        assert!(matches!(
+            encode_small::<7>(&[0; 20]),
+            Err(Error::OutVectorSize { byte_size: 7, .. })
+        ));
+        assert!(matches!(
+            encode_small::<8>(&[0, 0]),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 0
+            })
+        ));
+        assert!(matches!(
+            encode_small::<8>(&[0, 2]),
+            Err(Error::ValidationOutOfRange {
+                index: 1,
+                number: 2
+            })
+        ));
+    }
+}
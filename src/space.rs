@@ -0,0 +1,176 @@
+use dashu::integer::UBig;
+
+use crate::{decode, encode, error::Error, get_encode_size, permutation_count};
+
+/// The ranks `0..element_count!` of one fixed length.
+/// Computes the factorial once, for workloads that check, sample, encode and decode many permutations of the same length.
+///
+/// # Examples
+/// ```
+/// use big_lehmer::RankSpace;
+/// use dashu::integer::UBig;
+/// let space = RankSpace::new(3);
+/// assert_eq!(*space.count(), UBig::from(6u8));
+/// assert!(space.contains(&UBig::from(5u8)));
+/// assert!(!space.contains(&UBig::from(6u8)));
+///
+/// let encoded = space.encode(&[2, 0, 1]).unwrap();
+/// assert_eq!(space.decode(&encoded).unwrap(), [2, 0, 1]);
+/// assert!(space.encode(&[1, 0]).is_err());
+/// assert_eq!(RankSpace::new(20).size_bytes(), 8);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RankSpace {
+    element_count: u32,
+    count: UBig,
+}
+
+impl RankSpace {
+    /// Space of the permutations of `element_count` elements, computes `element_count!`
+    #[must_use]
+    pub fn new(element_count: u32) -> Self {
+        Self {
+            element_count,
+            count: permutation_count(element_count),
+        }
+    }
+
+    #[must_use]
+    pub fn element_count(&self) -> u32 {
+        self.element_count
+    }
+
+    /// Number of permutations, `element_count!`
+    #[must_use]
+    pub fn count(&self) -> &UBig {
+        &self.count
+    }
+
+    /// Whether `rank` is the rank of a permutation of this length, `rank < element_count!`
+    #[must_use]
+    pub fn contains(&self, rank: &UBig) -> bool {
+        *rank < self.count
+    }
+
+    /// Byte size of the codes of this length, same as `big_lehmer::get_encode_size`
+    #[must_use]
+    pub fn size_bytes(&self) -> usize {
+        get_encode_size(self.element_count)
+    }
+
+    /// Uniformly random rank of this space.
+    /// Draws as many random bits as the largest rank has and retries when the draw is too large,
+    /// that is less than two draws on average.
+    ///
+    /// # Examples
+    /// ```
+    /// use rand::SeedableRng;
+    /// let space = big_lehmer::RankSpace::new(100);
+    /// let mut rng = rand::rngs::StdRng::seed_from_u64(7);
+    /// assert!(space.contains(&space.random(&mut rng)));
+    /// ```
+    #[cfg(feature = "rand")]
+    pub fn random<R: rand::Rng + ?Sized>(&self, rng: &mut R) -> UBig {
+        use dashu::base::BitTest;
+        let bits = (&self.count - UBig::ONE).bit_len();
+        let mut bytes = vec![0; bits.div_ceil(8)];
+        let top_mask = u8::MAX >> (bytes.len() * 8 - bits);
+        loop {
+            rng.fill_bytes(&mut bytes);
+            if let Some(last) = bytes.last_mut() {
+                // Only the lowest bits of the top byte belong to the draw
+                *last &= top_mask;
+            }
+            let rank = UBig::from_le_bytes(&bytes);
+            if self.contains(&rank) {
+                return rank;
+            }
+        }
+    }
+
+    /// Same as `big_lehmer::encode`, for a permutation of this length
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::LengthMismatch` when `numbers` does not have `element_count` elements.
+    /// Same as `big_lehmer::encode`
+    pub fn encode(&self, numbers: &[u32]) -> Result<Box<[u8]>, Error> {
+        self.check_length(numbers.len())?;
+        encode(numbers)
+    }
+
+    /// Same as `big_lehmer::decode`, into a new permutation of this length
+    ///
+    /// # Errors
+    ///
+    /// Same as `big_lehmer::decode`
+    pub fn decode(&self, encoded: &[u8]) -> Result<Vec<u32>, Error> {
+        let len = usize::try_from(self.element_count).map_err(|_| Error::SequenceToLong {
+            element_count: usize::MAX,
+        })?;
+        let mut results = vec![0; len];
+        decode(encoded, &mut results)?;
+        Ok(results)
+    }
+
+    fn check_length(&self, got: usize) -> Result<(), Error> {
+        let expected = usize::try_from(self.element_count).unwrap_or(usize::MAX);
+        if got != expected {
+            return Err(Error::LengthMismatch { expected, got });
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rank_space() {
+        for element_count in [0, 1, 2, 5, 20, 21, 300] {
+            let space = RankSpace::new(element_count);
+            assert_eq!(space.element_count(), element_count);
+            assert_eq!(*space.count(), permutation_count(element_count));
+            assert!(space.contains(&(space.count() - UBig::ONE)));
+            assert!(!space.contains(space.count()));
+            assert_eq!(space.size_bytes(), get_encode_size(element_count));
+
+            let sequence: Vec<u32> = (0..element_count).rev().collect();
+            let encoded = space.encode(&sequence).unwrap();
+            assert_eq!(encoded.len(), space.size_bytes());
+            assert_eq!(space.decode(&encoded).unwrap(), sequence);
+        }
+        assert_eq!(RankSpace::new(20).size_bytes(), 8);
+        assert_eq!(
+            RankSpace::new(3).encode(&[0, 1]),
+            Err(Error::LengthMismatch {
+                expected: 3,
+                got: 2
+            })
+        );
+        let too_large = encode(&[3, 2, 1, 0]).unwrap();
+        assert!(RankSpace::new(3).decode(&too_large).is_err());
+    }
+
+    #[cfg(feature = "rand")]
+    #[test]
+    fn test_rank_space_random() {
+        use rand::SeedableRng;
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(917);
+        assert_eq!(RankSpace::new(0).random(&mut rng), UBig::ZERO);
+        assert_eq!(RankSpace::new(1).random(&mut rng), UBig::ZERO);
+        // All 6 ranks of 3 elements show up
+        let space = RankSpace::new(3);
+        let mut seen = [false; 6];
+        for _ in 0..200 {
+            let rank = space.random(&mut rng);
+            seen[usize::try_from(&rank).unwrap()] = true;
+        }
+        assert!(seen.iter().all(|&seen| seen));
+        let space = RankSpace::new(1000);
+        for _ in 0..20 {
+            assert!(space.contains(&space.random(&mut rng)));
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::{
+    decode, encode,
+    error::Error,
+    format::{read_varint, write_varint},
+    get_encode_size,
+    validation::is_valid_permutation,
+};
+
+const DENSE: u8 = 0;
+const SPARSE: u8 = 1;
+
+/// Encodes a mostly identity permutation by storing only the displaced elements.
+///
+/// Layout: one tag byte, `1` sparse or `0` dense.
+/// Sparse is followed by the displaced count as LEB128 varint, then for every displaced element
+/// the distance to the previous displaced position and the element, both as varint.
+/// Dense is followed by the bytes of `big_lehmer::encode`.
+///
+/// Sparse is chosen while its size is below `big_lehmer::get_encode_size(numbers.len())`, the size bound of the dense code,
+/// so the output never exceeds that bound by more than the few header bytes.
+/// For 1000 elements this holds up to roughly 1 in 3 displaced elements.
+/// 1_000_000 elements with 10 swaps need 82 bytes instead of the 2.3 MB of the dense code.
+/// The dense fallback costs a full `big_lehmer::encode`.
+///
+/// # Examples
+/// ```
+/// let mut sequence: Vec<u32> = (0..1000).collect();
+/// sequence.swap(10, 900);
+/// let encoded = big_lehmer::encode_sparse(&sequence).unwrap();
+/// assert!(encoded.len() < 10);
+///
+/// let mut roundtrip = vec![0; 1000];
+/// big_lehmer::decode_sparse(&encoded, &mut roundtrip).unwrap();
+/// assert_eq!(sequence, roundtrip);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_sparse(numbers: &[u32]) -> Result<Box<[u8]>, Error> {
+    is_valid_permutation(numbers)?;
+    let element_count = u32::try_from(numbers.len()).unwrap();
+    let dense_bound = get_encode_size(element_count);
+
+    let mut displaced = vec![];
+    let mut previous = 0;
+    let mut count = 0u64;
+    for (position, &number) in numbers.iter().enumerate() {
+        if number as usize == position {
+            continue;
+        }
+        write_varint(&mut displaced, (position - previous) as u64);
+        write_varint(&mut displaced, u64::from(number));
+        previous = position;
+        count += 1;
+        // Stop collecting as soon as dense is known to be smaller
+        if displaced.len() >= dense_bound {
+            let mut result = vec![DENSE];
+            result.extend_from_slice(&encode(numbers)?);
+            return Ok(result.into_boxed_slice());
+        }
+    }
+    let mut result = vec![SPARSE];
+    write_varint(&mut result, count);
+    result.extend_from_slice(&displaced);
+    Ok(result.into_boxed_slice())
+}
+
+/// Decodes a code created by `big_lehmer::encode_sparse` into `results`.  
+/// The positions that are not stored are filled with the identity.
+///
+/// # Errors
+///
+/// `Error::Decode` when the code is malformed: unknown tag, truncated varints,
+/// displaced positions out of range or not ascending, or the result is not a permutation.  
+/// Same as `big_lehmer::decode` for a dense code.
+pub fn decode_sparse(encoded: &[u8], results: &mut [u32]) -> Result<(), Error> {
+    let (&tag, mut bytes) = encoded.split_first().ok_or(Error::Decode)?;
+    match tag {
+        DENSE => decode(bytes, results),
+        SPARSE => {
+            let element_count =
+                u32::try_from(results.len()).map_err(|_| Error::SequenceToLong {
+                    element_count: results.len(),
+                })?;
+            let mut next_varint = || {
+                let (value, len) = read_varint(bytes).ok_or(Error::Decode)?;
+                bytes = &bytes[len..];
+                Ok::<u64, Error>(value)
+            };
+            for (position, result) in (0..element_count).zip(results.iter_mut()) {
+                *result = position;
+            }
+            let count = next_varint()?;
+            let mut position = 0u64;
+            for index in 0..count {
+                let distance = next_varint()?;
+                if index > 0 && distance == 0 {
+                    return Err(Error::Decode);
+                }
+                position = position.checked_add(distance).ok_or(Error::Decode)?;
+                let number = next_varint()?;
+                let slot = usize::try_from(position)
+                    .ok()
+                    .and_then(|position| results.get_mut(position))
+                    .ok_or(Error::Decode)?;
+                *slot = u32::try_from(number).map_err(|_| Error::Decode)?;
+            }
+            if !bytes.is_empty() {
+                return Err(Error::Decode);
+            }
+            is_valid_permutation(results).map_err(|_| Error::Decode)
+        }
+        _ => Err(Error::Decode),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roundtrip(numbers: &[u32]) -> Box<[u8]> {
+        let encoded = encode_sparse(numbers).unwrap();
+        let mut results = vec![0; numbers.len()];
+        decode_sparse(&encoded, &mut results).unwrap();
+        assert_eq!(numbers, results);
+        encoded
+    }
+
+    #[test]
+    fn test_sparse_roundtrip() {
+        assert_eq!(*roundtrip(&[]), [SPARSE, 0]);
+        assert_eq!(*roundtrip(&[0, 1, 2, 3]), [SPARSE, 0]);
+        // Positions 1 and 5 displaced
+        let mut sequence: Vec<u32> = (0..64).collect();
+        sequence.swap(1, 5);
+        assert_eq!(*roundtrip(&sequence), [SPARSE, 2, 1, 5, 4, 1]);
+
+        let mut sequence: Vec<u32> = (0..1_000_000).collect();
+        for i in 0..10 {
+            sequence.swap(i * 1000, 999_999 - i * 7);
+        }
+        let encoded = roundtrip(&sequence);
+        assert_eq!(encoded[0], SPARSE);
+        assert_eq!(encoded.len(), 82);
+    }
+
+    #[test]
+    fn test_sparse_dense_fallback() {
+        let sequence: Vec<u32> = (0..1000).rev().collect();
+        let encoded = roundtrip(&sequence);
+        assert_eq!(encoded[0], DENSE);
+        assert_eq!(encoded[1..], *encode(&sequence).unwrap());
+
+        // Falls back before the sparse part outgrows the dense bound
+        let mut sequence: Vec<u32> = (0..1000).collect();
+        for i in (0..1000).step_by(2) {
+            sequence.swap(i, i + 1);
+        }
+        let encoded = roundtrip(&sequence);
+        assert_eq!(encoded[0], DENSE);
+        assert!(encoded.len() <= get_encode_size(1000) + 1);
+    }
+
+    #[test]
+    fn test_sparse_malformed() {
+        let mut results = [0; 3];
+        for malformed in [
+            &[][..],
+            &[7],
+            &[SPARSE],
+            // Truncated pair
+            &[SPARSE, 1, 0],
+            // Position out of range
+            &[SPARSE, 1, 3, 0],
+            // Same position twice
+            &[SPARSE, 2, 0, 2, 0, 1],
+            // Not a permutation
+            &[SPARSE, 1, 0, 1],
+            // Trailing bytes
+            &[SPARSE, 0, 0],
+        ] {
+            assert!(matches!(
+                decode_sparse(malformed, &mut results),
+                Err(Error::Decode)
+            ));
+        }
+        assert!(matches!(
+            encode_sparse(&[0, 0]),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 0
+            })
+        ));
+    }
+}
@@ -0,0 +1,299 @@
+use crate::{
+    error::Error,
+    factoradic::{decode_to_factoradic, factoradic_digits},
+    validation::{is_valid_permutation, visit, Visited},
+};
+
+/// Sign of the permutation, `true` when it is odd.  
+/// The parity of a permutation is the parity of its inversion count, the sum of its Lehmer digits.
+///
+/// # Examples
+/// ```
+/// assert!(!big_lehmer::parity(&[0, 1, 2]).unwrap());
+/// assert!(big_lehmer::parity(&[1, 0, 2]).unwrap());
+/// assert!(!big_lehmer::parity(&[1, 2, 0]).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn parity(numbers: &[u32]) -> Result<bool, Error> {
+    factoradic_digits(numbers)?.try_fold(false, |odd, digit| Ok(odd ^ (digit? % 2 == 1)))
+}
+
+/// Same as `big_lehmer::parity`, but of an encoded permutation of `element_count` elements.  
+/// Only computes the Lehmer digits of the code, the permutation itself is not reconstructed.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[2, 1, 0]).unwrap();
+/// assert!(big_lehmer::parity_of_code(&encoded, 3).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn parity_of_code(encoded: &[u8], element_count: u32) -> Result<bool, Error> {
+    let digits = decode_to_factoradic(encoded, element_count)?;
+    Ok(digits
+        .iter()
+        .fold(false, |odd, digit| odd ^ (digit % 2 == 1)))
+}
+
+/// Number of inversions of the permutation, the pairs `i < j` with `numbers[i] > numbers[j]`.  
+/// This is the sum of the Lehmer digits and the Kendall tau distance to the identity.
+/// Costs O(N log N) through the encode tree, without the big number part of encode.
+///
+/// # Examples
+/// ```
+/// assert_eq!(big_lehmer::inversion_count(&[0, 1, 2, 3]).unwrap(), 0);
+/// assert_eq!(big_lehmer::inversion_count(&[1, 3, 0, 2]).unwrap(), 3);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn inversion_count(numbers: &[u32]) -> Result<u64, Error> {
+    factoradic_digits(numbers)?.try_fold(0, |count, digit| Ok(count + u64::from(digit?)))
+}
+
+/// Kendall tau distance, the number of pairs that `a` and `b` order differently.  
+/// `a` is relabeled by the positions of its numbers in `b`, the inversions of that permutation are the disagreements.
+/// Costs O(N log N) through the encode tree.
+///
+/// # Examples
+/// ```
+/// assert_eq!(big_lehmer::kendall_tau(&[0, 1, 2], &[0, 1, 2]).unwrap(), 0);
+/// // Only the pair of 1 and 2 disagrees
+/// assert_eq!(big_lehmer::kendall_tau(&[0, 1, 2], &[0, 2, 1]).unwrap(), 1);
+/// assert_eq!(big_lehmer::kendall_tau(&[2, 1, 0], &[0, 1, 2]).unwrap(), 3);
+/// ```
+///
+/// # Errors
+///
+/// Errors with `Error::LengthMismatch` when `a` and `b` have different lengths.  
+/// Validation errors of `a` or `b`, same as `big_lehmer::encode`
+pub fn kendall_tau(a: &[u32], b: &[u32]) -> Result<u64, Error> {
+    if a.len() != b.len() {
+        return Err(Error::LengthMismatch {
+            expected: a.len(),
+            got: b.len(),
+        });
+    }
+    if u32::try_from(a.len()).is_err() {
+        return Err(Error::SequenceToLong {
+            element_count: a.len(),
+        });
+    }
+    is_valid_permutation(a)?;
+    is_valid_permutation(b)?;
+    let mut positions = vec![0; b.len()];
+    for (position, &number) in (0u32..).zip(b) {
+        positions[number as usize] = position;
+    }
+    let relabeled: Vec<u32> = a.iter().map(|&number| positions[number as usize]).collect();
+    inversion_count(&relabeled)
+}
+
+/// Decomposes the permutation into its disjoint cycles, the standard alternative notation of a permutation.  
+/// The cycle `[a, b, c]` maps `a` to `b`, `b` to `c` and `c` back to `a`, so `numbers[a] == b`.
+/// Every cycle starts with its smallest number and the cycles are sorted by it, fixed points are cycles of length 1.
+/// The parity is the sum of `cycle.len() - 1` over all cycles, modulo 2.
+///
+/// # Examples
+/// ```
+/// assert_eq!(big_lehmer::to_cycles(&[1, 2, 0, 3]).unwrap(), [vec![0, 1, 2], vec![3]]);
+/// assert_eq!(big_lehmer::to_cycles(&[2, 3, 0, 1]).unwrap(), [vec![0, 2], vec![1, 3]]);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::is_valid_permutation`
+pub fn to_cycles(numbers: &[u32]) -> Result<Vec<Vec<u32>>, Error> {
+    is_valid_permutation(numbers)?;
+    let mut visited = vec![false; numbers.len()];
+    let mut cycles = vec![];
+    for start in 0..numbers.len() {
+        let mut cycle = vec![];
+        let mut position = start;
+        while !visited[position] {
+            visited[position] = true;
+            // Validated above, the positions fit into u32
+            cycle.push(position as u32);
+            position = numbers[position] as usize;
+        }
+        if !cycle.is_empty() {
+            cycles.push(cycle);
+        }
+    }
+    Ok(cycles)
+}
+
+/// Rebuilds the permutation of `0..element_count` from its cycles, the inverse of `big_lehmer::to_cycles`.  
+/// The cycles may start at any of their numbers and come in any order,
+/// numbers that are in no cycle are fixed points, so the common notation without the cycles of length 1 works too.
+///
+/// # Examples
+/// ```
+/// assert_eq!(big_lehmer::from_cycles(&[vec![0, 1, 2], vec![3]], 4).unwrap(), [1, 2, 0, 3]);
+/// assert_eq!(big_lehmer::from_cycles(&[vec![3, 1]], 4).unwrap(), [0, 3, 2, 1]);
+/// let sequence = [4, 0, 3, 1, 2];
+/// assert_eq!(big_lehmer::from_cycles(&big_lehmer::to_cycles(&sequence).unwrap(), 5).unwrap(), sequence);
+/// ```
+///
+/// # Errors
+///
+/// `Error::ValidationDuplicateNumber` or `Error::ValidationOutOfRange` for the first number that is in more than one cycle
+/// or not in `0..element_count`, `index` counts the numbers of all cycles in order.
+pub fn from_cycles(cycles: &[Vec<u32>], element_count: u32) -> Result<Vec<u32>, Error> {
+    let mut validation = Visited::new(element_count as usize);
+    for (index, &number) in cycles.iter().flatten().enumerate() {
+        visit(&mut validation, index, number)?;
+    }
+    let mut numbers: Vec<u32> = (0..element_count).collect();
+    for cycle in cycles {
+        for (&from, &to) in cycle.iter().zip(cycle.iter().cycle().skip(1)) {
+            numbers[from as usize] = to;
+        }
+    }
+    Ok(numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{all_permutations, encode};
+
+    #[test]
+    fn test_parity() {
+        for (numbers, odd) in [
+            (&[][..], false),
+            (&[0], false),
+            (&[1, 0], true),
+            (&[0, 2, 1], true),
+            (&[2, 0, 1], false),
+            (&[2, 1, 0], true),
+            (&[1, 0, 3, 2], false),
+            (&[1, 2, 3, 0], true),
+            (&[3, 2, 1, 0], false),
+        ] {
+            assert_eq!(parity(numbers).unwrap(), odd, "{numbers:?}");
+            let encoded = encode(numbers).unwrap();
+            let element_count = u32::try_from(numbers.len()).unwrap();
+            assert_eq!(parity_of_code(&encoded, element_count).unwrap(), odd);
+        }
+
+        // Every swap flips the parity
+        let mut sequence: Vec<u32> = crate::tests::shuffled(1000, 854);
+        let odd = parity(&sequence).unwrap();
+        sequence.swap(3, 500);
+        assert_eq!(parity(&sequence).unwrap(), !odd);
+
+        assert!(parity(&[0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_inversion_count() {
+        for n in [0u64, 1, 2, 8, 1000] {
+            let reverse: Vec<u32> = (0..u32::try_from(n).unwrap()).rev().collect();
+            assert_eq!(
+                inversion_count(&reverse).unwrap(),
+                n * n.saturating_sub(1) / 2
+            );
+        }
+        assert_eq!(inversion_count(&[7, 6, 5, 4, 3, 2, 1, 0]).unwrap(), 28);
+
+        // Compare with the quadratic definition
+        let sequence: Vec<u32> = crate::tests::shuffled(300, 855);
+        let mut expected = 0;
+        for i in 0..sequence.len() {
+            for j in i + 1..sequence.len() {
+                expected += u64::from(sequence[i] > sequence[j]);
+            }
+        }
+        assert_eq!(inversion_count(&sequence).unwrap(), expected);
+        assert_eq!(parity(&sequence).unwrap(), expected % 2 == 1);
+        assert!(inversion_count(&[0, 2]).is_err());
+    }
+
+    #[test]
+    fn test_cycles() {
+        for element_count in 0..=6 {
+            for sequence in all_permutations(element_count) {
+                let cycles = to_cycles(&sequence).unwrap();
+                assert_eq!(from_cycles(&cycles, element_count).unwrap(), sequence);
+                let swaps: usize = cycles.iter().map(|cycle| cycle.len() - 1).sum();
+                assert_eq!(parity(&sequence).unwrap(), swaps % 2 == 1);
+                let starts: Vec<u32> = cycles.iter().map(|cycle| cycle[0]).collect();
+                assert!(starts.is_sorted());
+                assert!(cycles
+                    .iter()
+                    .all(|cycle| cycle.iter().all(|&n| n >= cycle[0])));
+            }
+        }
+
+        // Rotated cycles, any order and omitted fixed points
+        let sequence = from_cycles(&[vec![5, 2], vec![], vec![4, 0, 3]], 7).unwrap();
+        assert_eq!(sequence, [3, 1, 5, 4, 0, 2, 6]);
+        assert_eq!(
+            to_cycles(&sequence).unwrap(),
+            [vec![0, 3, 4], vec![1], vec![2, 5], vec![6]]
+        );
+        assert!(from_cycles(&[], 0).unwrap().is_empty());
+
+        assert!(matches!(
+            from_cycles(&[vec![0, 1], vec![2, 1]], 3),
+            Err(Error::ValidationDuplicateNumber {
+                index: 3,
+                number: 1
+            })
+        ));
+        assert!(matches!(
+            from_cycles(&[vec![0, 3]], 3),
+            Err(Error::ValidationOutOfRange {
+                index: 1,
+                number: 3
+            })
+        ));
+        assert!(to_cycles(&[0, 0]).is_err());
+    }
+
+    #[test]
+    fn test_kendall_tau() {
+        let a: Vec<u32> = crate::tests::shuffled(200, 857);
+        let b: Vec<u32> = crate::tests::shuffled(200, 856);
+        let position = |sequence: &[u32], number: u32| sequence.iter().position(|&n| n == number);
+        let mut expected = 0;
+        for x in 0..200 {
+            for y in x + 1..200 {
+                let order_a = position(&a, x) < position(&a, y);
+                let order_b = position(&b, x) < position(&b, y);
+                expected += u64::from(order_a != order_b);
+            }
+        }
+        assert_eq!(kendall_tau(&a, &b).unwrap(), expected);
+        assert_eq!(kendall_tau(&b, &a).unwrap(), expected);
+        assert_eq!(kendall_tau(&a, &a).unwrap(), 0);
+        let identity: Vec<u32> = (0..200).collect();
+        assert_eq!(
+            kendall_tau(&a, &identity).unwrap(),
+            inversion_count(&a).unwrap()
+        );
+
+        assert!(matches!(
+            kendall_tau(&[0, 1], &[0]),
+            Err(Error::LengthMismatch {
+                expected: 2,
+                got: 1
+            })
+        ));
+        assert!(matches!(
+            kendall_tau(&[0, 1], &[1, 1]),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 1
+            })
+        ));
+        assert!(kendall_tau(&[0, 2], &[0, 1]).is_err());
+    }
+}
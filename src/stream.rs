@@ -0,0 +1,555 @@
+use std::io::{Read, Write};
+
+use dashu::{
+    base::BitTest,
+    integer::{UBig, Word},
+};
+
+use crate::{
+    encode::{reduce_caches, Cache, Encoder},
+    error::Error,
+    format::{
+        decode_self_describing, encode_self_describing, read_varint_from, write_varint,
+        EncodedLehmer,
+    },
+    try_decode_for_each,
+    validation::visit,
+};
+
+/// Incremental encoder that receives the numbers one at a time and writes the code to `W` on `finish`.
+///
+/// The code can only be finalized after the reduce, so nothing is written before `finish`.
+/// The point is that the input never has to be held in memory as a whole:
+/// while pushing, only the tree (about 1.5 bit per element), the validation (1 bit per element)
+/// and the caches (roughly the size of the code) are held.
+/// The slice based `big_lehmer::encode` additionally needs the 4 byte per element input slice.
+///
+/// # Examples
+/// ```
+/// let mut stream = big_lehmer::StreamEncoder::new(Vec::new(), 4).unwrap();
+/// for number in [3, 1, 0, 2] {
+///     stream.push(number).unwrap();
+/// }
+/// let written = stream.finish().unwrap();
+/// assert_eq!(*written, *big_lehmer::encode(&[3, 1, 0, 2]).unwrap());
+/// ```
+#[derive(Debug)]
+pub struct StreamEncoder<W: Write> {
+    writer: W,
+    encoder: Encoder,
+    cache: Cache,
+    element_count: u32,
+    pushed: u32,
+}
+
+impl<W: Write> StreamEncoder<W> {
+    /// Starts encoding a permutation of `element_count` elements into `writer`
+    ///
+    /// # Errors
+    ///
+    /// Errors with `Error::SequenceToLong` when the internal tree does not fit into memory
+    pub fn new(writer: W, element_count: u32) -> Result<Self, Error> {
+        let mut encoder = Encoder::new();
+        encoder.reset(element_count)?;
+        Ok(Self {
+            writer,
+            encoder,
+            cache: Cache::default(),
+            element_count,
+            pushed: 0,
+        })
+    }
+
+    /// Adds the next number of the permutation
+    ///
+    /// # Errors
+    ///
+    /// Validation errors like `big_lehmer::encode`.  
+    /// `Error::LengthMismatch` when more than `element_count` numbers are pushed.
+    pub fn push(&mut self, number: u32) -> Result<(), Error> {
+        if self.pushed == self.element_count {
+            return Err(Error::LengthMismatch {
+                expected: self.element_count as usize,
+                got: self.element_count as usize + 1,
+            });
+        }
+        visit(&mut self.encoder.validation, self.pushed as usize, number)?;
+        self.pushed += 1;
+
+        // The last number does not contribute to the code
+        if self.pushed < self.element_count {
+            let add = u64::from(self.encoder.tree.insert_and_rank(number));
+            let mul = u64::from(self.element_count - self.pushed);
+            self.cache.push_step(&mut self.encoder.caches, add, mul);
+        }
+        Ok(())
+    }
+
+    /// Computes the code, writes it to the writer and returns the writer
+    ///
+    /// # Errors
+    ///
+    /// `Error::LengthMismatch` when fewer than `element_count` numbers were pushed.  
+    /// `Error::Io` when writing fails.
+    pub fn finish(mut self) -> Result<W, Error> {
+        if self.pushed != self.element_count {
+            return Err(Error::LengthMismatch {
+                expected: self.element_count as usize,
+                got: self.pushed as usize,
+            });
+        }
+        self.encoder.caches.push(self.cache);
+        let code = reduce_caches(&self.encoder.caches, 1, None).add;
+        write_ubig(&code, &mut self.writer)?;
+        Ok(self.writer)
+    }
+}
+
+/// Same as `big_lehmer::encode`, but writes the code to `writer` instead of returning it.
+/// Returns the number of bytes written.  
+/// The bytes are written in chunks straight from the words of the big number,
+/// so after the reduce the code is held in memory once instead of twice (the big number and its `Box<[u8]>` copy).
+///
+/// The process peak does not change: it is reached within the last combine of the reduce,
+/// where the factors and the product are alive at the same time, not by the output copy.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let mut written = Vec::new();
+/// let len = big_lehmer::encode_to_writer(&sequence, &mut written).unwrap();
+/// assert_eq!(len, written.len());
+/// assert_eq!(*written, *big_lehmer::encode(&sequence).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`. `Error::Io` when writing fails.
+pub fn encode_to_writer<W: Write>(numbers: &[u32], writer: &mut W) -> Result<usize, Error> {
+    let code = Encoder::new().encode_to_ubig(numbers)?;
+    write_ubig(&code, writer).map_err(Error::from)
+}
+
+/// Writes the minimal little endian bytes of `number`, same bytes as `UBig::to_le_bytes`
+fn write_ubig<W: Write>(number: &UBig, writer: &mut W) -> std::io::Result<usize> {
+    const CHUNK_WORDS: usize = 8192;
+    const WORD_BYTES: usize = (Word::BITS / 8) as usize;
+    let len = number.bit_len().div_ceil(8);
+    let mut remaining = len;
+    let mut buffer = Vec::with_capacity(CHUNK_WORDS * WORD_BYTES);
+    for words in number.as_words().chunks(CHUNK_WORDS) {
+        buffer.clear();
+        for word in words {
+            buffer.extend_from_slice(&word.to_le_bytes());
+        }
+        // The highest word can have zero bytes above the number
+        buffer.truncate(remaining);
+        writer.write_all(&buffer)?;
+        remaining -= buffer.len();
+    }
+    Ok(len)
+}
+
+/// Same as `big_lehmer::decode`, but writes the elements to `writer` as little endian `u32`s (4 byte per element)
+/// instead of into a `results` slice.  
+/// The elements are written in chunks of 64 KiB as they come out of the tree, the permutation is never held in memory.
+/// `writer` is not flushed.
+///
+/// This saves the 4 byte per element of the results, the rest of the decode is unchanged:
+/// - the factoradic digits of all positions are computed before the first element, 4 byte per element.
+///   The digits of the first positions come out of the last divisions, so they can not be streamed
+/// - the rank tree, about 1.5 bit per element
+/// - the code, and the quotient and remainder of the largest division, a few times the code size
+///   (the code is about 2.3 byte per element at 1_000_000 elements, growing with `log2(element_count)`)
+///
+/// For 1_000_000_000 elements that is still 4 GB of digits next to a 3.6 GB code, instead of an additional 4 GB of results.
+///
+/// Decoding 1_000_000 elements into a file instead of a `Vec` lowered the peak of the whole process from 15.6 MB to 12.3 MB.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[2, 0, 1]).unwrap();
+/// let mut written = Vec::new();
+/// big_lehmer::decode_streaming(&encoded, 3, &mut written).unwrap();
+/// assert_eq!(written, [2, 0, 0, 0, 0, 0, 0, 0, 1, 0, 0, 0]);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`. `Error::Io` when writing fails.  
+/// The code is fully checked before the first write, on a decode error nothing is written.
+pub fn decode_streaming<W: Write>(
+    encoded: &[u8],
+    element_count: u32,
+    writer: &mut W,
+) -> Result<(), Error> {
+    const CHUNK_BYTES: usize = 64 * 1024;
+    let mut buffer =
+        Vec::with_capacity(CHUNK_BYTES.min((element_count as usize).saturating_mul(4)));
+    try_decode_for_each(encoded, element_count, |_, element| {
+        buffer.extend_from_slice(&element.to_le_bytes());
+        if buffer.len() == CHUNK_BYTES {
+            writer.write_all(&buffer)?;
+            buffer.clear();
+        }
+        Ok(())
+    })?;
+    writer.write_all(&buffer).map_err(Error::from)
+}
+
+/// Encodes the numbers of a fallible iterator, e.g. numbers parsed from text,
+/// without collecting them into an intermediate `Result<Vec<u32>, _>`.  
+/// Stops at the first input error. The iterator can not tell its length up front (e.g. after filtering),
+/// so the element count has to be passed separately.
+///
+/// # Examples
+/// ```
+/// let text = "3 1 0 2";
+/// let numbers = text.split(' ').map(str::parse::<u32>);
+/// let encoded = big_lehmer::encode_try_iter(numbers, 4).unwrap();
+/// assert_eq!(encoded, big_lehmer::encode(&[3, 1, 0, 2]).unwrap());
+///
+/// let numbers = "3 x 0 2".split(' ').map(str::parse::<u32>);
+/// assert!(matches!(
+///     big_lehmer::encode_try_iter(numbers, 4),
+///     Err(big_lehmer::Error::Input { index: 1, .. })
+/// ));
+/// ```
+///
+/// # Errors
+///
+/// `Error::Input` with the index and message of the first input error.  
+/// `Error::LengthMismatch` when the iterator does not yield exactly `element_count` numbers.  
+/// Validation errors like `big_lehmer::encode`
+pub fn encode_try_iter<I, E>(numbers: I, element_count: u32) -> Result<Box<[u8]>, Error>
+where
+    I: IntoIterator<Item = Result<u32, E>>,
+    E: std::fmt::Display,
+{
+    let mut stream = StreamEncoder::new(Vec::new(), element_count)?;
+    for (index, number) in numbers.into_iter().enumerate() {
+        let number = number.map_err(|e| Error::Input {
+            index,
+            message: e.to_string(),
+        })?;
+        stream.push(number)?;
+    }
+    Ok(stream.finish()?.into_boxed_slice())
+}
+
+/// Writes many permutations into one stream, read them back with `big_lehmer::StreamReader`.
+///
+/// Every record is the byte length of the record as LEB128 varint,
+/// followed by the self describing code of `big_lehmer::encode_self_describing`.
+/// So every record carries its element count and the permutations can have different lengths.
+///
+/// # Examples
+/// ```
+/// let mut writer = big_lehmer::StreamWriter::new(Vec::new());
+/// writer.write(&[2, 0, 1]).unwrap();
+/// writer.write(&[0, 3, 1, 2, 4]).unwrap();
+/// let bytes = writer.into_inner();
+///
+/// let records: Result<Vec<_>, _> = big_lehmer::StreamReader::new(&bytes[..]).collect();
+/// assert_eq!(records.unwrap(), [vec![2, 0, 1], vec![0, 3, 1, 2, 4]]);
+/// ```
+#[derive(Debug)]
+pub struct StreamWriter<W: Write> {
+    writer: W,
+}
+
+impl<W: Write> StreamWriter<W> {
+    #[must_use]
+    pub fn new(writer: W) -> Self {
+        Self { writer }
+    }
+
+    /// Encodes `numbers` and writes it as the next record
+    ///
+    /// # Errors
+    ///
+    /// Same as `big_lehmer::encode`. `Error::Io` when writing fails.
+    pub fn write(&mut self, numbers: &[u32]) -> Result<(), Error> {
+        let encoded = encode_self_describing(numbers)?;
+        let mut prefix = Vec::with_capacity(10);
+        write_varint(&mut prefix, encoded.as_bytes().len() as u64);
+        self.writer
+            .write_all(&prefix)
+            .and_then(|()| self.writer.write_all(encoded.as_bytes()))
+            .map_err(Error::from)
+    }
+
+    /// Flushes the underlying writer
+    ///
+    /// # Errors
+    ///
+    /// `Error::Io` when flushing fails.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        self.writer.flush().map_err(Error::from)
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+}
+
+/// Reads the records of a `big_lehmer::StreamWriter`, yields one decoded permutation per record.
+///
+/// The iteration ends with `None` when the stream ends between two records.
+/// A stream that ends within a record yields an `Error::Io` of kind `UnexpectedEof`.
+/// After the first error the iteration ends, the position in the stream is unknown.
+#[derive(Debug)]
+pub struct StreamReader<R: Read> {
+    reader: R,
+    failed: bool,
+}
+
+impl<R: Read> StreamReader<R> {
+    #[must_use]
+    pub fn new(reader: R) -> Self {
+        Self {
+            reader,
+            failed: false,
+        }
+    }
+
+    #[must_use]
+    pub fn into_inner(self) -> R {
+        self.reader
+    }
+
+    /// Reads the LEB128 length prefix. `None` when the stream ends before its first byte
+    fn read_len(&mut self) -> Result<Option<u64>, Error> {
+        read_varint_from(&mut self.reader)
+    }
+
+    fn read_record(&mut self) -> Result<Option<Vec<u32>>, Error> {
+        let Some(len) = self.read_len()? else {
+            return Ok(None);
+        };
+        // Reads through take, so a corrupted length can not allocate more than the stream contains
+        let mut bytes = Vec::new();
+        self.reader.by_ref().take(len).read_to_end(&mut bytes)?;
+        if bytes.len() as u64 != len {
+            return Err(std::io::Error::from(std::io::ErrorKind::UnexpectedEof).into());
+        }
+        let encoded = EncodedLehmer::from_bytes(&bytes)?;
+        decode_self_describing(&encoded).map(Some)
+    }
+}
+
+impl<R: Read> Iterator for StreamReader<R> {
+    type Item = Result<Vec<u32>, Error>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed {
+            return None;
+        }
+        let record = self.read_record();
+        self.failed = record.is_err();
+        record.transpose()
+    }
+}
+
+impl<R: Read> std::iter::FusedIterator for StreamReader<R> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::encode;
+
+    #[test]
+    fn test_stream_matches_encode() {
+        for sequence in [
+            vec![],
+            vec![0],
+            vec![7, 2, 0, 6, 5, 1, 4, 3],
+            (0..5000).rev().collect(),
+        ] {
+            let mut stream =
+                StreamEncoder::new(Vec::new(), u32::try_from(sequence.len()).unwrap()).unwrap();
+            for &number in &sequence {
+                stream.push(number).unwrap();
+            }
+            assert_eq!(*stream.finish().unwrap(), *encode(&sequence).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_stream_errors() {
+        let mut stream = StreamEncoder::new(Vec::new(), 2).unwrap();
+        stream.push(1).unwrap();
+        assert!(matches!(
+            stream.push(1),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 1
+            })
+        ));
+        assert!(matches!(
+            stream.finish(),
+            Err(Error::LengthMismatch {
+                expected: 2,
+                got: 1
+            })
+        ));
+
+        let mut stream = StreamEncoder::new(Vec::new(), 1).unwrap();
+        stream.push(0).unwrap();
+        assert!(matches!(stream.push(1), Err(Error::LengthMismatch { .. })));
+    }
+
+    #[test]
+    fn test_stream_records_roundtrip() {
+        let permutations: Vec<Vec<u32>> = vec![
+            vec![],
+            vec![0],
+            crate::tests::shuffled(5000, 852),
+            vec![1, 0],
+            (0..300).rev().collect(),
+        ];
+        let mut writer = StreamWriter::new(Vec::new());
+        for numbers in &permutations {
+            writer.write(numbers).unwrap();
+        }
+        writer.flush().unwrap();
+        let bytes = writer.into_inner();
+
+        let read: Vec<Vec<u32>> = StreamReader::new(&bytes[..]).map(Result::unwrap).collect();
+        assert_eq!(read, permutations);
+        assert!(StreamReader::new(&[][..]).next().is_none());
+
+        // Truncated last record
+        let mut reader = StreamReader::new(&bytes[..bytes.len() - 1]);
+        for numbers in &permutations[..4] {
+            assert_eq!(reader.next().unwrap().unwrap(), *numbers);
+        }
+        assert!(matches!(
+            reader.next(),
+            Some(Err(Error::Io {
+                kind: std::io::ErrorKind::UnexpectedEof,
+                ..
+            }))
+        ));
+        assert!(reader.next().is_none());
+
+        // Truncated within the length prefix
+        let mut prefix = vec![];
+        write_varint(&mut prefix, 300);
+        assert!(matches!(
+            StreamReader::new(&prefix[..1]).next(),
+            Some(Err(Error::Io {
+                kind: std::io::ErrorKind::UnexpectedEof,
+                ..
+            }))
+        ));
+        // Record that is not a self describing code
+        assert!(matches!(
+            StreamReader::new(&[2, 0, 0][..]).next(),
+            Some(Err(Error::InvalidHeader))
+        ));
+    }
+
+    #[test]
+    fn test_encode_to_writer() {
+        for sequence in [
+            vec![],
+            vec![0, 1, 2],
+            vec![1, 0],
+            (0..20).rev().collect(),
+            crate::tests::shuffled(30_000, 858),
+        ] {
+            let mut written = Vec::new();
+            let len = encode_to_writer(&sequence, &mut written).unwrap();
+            assert_eq!(len, written.len());
+            assert_eq!(*written, *encode(&sequence).unwrap());
+        }
+        for number in [
+            UBig::ZERO,
+            UBig::ONE,
+            UBig::from(u64::MAX),
+            UBig::ONE << 1_000_000,
+        ] {
+            let mut written = Vec::new();
+            write_ubig(&number, &mut written).unwrap();
+            assert_eq!(*written, *number.to_le_bytes());
+        }
+    }
+
+    #[test]
+    fn test_decode_streaming() {
+        for sequence in [
+            vec![],
+            vec![0],
+            vec![1, 0],
+            crate::tests::shuffled(20_000, 905),
+        ] {
+            let encoded = encode(&sequence).unwrap();
+            let element_count = u32::try_from(sequence.len()).unwrap();
+            let mut written = Vec::new();
+            decode_streaming(&encoded, element_count, &mut written).unwrap();
+            let expected: Vec<u8> = sequence.iter().flat_map(|n| n.to_le_bytes()).collect();
+            assert_eq!(written, expected);
+        }
+
+        // Decode errors before the first write
+        let mut written = Vec::new();
+        assert!(matches!(
+            decode_streaming(&[6], 3, &mut written),
+            Err(Error::ElementCountMismatch { .. })
+        ));
+        assert!(written.is_empty());
+
+        // Write errors stop the decode
+        let encoded = encode(&(0..20_000).rev().collect::<Vec<u32>>()).unwrap();
+        let mut full = [0u8; 50_000];
+        assert!(matches!(
+            decode_streaming(&encoded, 20_000, &mut &mut full[..]),
+            Err(Error::Io {
+                kind: std::io::ErrorKind::WriteZero,
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn test_encode_try_iter() {
+        let sequence: Vec<u32> = crate::tests::shuffled(3000, 850);
+        let text: Vec<String> = sequence.iter().map(u32::to_string).collect();
+        let parsed = text.iter().map(|number| number.parse::<u32>());
+        assert_eq!(
+            encode_try_iter(parsed, 3000).unwrap(),
+            encode(&sequence).unwrap()
+        );
+
+        // Stops at the first input error, the following ones are not read
+        let mut read = 0;
+        let numbers = ["0", "-1", "x", "2"].iter().map(|number| {
+            read += 1;
+            number.parse::<u32>()
+        });
+        assert!(matches!(
+            encode_try_iter(numbers, 4),
+            Err(Error::Input { index: 1, .. })
+        ));
+        assert_eq!(read, 2);
+
+        let numbers = [Ok::<u32, String>(1), Ok(0)];
+        assert!(matches!(
+            encode_try_iter(numbers, 3),
+            Err(Error::LengthMismatch {
+                expected: 3,
+                got: 2
+            })
+        ));
+        let numbers = [Ok::<u32, String>(1), Ok(1)];
+        assert!(matches!(
+            encode_try_iter(numbers, 2),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 1
+            })
+        ));
+    }
+}
@@ -0,0 +1,37 @@
+//! Test utilities to wire `big_lehmer` into property test suites (proptest, quickcheck, ...).  
+//! Only available with the `testing` feature.
+
+use crate::{decode, encode, error::Error};
+
+/// Encodes `numbers`, decodes the code again and returns whether the roundtrip matches.
+///
+/// # Examples
+/// ```
+/// assert!(big_lehmer::testing::roundtrip(&[7, 2, 0, 6, 5, 1, 4, 3]).unwrap());
+/// assert!(big_lehmer::testing::roundtrip(&[0, 0]).is_err());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode` and `big_lehmer::decode`
+pub fn roundtrip(numbers: &[u32]) -> Result<bool, Error> {
+    let encoded = encode(numbers)?;
+    let mut results = vec![0; numbers.len()];
+    decode(&encoded, &mut results)?;
+    Ok(results == numbers)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    proptest! {
+        #[test]
+        fn test_roundtrip_shuffles(
+            numbers in (0u32..3000).prop_flat_map(|n| Just((0..n).collect::<Vec<u32>>()).prop_shuffle())
+        ) {
+            prop_assert!(roundtrip(&numbers).unwrap());
+        }
+    }
+}
@@ -0,0 +1,177 @@
+use crate::error::Error;
+
+/// Packed bitset of the numbers seen so far, one bit per element instead of one `bool` byte.
+///
+/// A full `encode` is dominated by the big numbers, the saved bits mostly lower the peak of the validation itself.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(crate) struct Visited {
+    bits: Vec<u64>,
+    len: usize,
+}
+
+impl Visited {
+    /// Empty set for the numbers `0..len`
+    pub(crate) fn new(len: usize) -> Self {
+        let mut visited = Self::default();
+        visited.reset(len);
+        visited
+    }
+
+    /// Clears the set in place for the numbers `0..len`, only reallocates when it grows
+    pub(crate) fn reset(&mut self, len: usize) {
+        self.bits.clear();
+        self.bits.resize(len.div_ceil(64), 0);
+        self.len = len;
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Sets the bit of `number`, returns whether it was already set.
+    /// None when `number` is out of range.
+    fn insert(&mut self, number: usize) -> Option<bool> {
+        if number >= self.len {
+            return None;
+        }
+        let word = &mut self.bits[number / 64];
+        let mask = 1 << (number % 64);
+        let visited = *word & mask != 0;
+        *word |= mask;
+        Some(visited)
+    }
+}
+
+#[cfg(feature = "zeroize")]
+impl Drop for Visited {
+    fn drop(&mut self) {
+        zeroize::Zeroize::zeroize(&mut self.bits);
+    }
+}
+
+/// Marks `number` as visited.  
+/// Errors when `number` is out of range or was already visited, `index` is the position of `number` in the input.
+pub(crate) fn visit(validation: &mut Visited, index: usize, number: u32) -> Result<(), Error> {
+    visit_offset(validation, index, number, 0)
+}
+
+/// Same as `visit`, but for permutations of `offset..offset + validation.len()`.  
+/// Marks `number - offset`, the errors report the original `number`.
+pub(crate) fn visit_offset(
+    validation: &mut Visited,
+    index: usize,
+    number: u32,
+    offset: u32,
+) -> Result<(), Error> {
+    let visited = number
+        .checked_sub(offset)
+        .and_then(|number| usize::try_from(number).ok())
+        .and_then(|number| validation.insert(number))
+        .ok_or(Error::ValidationOutOfRange { index, number })?;
+    if visited {
+        return Err(Error::ValidationDuplicateNumber { index, number });
+    }
+    Ok(())
+}
+
+/// `visit_offset` of all `numbers` in order, stops at the first invalid number
+pub(crate) fn visit_all(
+    validation: &mut Visited,
+    numbers: &[u32],
+    offset: u32,
+) -> Result<(), Error> {
+    for (index, &number) in numbers.iter().enumerate() {
+        visit_offset(validation, index, number, offset)?;
+    }
+    Ok(())
+}
+
+/// Shared shortcut for the trivial permutations of 0 and 1 elements:
+/// both have the single rank 0, so their code is empty and there is no big number work.  
+/// `Some` with the validation result of the single element (`offset` as in `visit_offset`) when `numbers` is trivial,
+/// `None` otherwise.
+pub(crate) fn trivial_permutation(numbers: &[u32], offset: u32) -> Option<Result<(), Error>> {
+    match numbers {
+        [] => Some(Ok(())),
+        &[number] => Some(visit_offset(&mut Visited::new(1), 0, number, offset)),
+        _ => None,
+    }
+}
+
+/// Checks that `numbers` is a permutation of `0..numbers.len()`, without encoding it.  
+/// Runs the same checks as `big_lehmer::encode`, so it can be used before committing to the encode work
+/// or to validate the output of `big_lehmer::decode` when consuming untrusted codes.
+///
+/// # Examples
+/// ```
+/// assert!(big_lehmer::is_valid_permutation(&[2, 0, 1]).is_ok());
+/// assert!(big_lehmer::is_valid_permutation(&[0, 0]).is_err());
+/// assert!(big_lehmer::is_valid_permutation(&[1, 3, 2]).is_err());
+/// ```
+///
+/// # Errors
+///
+/// `Error::ValidationDuplicateNumber` or `Error::ValidationOutOfRange` for the first invalid number, with its index.
+pub fn is_valid_permutation(numbers: &[u32]) -> Result<(), Error> {
+    visit_all(&mut Visited::new(numbers.len()), numbers, 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_visited_word_boundaries() {
+        let mut visited = Visited::new(130);
+        for number in [0, 63, 64, 127, 128, 129] {
+            assert_eq!(visited.insert(number), Some(false));
+            assert_eq!(visited.insert(number), Some(true));
+        }
+        assert_eq!(visited.insert(130), None);
+        assert_eq!(visited.insert(62), Some(false));
+
+        // Shrinking clears the tail bits of the last word
+        visited.reset(2);
+        assert_eq!(visited, Visited::new(2));
+        assert_eq!(visited.insert(1), Some(false));
+        assert_eq!(visited.insert(63), None);
+    }
+
+    #[test]
+    fn test_trivial_permutation() {
+        assert!(matches!(trivial_permutation(&[], 0), Some(Ok(()))));
+        assert!(matches!(trivial_permutation(&[0], 0), Some(Ok(()))));
+        assert!(matches!(trivial_permutation(&[7], 7), Some(Ok(()))));
+        assert!(matches!(
+            trivial_permutation(&[1], 0),
+            Some(Err(Error::ValidationOutOfRange {
+                index: 0,
+                number: 1
+            }))
+        ));
+        assert!(trivial_permutation(&[0, 1], 0).is_none());
+    }
+
+    #[test]
+    fn test_valid_permutation_errors() {
+        assert!(is_valid_permutation(&[]).is_ok());
+        let sequence: Vec<u32> = (0..200).rev().collect();
+        assert!(is_valid_permutation(&sequence).is_ok());
+        assert!(matches!(
+            is_valid_permutation(&[64, 1, 64]),
+            Err(Error::ValidationOutOfRange {
+                index: 0,
+                number: 64
+            })
+        ));
+        let mut sequence: Vec<u32> = (0..200).collect();
+        sequence[150] = 64;
+        assert!(matches!(
+            is_valid_permutation(&sequence),
+            Err(Error::ValidationDuplicateNumber {
+                index: 150,
+                number: 64
+            })
+        ));
+    }
+}
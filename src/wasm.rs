@@ -0,0 +1,29 @@
+//! `wasm-bindgen` exports of the core functions.
+//!
+//! Build with `wasm-pack build --target web --no-default-features --features wasm`.
+//! The `wasm` feature always takes the serial path, since rayon does not work out of the box on wasm.
+//! Errors are thrown as JS `Error` carrying the `Display` message of `big_lehmer::Error`.
+
+use wasm_bindgen::prelude::*;
+
+/// Encodes the number sequence into a Lehmer code. See `big_lehmer::encode`
+///
+/// # Errors
+///
+/// Throws when the input number sequence is not valid.
+#[wasm_bindgen]
+pub fn encode(numbers: &[u32]) -> Result<Box<[u8]>, JsError> {
+    crate::encode(numbers).map_err(|e| JsError::new(&e.to_string()))
+}
+
+/// Decodes a Lehmer code of `element_count` elements. See `big_lehmer::decode`
+///
+/// # Errors
+///
+/// Throws when `encoded` is not a valid Lehmer code for `element_count` elements.
+#[wasm_bindgen]
+pub fn decode(encoded: &[u8], element_count: u32) -> Result<Box<[u32]>, JsError> {
+    let mut results = vec![0; element_count as usize].into_boxed_slice();
+    crate::decode(encoded, &mut results).map_err(|e| JsError::new(&e.to_string()))?;
+    Ok(results)
+}
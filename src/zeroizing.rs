@@ -0,0 +1,83 @@
+use zeroize::Zeroizing;
+
+use crate::{decode::DecodeScratch, decode_with_scratch, encode::Encoder, error::Error};
+
+/// Same as `big_lehmer::encode`, for sensitive orderings like cryptographic shuffles.  
+/// Wipes the internal tree, validation and cache buffers when done,
+/// and returns the code in a `zeroize::Zeroizing` buffer that wipes itself on drop.
+///
+/// This is best effort: `dashu` gives no mutable access to the words of a `UBig`,
+/// and its multiplications allocate temporaries that are freed without wiping.
+/// Parts of the rank can therefore remain in freed memory. Only the buffers of this crate are wiped.
+///
+/// # Examples
+/// ```
+/// let sequence = [7, 2, 0, 6, 5, 1, 4, 3];
+/// let encoded = big_lehmer::encode_zeroizing(&sequence).unwrap();
+/// assert_eq!(**encoded, *big_lehmer::encode(&sequence).unwrap());
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::encode`
+pub fn encode_zeroizing(numbers: &[u32]) -> Result<Zeroizing<Vec<u8>>, Error> {
+    // The encoder wipes its buffers on drop
+    let mut encoder = Encoder::new();
+    Ok(Zeroizing::new(encoder.encode(numbers)?.into_vec()))
+}
+
+/// Same as `big_lehmer::decode`, for sensitive orderings like cryptographic shuffles.  
+/// Wipes the internal tree and digit buffers when done,
+/// and returns the permutation in a `zeroize::Zeroizing` buffer that wipes itself on drop.
+///
+/// Best effort, the divisions of `dashu` leave parts of the rank in freed memory, see `big_lehmer::encode_zeroizing`.
+///
+/// # Examples
+/// ```
+/// let encoded = big_lehmer::encode(&[2, 0, 1]).unwrap();
+/// let decoded = big_lehmer::decode_zeroizing(&encoded, 3).unwrap();
+/// assert_eq!(**decoded, [2, 0, 1]);
+/// ```
+///
+/// # Errors
+///
+/// Same as `big_lehmer::decode`
+pub fn decode_zeroizing(encoded: &[u8], element_count: u32) -> Result<Zeroizing<Vec<u32>>, Error> {
+    let len = usize::try_from(element_count).map_err(|_| Error::SequenceToLong {
+        element_count: usize::MAX,
+    })?;
+    let mut results = Zeroizing::new(vec![0; len]);
+    // The scratch wipes its buffers on drop
+    let mut scratch = DecodeScratch::new();
+    decode_with_scratch(encoded, &mut results, &mut scratch)?;
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_zeroizing_roundtrip() {
+        let sequence: Vec<u32> = crate::tests::shuffled(3000, 874);
+        let encoded = encode_zeroizing(&sequence).unwrap();
+        assert_eq!(**encoded, *crate::encode(&sequence).unwrap());
+        let decoded = decode_zeroizing(&encoded, 3000).unwrap();
+        assert_eq!(**decoded, sequence);
+        assert!(decode_zeroizing(&encoded, 10).is_err());
+        assert!(encode_zeroizing(&[1, 1]).is_err());
+    }
+
+    #[test]
+    fn test_scratch_reuse_after_shrink() {
+        // Dropping a scratch with spare capacity must not touch freed memory
+        let mut scratch = DecodeScratch::new();
+        let mut results = vec![0; 1000];
+        let encoded = crate::encode(&(0..1000).rev().collect::<Vec<u32>>()).unwrap();
+        decode_with_scratch(&encoded, &mut results, &mut scratch).unwrap();
+        let mut results = [0; 2];
+        decode_with_scratch(&[1], &mut results, &mut scratch).unwrap();
+        assert_eq!(results, [1, 0]);
+        drop(scratch);
+    }
+}
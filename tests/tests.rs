@@ -1,10 +1,28 @@
-use big_lehmer::{decode, encode, get_encode_size};
+use big_lehmer::{
+    actual_encode_size, all_permutations, code_add, code_bit_length, code_bit_length_of,
+    code_from_base, code_sub, code_to_base, decode, decode_at, decode_at_many, decode_batch,
+    decode_batch_results, decode_cancellable, decode_checked, decode_delta, decode_enumerated,
+    decode_for_each, decode_in, decode_inverse_into, decode_one_based, decode_prefix_fill,
+    decode_range, decode_self_describing, decode_self_describing_to_vec, decode_to_factoradic,
+    decode_to_vec, decode_with_backend, decode_with_config, decode_with_progress,
+    decode_with_scratch, decode_with_strategy, embed, encode, encode_batch, encode_batch_results,
+    encode_cancellable, encode_delta, encode_into_uninit, encode_one_based, encode_range,
+    encode_self_describing, encode_subrange, encode_trimmed, encode_try_iter, encode_unchecked,
+    encode_with_backend, encode_with_progress, estimate_decode_memory, estimate_encode_memory,
+    factorial_bit_length, from_mixed_radix, get_encode_size, get_encode_size_const, identity_code,
+    inversion_count, is_canonical_code, is_identity, is_valid_permutation, max_code, pack, parity,
+    permutation_count, permutation_rank, permutation_unrank, ranking::OrderStatisticTree,
+    reverse_code, to_factoradic, to_mixed_radix, try_decode_any, unpack, validate_codes,
+    verify_batch, verify_encode_size, DecodeConfig, DecodeScratch, DecodeStrategy, EncodedLehmer,
+    Encoder, Error, ErrorKind, LehmerBuilder, ENCODE_SIZE_CONST_MAX,
+};
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use dashu::integer::UBig;
     use dashu::rational::ops::EstimatedLog2;
+    use proptest::strategy::{Just, Strategy};
     use rand::prelude::*;
     use std::time::Instant;
 
@@ -23,6 +41,52 @@ mod tests {
         // assert_eq!(get_encode_size(u32::MAX), 16405328180 + 32);
     }
 
+    #[test]
+    fn test_factorial_bit_length() {
+        assert_eq!(factorial_bit_length(0), 1);
+        assert_eq!(factorial_bit_length(2), 2);
+        for element_count in [3, 20, 21, 1024, 4000, 100_000] {
+            let bytes = usize::try_from(factorial_bit_length(element_count).div_ceil(8)).unwrap();
+            assert!(bytes <= get_encode_size(element_count));
+            assert!(bytes + 2 >= get_encode_size(element_count));
+        }
+    }
+
+    #[test]
+    fn test_verify_encode_size() {
+        for element_count in 0..=4100 {
+            assert!(verify_encode_size(element_count), "{element_count}");
+        }
+        for element_count in [10_000, 100_000] {
+            assert!(verify_encode_size(element_count), "{element_count}");
+        }
+    }
+
+    #[test]
+    #[ignore = "takes minutes in debug builds, run with cargo test --release -- --ignored"]
+    fn test_verify_encode_size_millions() {
+        // Both sides of the 1_000_000 threshold, about 3s per million elements in release
+        for element_count in [999_999, 1_000_000, 1_000_001, 1 << 20, 2_000_000, 4_000_000] {
+            assert!(verify_encode_size(element_count), "{element_count}");
+        }
+    }
+
+    #[test]
+    fn test_encode_size_const() {
+        for element_count in 0..=ENCODE_SIZE_CONST_MAX {
+            assert_eq!(
+                get_encode_size_const(element_count),
+                get_encode_size(element_count),
+                "{element_count}"
+            );
+        }
+        const SIZE: usize = get_encode_size_const(64);
+        let sequence: Vec<u32> = (0..64).rev().collect();
+        let mut buffer = [std::mem::MaybeUninit::uninit(); SIZE];
+        let encoded = encode_into_uninit(&sequence, &mut buffer).unwrap();
+        assert_eq!(*encoded, *encode(&sequence).unwrap());
+    }
+
     #[test]
     #[allow(
         clippy::cast_possible_truncation,
@@ -91,6 +155,15 @@ mod tests {
         let mut roundtrip: Vec<u32> = vec![0; sequence.len()];
         decode(&encoded, &mut roundtrip).unwrap();
         assert_eq!(sequence, *roundtrip);
+
+        let mut inverse = vec![0; sequence.len()];
+        for (position, &element) in (0..).zip(&roundtrip) {
+            inverse[element as usize] = position;
+        }
+        let mut decoded_inverse = vec![0; sequence.len()];
+        decode_inverse_into(&encoded, &mut decoded_inverse).unwrap();
+        assert_eq!(decoded_inverse, inverse);
+        assert!(decode_inverse_into(&encoded, &mut decoded_inverse[..10]).is_err());
     }
 
     #[test]
@@ -150,4 +223,1599 @@ mod tests {
             encoded.len()
         );
     }
+
+    #[test]
+    fn test_code_base_roundtrip() {
+        let sequence: Vec<u32> = (0..128).rev().collect();
+        let encoded = encode(&sequence).unwrap();
+
+        // Base 256 digits are exactly the little endian bytes
+        let digits = code_to_base(&encoded, 256).unwrap();
+        assert!(digits
+            .iter()
+            .zip(encoded.iter())
+            .all(|(&d, &b)| d == u32::from(b)));
+
+        for radix in [2, 3, 10, 1000, u32::MAX] {
+            let digits = code_to_base(&encoded, radix).unwrap();
+            assert!(digits.iter().all(|&d| d < radix));
+            assert_eq!(code_from_base(&digits, radix).unwrap(), encoded);
+        }
+    }
+
+    #[test]
+    fn test_code_base_invalid() {
+        assert!(matches!(
+            code_to_base(&[1], 1),
+            Err(Error::InvalidRadix { radix: 1 })
+        ));
+        assert!(matches!(
+            code_from_base(&[1], 0),
+            Err(Error::InvalidRadix { radix: 0 })
+        ));
+        assert!(matches!(
+            code_from_base(&[1, 10], 10),
+            Err(Error::InvalidDigit { index: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn test_mixed_radix() {
+        let mut sequence: Vec<u32> = (0..300).collect();
+        sequence.shuffle(&mut rand::thread_rng());
+
+        // The factorial radices are the reversed Lehmer digits
+        let factorial: Vec<u32> = (1..=300).collect();
+        let digits = to_mixed_radix(&sequence, &factorial).unwrap();
+        let mut lehmer = to_factoradic(&sequence).unwrap();
+        lehmer.reverse();
+        assert_eq!(digits, lehmer);
+        assert_eq!(
+            from_mixed_radix(&digits, &factorial, 300).unwrap(),
+            sequence
+        );
+
+        // Same rank in other radices, one digit more than needed leaves a zero on top
+        for radix in [2, 7, 256, u32::MAX] {
+            let mut radices = vec![
+                radix;
+                code_to_base(&encode(&sequence).unwrap(), radix)
+                    .unwrap()
+                    .len()
+            ];
+            radices.push(radix);
+            let digits = to_mixed_radix(&sequence, &radices).unwrap();
+            assert_eq!(digits.last(), Some(&0));
+            assert_eq!(from_mixed_radix(&digits, &radices, 300).unwrap(), sequence);
+        }
+
+        // Radix 1 is allowed, its digit is always zero
+        let radices = [1, 3, 1, 2, 1];
+        for rank in 0u8..6 {
+            let mut numbers = [0; 3];
+            decode(&[rank], &mut numbers).unwrap();
+            let digits = to_mixed_radix(&numbers, &radices).unwrap();
+            assert_eq!(digits[0] + digits[2] + digits[4], 0);
+            assert_eq!(u32::from(rank), digits[1] + 3 * digits[3]);
+            assert_eq!(from_mixed_radix(&digits, &radices, 3).unwrap(), numbers);
+        }
+    }
+
+    #[test]
+    fn test_mixed_radix_invalid() {
+        assert!(matches!(
+            to_mixed_radix(&[1, 0], &[2, 0]),
+            Err(Error::InvalidRadix { radix: 0 })
+        ));
+        assert!(matches!(
+            to_mixed_radix(&[2, 1, 0], &[5]),
+            Err(Error::MixedRadixOverflow { digit_count: 1 })
+        ));
+        assert!(to_mixed_radix(&[2, 1, 0], &[6]).is_ok());
+        assert!(matches!(
+            to_mixed_radix(&[1, 1], &[1, 2]),
+            Err(Error::ValidationDuplicateNumber { .. })
+        ));
+        assert!(matches!(
+            from_mixed_radix(&[1, 3], &[2, 3], 3),
+            Err(Error::InvalidDigit {
+                index: 1,
+                digit: 3,
+                radix: 3
+            })
+        ));
+        assert!(matches!(
+            from_mixed_radix(&[1], &[2, 3], 3),
+            Err(Error::LengthMismatch {
+                expected: 2,
+                got: 1
+            })
+        ));
+        // Rank 6 needs 4 elements
+        assert!(matches!(
+            from_mixed_radix(&[6], &[7], 3),
+            Err(Error::ElementCountMismatch { got: 3, .. })
+        ));
+        assert!(from_mixed_radix(&[], &[], 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_error_kind() {
+        fn read_code(path: &str) -> Result<Vec<u8>, Error> {
+            // Composes with ? without manual mapping
+            Ok(std::fs::read(path)?)
+        }
+        let error = read_code("/this/path/does/not/exist").unwrap_err();
+        assert_eq!(error.kind(), ErrorKind::Io);
+        assert!(matches!(
+            error,
+            Error::Io {
+                kind: std::io::ErrorKind::NotFound,
+                ..
+            }
+        ));
+
+        assert_eq!(encode(&[0, 2]).unwrap_err().kind(), ErrorKind::Validation);
+        assert_eq!(
+            code_to_base(&[1], 0).unwrap_err().kind(),
+            ErrorKind::Validation
+        );
+        assert_eq!(
+            decode(&[0xFF; 8], &mut [0; 4]).unwrap_err().kind(),
+            ErrorKind::Decode
+        );
+        assert_eq!(
+            decode_range(&[], u32::MAX, &mut [0; 2]).unwrap_err().kind(),
+            ErrorKind::Size
+        );
+        assert_eq!(
+            EncodedLehmer::from_bytes(&[]).unwrap_err().kind(),
+            ErrorKind::Format
+        );
+
+        // Exactly one predicate holds, the one of the kind
+        let errors = [
+            error,
+            encode(&[0, 2]).unwrap_err(),
+            decode(&[0xFF; 8], &mut [0; 4]).unwrap_err(),
+            decode_range(&[], u32::MAX, &mut [0; 2]).unwrap_err(),
+            EncodedLehmer::from_bytes(&[]).unwrap_err(),
+            Error::Cancelled,
+        ];
+        for error in &errors {
+            let predicates = [
+                (error.is_validation(), ErrorKind::Validation),
+                (error.is_decode(), ErrorKind::Decode),
+                (error.is_size(), ErrorKind::Size),
+                (error.is_io(), ErrorKind::Io),
+                (error.is_format(), ErrorKind::Format),
+                (error.is_cancelled(), ErrorKind::Cancelled),
+            ];
+            let held: Vec<ErrorKind> = predicates
+                .iter()
+                .filter(|(held, _)| *held)
+                .map(|&(_, kind)| kind)
+                .collect();
+            assert_eq!(held, [error.kind()]);
+        }
+    }
+
+    #[test]
+    fn test_error_eq() {
+        assert_eq!(
+            encode(&[0, 0]),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 0
+            })
+        );
+        assert_ne!(
+            encode(&[0, 0]).unwrap_err(),
+            Error::ValidationDuplicateNumber {
+                index: 0,
+                number: 0
+            }
+        );
+        let error = decode(&[6], &mut [0; 3]).unwrap_err();
+        let errors = [error.clone(), error.clone()];
+        assert!(errors.iter().all(|stored| *stored == error));
+
+        // Io errors compare kind and message
+        let io = Error::from(std::io::Error::other("disk"));
+        assert_eq!(io.clone(), io);
+        assert_ne!(io, Error::from(std::io::Error::other("network")));
+    }
+
+    #[test]
+    fn test_decode_with_scratch() {
+        let mut rng = rand::thread_rng();
+        let mut scratch = DecodeScratch::new();
+
+        // Varying lengths make sure the scratch is correctly reset when shrinking and growing
+        for len in [8, 300, 2, 64, 2000, 0, 5] {
+            let mut sequence: Vec<u32> = (0..len).collect();
+            sequence.shuffle(&mut rng);
+            let encoded = encode(&sequence).unwrap();
+            let mut roundtrip: Vec<u32> = vec![0; sequence.len()];
+            decode_with_scratch(&encoded, &mut roundtrip, &mut scratch).unwrap();
+            assert_eq!(sequence, roundtrip);
+        }
+    }
+
+    #[test]
+    fn test_decode_in() {
+        let mut rng = rand::thread_rng();
+        let mut remainders = vec![0; 2000];
+        for len in [8, 300, 2, 2000, 0] {
+            let mut sequence: Vec<u32> = (0..len).collect();
+            sequence.shuffle(&mut rng);
+            let encoded = encode(&sequence).unwrap();
+            let mut tree = OrderStatisticTree::new(len).unwrap();
+            let mut roundtrip: Vec<u32> = vec![0; sequence.len()];
+            // Reusing the tree and the dirty remainders of the previous call
+            for _ in 0..2 {
+                decode_in(&encoded, &mut roundtrip, &mut remainders, &mut tree).unwrap();
+                assert_eq!(sequence, roundtrip);
+            }
+        }
+
+        let encoded = encode(&[2, 0, 1]).unwrap();
+        let mut roundtrip = [0; 3];
+        assert!(matches!(
+            decode_in(
+                &encoded,
+                &mut roundtrip,
+                &mut [0; 2],
+                &mut OrderStatisticTree::new(3).unwrap()
+            ),
+            Err(Error::LengthMismatch {
+                expected: 3,
+                got: 2
+            })
+        ));
+        assert!(matches!(
+            decode_in(
+                &encoded,
+                &mut roundtrip,
+                &mut [0; 3],
+                &mut OrderStatisticTree::new(4).unwrap()
+            ),
+            Err(Error::LengthMismatch {
+                expected: 3,
+                got: 4
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_with_scratch_reuse() {
+        let mut sequence: Vec<u32> = (0..256).collect();
+        let mut rng = rand::thread_rng();
+        let sequences: Vec<Vec<u32>> = (0..2000)
+            .map(|_| {
+                sequence.shuffle(&mut rng);
+                sequence.clone()
+            })
+            .collect();
+
+        let mut scratch = DecodeScratch::new();
+        let mut expected = vec![0; sequence.len()];
+        let mut roundtrip = vec![0; sequence.len()];
+        for sequence in &sequences {
+            let code = encode(sequence).unwrap();
+            decode(&code, &mut expected).unwrap();
+            decode_with_scratch(&code, &mut roundtrip, &mut scratch).unwrap();
+            assert_eq!(roundtrip, *sequence);
+            assert_eq!(roundtrip, expected);
+        }
+    }
+
+    #[test]
+    fn test_encode_batch() {
+        let mut rng = rand::thread_rng();
+        let perms: Vec<Vec<u32>> = (0..500)
+            .map(|i| {
+                let mut sequence: Vec<u32> = (0..(i % 40)).collect();
+                sequence.shuffle(&mut rng);
+                sequence
+            })
+            .collect();
+        let slices: Vec<&[u32]> = perms.iter().map(Vec::as_slice).collect();
+
+        let codes = encode_batch(&slices).unwrap();
+        for (perm, code) in perms.iter().zip(codes.iter()) {
+            assert_eq!(*code, encode(perm).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_encode_batch_error() {
+        let perms: [&[u32]; 4] = [&[0, 1], &[0, 0, 1], &[1, 0], &[5, 0]];
+        assert!(matches!(
+            encode_batch(&perms),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 0
+            })
+        ));
+
+        let results = encode_batch_results(&perms);
+        assert!(results[0].is_ok());
+        assert!(results[1].is_err());
+        assert!(results[2].is_ok());
+        assert!(matches!(
+            results[3],
+            Err(Error::ValidationOutOfRange {
+                index: 0,
+                number: 5
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_batch() {
+        let mut rng = rand::thread_rng();
+        let perms: Vec<Vec<u32>> = (0..500)
+            .map(|_| {
+                let mut sequence: Vec<u32> = (0..40).collect();
+                sequence.shuffle(&mut rng);
+                sequence
+            })
+            .collect();
+        let slices: Vec<&[u32]> = perms.iter().map(Vec::as_slice).collect();
+        let codes = encode_batch(&slices).unwrap();
+        let codes: Vec<&[u8]> = codes.iter().map(|code| &code[..]).collect();
+
+        assert_eq!(decode_batch(&codes, 40).unwrap(), perms);
+
+        // A code of 40 elements is too large for 4 elements
+        let results = decode_batch_results(&codes[..3], 4);
+        assert!(results
+            .iter()
+            .all(|result| matches!(result, Err(Error::ElementCountMismatch { got: 4, .. }))));
+        assert!(decode_batch(&[&[0xFF; 4], &[0]], 3).is_err());
+        assert!(decode_batch(&[], 3).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_validation() {
+        assert!(is_valid_permutation(&[]).is_ok());
+        assert!(is_valid_permutation(&[7, 2, 0, 6, 5, 1, 4, 3]).is_ok());
+
+        // The last element must be validated as well
+        for invalid in [&[0, 0][..], &[1, 1], &[0, 2], &[1, 3, 2], &[0, 1, 1]] {
+            assert!(is_valid_permutation(invalid).is_err());
+            assert!(encode(invalid).is_err());
+        }
+        assert!(matches!(
+            encode(&[0, 0]),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 0
+            })
+        ));
+        assert!(matches!(
+            is_valid_permutation(&[1, 3, 2]),
+            Err(Error::ValidationOutOfRange {
+                index: 1,
+                number: 3
+            })
+        ));
+    }
+
+    #[test]
+    fn test_encode_unchecked() {
+        let mut sequence: Vec<u32> = (0..100_000).collect();
+        let mut rng = rand::thread_rng();
+        sequence.shuffle(&mut rng);
+
+        let encoded = encode(&sequence).unwrap();
+        // Safety: sequence is a shuffle of 0..len
+        let unchecked = unsafe { encode_unchecked(&sequence) };
+        assert_eq!(encoded, unchecked);
+    }
+
+    #[test]
+    fn test_roundtrip_one_based() {
+        let mut sequence: Vec<u32> = (1..=300).collect();
+        sequence.shuffle(&mut rand::thread_rng());
+        let zero_based: Vec<u32> = sequence.iter().map(|n| n - 1).collect();
+
+        let encoded = encode_one_based(&sequence).unwrap();
+        assert_eq!(encoded, encode(&zero_based).unwrap());
+        let mut roundtrip: Vec<u32> = vec![0; sequence.len()];
+        decode_one_based(&encoded, &mut roundtrip).unwrap();
+        assert_eq!(sequence, roundtrip);
+    }
+
+    #[test]
+    fn test_roundtrip_range() {
+        let mut sequence: Vec<u32> = (100..164).collect();
+        sequence.shuffle(&mut rand::thread_rng());
+        let zero_based: Vec<u32> = sequence.iter().map(|n| n - 100).collect();
+
+        let encoded = encode_range(&sequence, 100).unwrap();
+        assert_eq!(encoded, encode(&zero_based).unwrap());
+        let mut roundtrip: Vec<u32> = vec![0; sequence.len()];
+        decode_range(&encoded, 100, &mut roundtrip).unwrap();
+        assert_eq!(sequence, roundtrip);
+
+        // The range may end exactly at u32::MAX
+        let sequence = [u32::MAX, u32::MAX - 1];
+        let encoded = encode_range(&sequence, u32::MAX - 1).unwrap();
+        let mut roundtrip = [0; 2];
+        decode_range(&encoded, u32::MAX - 1, &mut roundtrip).unwrap();
+        assert_eq!(sequence, roundtrip);
+        assert!(matches!(
+            decode_range(&encoded, u32::MAX, &mut roundtrip),
+            Err(Error::SequenceToLong { element_count: 2 })
+        ));
+    }
+
+    #[test]
+    fn test_identity() {
+        for element_count in [0, 1, 2, 100, 5000] {
+            let identity: Vec<u32> = (0..element_count).collect();
+            assert!(is_identity(&identity));
+            let encoded = encode(&identity).unwrap();
+            assert!(encoded.is_empty());
+            assert_eq!(encoded, identity_code(element_count));
+            let mut results = vec![u32::MAX; identity.len()];
+            decode(&identity_code(element_count), &mut results).unwrap();
+            assert_eq!(results, identity);
+            // All zero bytes are the same code
+            decode(&[0; 8], &mut results).unwrap();
+            assert_eq!(results, identity);
+        }
+        assert!(!is_identity(&[0, 2, 1]));
+        assert!(!is_identity(&[1]));
+
+        // Only a prefix of zero digits, the reduce skips it
+        let mut sequence: Vec<u32> = (0..5000).collect();
+        sequence.swap(4000, 4999);
+        let encoded = encode(&sequence).unwrap();
+        let mut results = vec![0; sequence.len()];
+        decode(&encoded, &mut results).unwrap();
+        assert_eq!(results, sequence);
+    }
+
+    #[test]
+    fn test_reverse() {
+        let mut results = [0; 8];
+        decode(&max_code(8), &mut results).unwrap();
+        assert_eq!(results, [7, 6, 5, 4, 3, 2, 1, 0]);
+        for element_count in [0, 1, 2, 3, 100, 1025, 5000] {
+            let reverse: Vec<u32> = (0..element_count).rev().collect();
+            let encoded = encode(&reverse).unwrap();
+            assert_eq!(encoded, max_code(element_count));
+            assert_eq!(encoded, reverse_code(element_count));
+            assert!(encoded.len() <= get_encode_size(element_count));
+            // encode_with_stats and decode_with_progress take the full paths
+            let (full, _) = Encoder::new().encode_with_stats(&reverse).unwrap();
+            assert_eq!(encoded, full);
+            let mut results = vec![0; reverse.len()];
+            decode(&encoded, &mut results).unwrap();
+            assert_eq!(results, reverse);
+            let mut full = vec![0; reverse.len()];
+            decode_with_progress(&encoded, &mut full, |_| {}).unwrap();
+            assert_eq!(results, full);
+
+            // The largest rank of n elements is a different permutation of n + 1 elements: [0, n, n - 1, ..., 1]
+            let mut results = vec![0; reverse.len() + 1];
+            decode(&encoded, &mut results).unwrap();
+            let expected: Vec<u32> = [0].into_iter().chain((1..=element_count).rev()).collect();
+            assert_eq!(results, expected);
+        }
+
+        // Next to the reverse, the low bits check passes but the rank differs
+        let near: Vec<u32> = [4998, 4999].into_iter().chain((0..4998).rev()).collect();
+        let encoded = encode(&near).unwrap();
+        let mut results = vec![0; near.len()];
+        decode(&encoded, &mut results).unwrap();
+        assert_eq!(results, near);
+        assert!(decode(&max_code(5000), &mut [0; 4999]).is_err());
+    }
+
+    #[test]
+    fn test_decode_over_long_slice() {
+        let mut rng = SmallRng::seed_from_u64(878);
+        let mut sequence: Vec<u32> = (0..100).collect();
+        sequence.shuffle(&mut rng);
+        let encoded = encode(&sequence).unwrap();
+
+        // decode reads the same rank in the larger space
+        let mut padded = vec![0; 120];
+        decode(&encoded, &mut padded).unwrap();
+        assert_ne!(padded[..100], sequence);
+        assert!(is_valid_permutation(&padded).is_ok());
+
+        let mut padded = vec![u32::MAX; 120];
+        decode_prefix_fill(&encoded, 100, &mut padded).unwrap();
+        assert_eq!(padded[..100], sequence);
+        assert!(padded[100..].iter().copied().eq(100..120));
+        assert_eq!(*encode(&padded).unwrap(), *embed(&sequence, 120).unwrap());
+
+        let mut exact = vec![0; 100];
+        decode_prefix_fill(&encoded, 100, &mut exact).unwrap();
+        assert_eq!(exact, sequence);
+
+        let mut short = vec![7; 99];
+        assert!(matches!(
+            decode_prefix_fill(&encoded, 100, &mut short),
+            Err(Error::LengthMismatch {
+                expected: 100,
+                got: 99
+            })
+        ));
+        assert!(short.iter().all(|&number| number == 7));
+        assert!(matches!(
+            decode_prefix_fill(&encoded, 50, &mut padded),
+            Err(Error::ElementCountMismatch { got: 50, .. })
+        ));
+    }
+
+    #[test]
+    fn test_pack_unpack() {
+        let mut rng = SmallRng::seed_from_u64(885);
+        let mut perms = vec![];
+        let mut lengths = vec![];
+        for index in 0..50 {
+            let length = if index % 3 == 0 { 16 } else { 8 };
+            let mut sequence: Vec<u32> = (0..length).collect();
+            sequence.shuffle(&mut rng);
+            perms.push(sequence);
+            lengths.push(length);
+        }
+        let refs: Vec<&[u32]> = perms.iter().map(Vec::as_slice).collect();
+        let packed = pack(&refs).unwrap();
+        assert_eq!(unpack(&packed, &lengths).unwrap(), perms);
+
+        // Denser than the padded individual codes
+        let padded: usize = lengths.iter().map(|&length| get_encode_size(length)).sum();
+        assert!(packed.len() < padded);
+
+        // Identities contribute zero, empty and single permutations have radix 1
+        let packed = pack(&[&[0, 1], &[], &[0], &[1, 0]]).unwrap();
+        assert_eq!(*packed, [1]);
+        assert_eq!(
+            unpack(&packed, &[2, 0, 1, 2]).unwrap(),
+            [vec![0, 1], vec![], vec![0], vec![1, 0]]
+        );
+        assert!(pack(&[]).unwrap().is_empty());
+        assert!(unpack(&[], &[]).unwrap().is_empty());
+
+        // Too large for the lengths
+        assert!(matches!(unpack(&[12], &[2, 3]), Err(Error::Decode)));
+        assert!(matches!(
+            pack(&[&[0, 1], &[1, 1]]),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 1
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_for_each() {
+        let mut rng = SmallRng::seed_from_u64(888);
+        let mut sequence: Vec<u32> = (0..3000).collect();
+        sequence.shuffle(&mut rng);
+        let encoded = encode(&sequence).unwrap();
+        let mut next_position = 0;
+        decode_for_each(&encoded, 3000, |position, element| {
+            assert_eq!(position, next_position);
+            assert_eq!(element, sequence[position as usize]);
+            next_position += 1;
+        })
+        .unwrap();
+        assert_eq!(next_position, 3000);
+
+        let mut calls = 0;
+        decode_for_each(&[], 0, |_, _| calls += 1).unwrap();
+        decode_for_each(&[], 1, |_, _| calls += 1).unwrap();
+        assert_eq!(calls, 1);
+
+        // Invalid codes never reach the callback
+        let mut calls = 0;
+        assert!(matches!(
+            decode_for_each(&encoded, 100, |_, _| calls += 1),
+            Err(Error::ElementCountMismatch { got: 100, .. })
+        ));
+        assert!(decode_for_each(&[0xFF; 8], 20, |_, _| calls += 1).is_err());
+        assert_eq!(calls, 0);
+    }
+
+    #[test]
+    fn test_decode_to_vec() {
+        let mut rng = SmallRng::seed_from_u64(884);
+        let mut sequence: Vec<u32> = (0..2000).collect();
+        sequence.shuffle(&mut rng);
+        let encoded = encode(&sequence).unwrap();
+        assert_eq!(decode_to_vec(&encoded, 2000).unwrap(), sequence);
+        assert!(decode_to_vec(&[], 0).unwrap().is_empty());
+        assert!(matches!(
+            decode_to_vec(&encoded, 10),
+            Err(Error::ElementCountMismatch { got: 10, .. })
+        ));
+
+        let stored = encode_self_describing(&sequence).unwrap().into_bytes();
+        assert_eq!(decode_self_describing_to_vec(&stored).unwrap(), sequence);
+        assert!(matches!(
+            decode_self_describing_to_vec(&stored[1..]),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_decode_enumerated() {
+        let mut sequence: Vec<u32> = (0..500).collect();
+        sequence.shuffle(&mut rand::thread_rng());
+        let encoded = encode(&sequence).unwrap();
+        let pairs = decode_enumerated(&encoded, 500).unwrap();
+        assert_eq!(pairs.len(), 500);
+        for (position, element) in pairs {
+            assert_eq!(sequence[position as usize], element);
+        }
+        assert!(decode_enumerated(&[], 0).unwrap().is_empty());
+        assert!(decode_enumerated(&encoded, 10).is_err());
+    }
+
+    #[test]
+    fn test_encode_trimmed() {
+        let mut rng = rand::thread_rng();
+        for len in [0, 1, 2, 3, 7, 100, 1000] {
+            let mut sequence: Vec<u32> = (0..len).collect();
+            sequence.shuffle(&mut rng);
+            let encoded = encode_trimmed(&sequence).unwrap();
+            assert_ne!(encoded.last(), Some(&0));
+            assert_eq!(encoded, encode(&sequence).unwrap());
+            let mut roundtrip = vec![0; sequence.len()];
+            decode(&encoded, &mut roundtrip).unwrap();
+            assert_eq!(sequence, roundtrip);
+        }
+
+        // The element count is not part of the code
+        let sequence = [3, 1, 4, 0, 2];
+        let encoded = encode_trimmed(&sequence).unwrap();
+        assert!(matches!(
+            decode(&encoded, &mut [0; 4]),
+            Err(Error::ElementCountMismatch { got: 4, .. })
+        ));
+        let mut larger = [0; 6];
+        decode(&encoded, &mut larger).unwrap();
+        assert_ne!(larger[..5], sequence);
+    }
+
+    #[test]
+    fn test_range_invalid() {
+        assert!(encode_range(&[100, 99], 99).is_ok());
+        assert!(matches!(
+            encode_range(&[100, 98], 99),
+            Err(Error::ValidationOutOfRange {
+                index: 1,
+                number: 98
+            })
+        ));
+        assert!(matches!(
+            encode_range(&[101, 99], 99),
+            Err(Error::ValidationOutOfRange {
+                index: 0,
+                number: 101
+            })
+        ));
+        assert!(matches!(
+            encode_range(&[99, 99], 99),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 99
+            })
+        ));
+    }
+
+    #[test]
+    fn test_one_based_invalid() {
+        assert!(encode_one_based(&[1, 2, 3]).is_ok());
+        for invalid in [
+            &[0, 1, 2][..],
+            &[1, 2, 4],
+            &[4, 2, 1],
+            &[2, 1, 0],
+            &[1, 1, 2],
+        ] {
+            assert!(encode_one_based(invalid).is_err());
+        }
+        assert!(matches!(
+            encode_one_based(&[1, 0]),
+            // Reports the original number, not the offset one
+            Err(Error::ValidationOutOfRange {
+                index: 1,
+                number: 0
+            })
+        ));
+    }
+
+    #[test]
+    fn test_decode_at() {
+        let mut sequence: Vec<u32> = (0..1500).collect();
+        sequence.shuffle(&mut rand::thread_rng());
+        let encoded = encode(&sequence).unwrap();
+        let element_count = u32::try_from(sequence.len()).unwrap();
+
+        for position in [0, 1, 2, 700, 1498, 1499] {
+            assert_eq!(
+                decode_at(&encoded, element_count, position).unwrap(),
+                sequence[position as usize]
+            );
+        }
+        assert!(matches!(
+            decode_at(&encoded, element_count, element_count),
+            Err(Error::PositionOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_decode_at_many() {
+        let mut sequence: Vec<u32> = (0..1500).collect();
+        sequence.shuffle(&mut rand::thread_rng());
+        let encoded = encode(&sequence).unwrap();
+
+        let positions = [0, 1, 1, 700, 1300, 1499];
+        let mut out = [0; 6];
+        decode_at_many(&encoded, 1500, &positions, &mut out).unwrap();
+        for (&position, &number) in positions.iter().zip(out.iter()) {
+            assert_eq!(number, sequence[position as usize]);
+        }
+        decode_at_many(&encoded, 1500, &[], &mut []).unwrap();
+
+        assert!(matches!(
+            decode_at_many(&encoded, 1500, &[5, 3], &mut [0; 2]),
+            Err(Error::PositionsNotSorted { index: 1 })
+        ));
+        assert!(matches!(
+            decode_at_many(&encoded, 1500, &[5, 1500], &mut [0; 2]),
+            Err(Error::PositionOutOfRange { position: 1500, .. })
+        ));
+        assert!(matches!(
+            decode_at_many(&encoded, 1500, &[5], &mut [0; 2]),
+            Err(Error::LengthMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_roundtrip_self_describing() {
+        let mut rng = rand::thread_rng();
+        for len in [0, 1, 2, 8, 200, 5000] {
+            let mut sequence: Vec<u32> = (0..len).collect();
+            sequence.shuffle(&mut rng);
+            let encoded = encode_self_describing(&sequence).unwrap();
+            assert_eq!(encoded.element_count(), len);
+            assert_eq!(*encoded.code(), *encode(&sequence).unwrap());
+
+            let read = EncodedLehmer::from_bytes(encoded.as_bytes()).unwrap();
+            assert_eq!(read, encoded);
+            assert_eq!(decode_self_describing(&read).unwrap(), sequence);
+        }
+    }
+
+    #[test]
+    fn test_self_describing_invalid_header() {
+        assert!(matches!(
+            EncodedLehmer::from_bytes(&[]),
+            Err(Error::InvalidHeader)
+        ));
+        assert!(matches!(
+            EncodedLehmer::from_bytes(&[0, EncodedLehmer::VERSION, 0]),
+            Err(Error::InvalidHeader)
+        ));
+        assert!(matches!(
+            EncodedLehmer::from_bytes(&[EncodedLehmer::MAGIC, 200, 0]),
+            Err(Error::UnsupportedVersion { version: 200 })
+        ));
+        // Truncated varint
+        assert!(matches!(
+            EncodedLehmer::from_bytes(&[EncodedLehmer::MAGIC, EncodedLehmer::VERSION, 0x80]),
+            Err(Error::InvalidHeader)
+        ));
+    }
+
+    #[test]
+    fn test_roundtrip_delta() {
+        let mut rng = rand::thread_rng();
+        let mut base: Vec<u32> = (0..300).collect();
+        base.shuffle(&mut rng);
+
+        // Small change, small delta
+        let mut target = base.clone();
+        target.swap(298, 299);
+        let delta = encode_delta(&base, &target).unwrap();
+        assert!(delta.len() <= 2);
+        assert_eq!(decode_delta(&base, &delta).unwrap(), target);
+
+        // Both signs
+        let mut target = base.clone();
+        target.shuffle(&mut rng);
+        for (from, to) in [(&base, &target), (&target, &base)] {
+            let delta = encode_delta(from, to).unwrap();
+            assert_eq!(decode_delta(from, &delta).unwrap(), *to);
+        }
+
+        let delta = encode_delta(&base, &base).unwrap();
+        assert_eq!(decode_delta(&base, &delta).unwrap(), base);
+    }
+
+    #[test]
+    fn test_progress() {
+        use std::sync::Mutex;
+        let mut rng = SmallRng::seed_from_u64(901);
+        let mut sequence: Vec<u32> = (0..50_000).collect();
+        sequence.shuffle(&mut rng);
+        let reports = Mutex::new(vec![]);
+        let record = |fraction: f32| reports.lock().unwrap().push(fraction);
+
+        let encoded = encode_with_progress(&sequence, record).unwrap();
+        assert_eq!(encoded, encode(&sequence).unwrap());
+        let encode_reports = std::mem::take(&mut *reports.lock().unwrap());
+        let mut results = vec![0; 50_000];
+        decode_with_progress(&encoded, &mut results, record).unwrap();
+        assert_eq!(results, sequence);
+        let decode_reports = std::mem::take(&mut *reports.lock().unwrap());
+
+        for reports in [encode_reports, decode_reports] {
+            // Throttled to whole percents, every percent at most once
+            assert!(reports.len() > 2 && reports.len() <= 101, "{reports:?}");
+            assert_eq!(reports[0], 0.0);
+            assert_eq!(*reports.last().unwrap(), 1.0);
+            let mut sorted = reports.clone();
+            sorted.sort_by(f32::total_cmp);
+            sorted.dedup();
+            assert_eq!(sorted.len(), reports.len());
+        }
+
+        // No completion on errors
+        let mut invalid = sequence.clone();
+        invalid[100] = invalid[0];
+        assert!(encode_with_progress(&invalid, record).is_err());
+        assert!(decode_with_progress(&encoded, &mut results[..40_000], record).is_err());
+        assert!(reports
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|&fraction| fraction < 1.0));
+
+        for trivial in [&[][..], &[0]] {
+            let reports = Mutex::new(vec![]);
+            let record = |fraction: f32| reports.lock().unwrap().push(fraction);
+            assert!(encode_with_progress(trivial, record).unwrap().is_empty());
+            decode_with_progress(&[], &mut vec![0; trivial.len()], record).unwrap();
+            assert_eq!(*reports.lock().unwrap(), [0.0, 1.0, 0.0, 1.0]);
+        }
+    }
+
+    #[test]
+    fn test_cancellation() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+        use std::time::Duration;
+        let mut rng = SmallRng::seed_from_u64(902);
+        let mut sequence: Vec<u32> = (0..40_000).collect();
+        sequence.shuffle(&mut rng);
+        let expected = encode(&sequence).unwrap();
+        let mut results = vec![0; sequence.len()];
+
+        // Set before the call
+        let cancel = AtomicBool::new(true);
+        assert!(matches!(
+            encode_cancellable(&sequence, &cancel),
+            Err(Error::Cancelled)
+        ));
+        assert!(matches!(
+            decode_cancellable(&expected, &mut results, &cancel),
+            Err(Error::Cancelled)
+        ));
+        assert_eq!(Error::Cancelled.kind(), ErrorKind::Cancelled);
+
+        // Set from another thread while the work runs, the call either finishes or stops cleanly
+        let cancel_after = |delay: u64, work: &mut dyn FnMut(&AtomicBool) -> Result<(), Error>| {
+            let cancel = AtomicBool::new(false);
+            std::thread::scope(|scope| {
+                scope.spawn(|| {
+                    std::thread::sleep(Duration::from_millis(delay));
+                    cancel.store(true, Ordering::Relaxed);
+                });
+                work(&cancel)
+            })
+        };
+        for delay in [0, 1, 10, 50] {
+            match cancel_after(delay, &mut |cancel| {
+                assert_eq!(encode_cancellable(&sequence, cancel)?, expected);
+                Ok(())
+            }) {
+                Ok(()) | Err(Error::Cancelled) => {}
+                Err(error) => panic!("{error}"),
+            }
+            match cancel_after(delay, &mut |cancel| {
+                decode_cancellable(&expected, &mut results, cancel)
+            }) {
+                Ok(()) => assert_eq!(results, sequence),
+                Err(Error::Cancelled) => {}
+                Err(error) => panic!("{error}"),
+            }
+        }
+
+        // Never set
+        let cancel = AtomicBool::new(false);
+        assert_eq!(encode_cancellable(&sequence, &cancel).unwrap(), expected);
+        decode_cancellable(&expected, &mut results, &cancel).unwrap();
+        assert_eq!(results, sequence);
+    }
+
+    #[test]
+    fn test_split_policy() {
+        let mut rng = SmallRng::seed_from_u64(899);
+        let mut sequence: Vec<u32> = (0..1500).collect();
+        sequence.shuffle(&mut rng);
+        let encoded = encode(&sequence).unwrap();
+        let policies: [fn(usize) -> usize; 6] = [
+            DecodeConfig::default_split_policy,
+            |bit_length| bit_length / 2,
+            |bit_length| bit_length - 1,
+            |_| 0,
+            |_| 64,
+            |bit_length| bit_length * 2,
+        ];
+        for policy in policies {
+            for strategy in [
+                DecodeStrategy::Serial,
+                DecodeStrategy::Recursive,
+                DecodeStrategy::FlatParallel,
+                DecodeStrategy::Hybrid,
+            ] {
+                let config = DecodeConfig::new()
+                    .with_strategy(strategy)
+                    .with_split_policy(policy);
+                let mut results = vec![0; 1500];
+                decode_with_config(&encoded, &mut results, config).unwrap();
+                assert_eq!(results, sequence);
+                // Too large codes are still detected
+                assert!(matches!(
+                    decode_with_config(&encoded, &mut results[..1000], config),
+                    Err(Error::ElementCountMismatch { got: 1000, .. })
+                ));
+            }
+        }
+        assert_eq!(DecodeConfig::new(), DecodeConfig::default());
+        assert_ne!(
+            DecodeConfig::new(),
+            DecodeConfig::new().with_split_policy(|bit_length| bit_length / 2)
+        );
+    }
+
+    #[test]
+    fn test_verify_batch() {
+        let mut rng = SmallRng::seed_from_u64(898);
+        let mut perms: Vec<Vec<u32>> = (0..3000)
+            .map(|_| {
+                let mut perm: Vec<u32> = (0..rng.gen_range(0..600)).collect();
+                perm.shuffle(&mut rng);
+                perm
+            })
+            .collect();
+        perms.push((0..20_000).rev().collect());
+        let slices: Vec<&[u32]> = perms.iter().map(Vec::as_slice).collect();
+        verify_batch(&slices).unwrap();
+        assert!(verify_batch(&[]).is_ok());
+
+        // The first failure by position is reported
+        perms[1700] = vec![0, 2];
+        perms[2500] = vec![1, 1];
+        let slices: Vec<&[u32]> = perms.iter().map(Vec::as_slice).collect();
+        let error = verify_batch(&slices).unwrap_err();
+        assert!(matches!(
+            &error,
+            Error::RoundtripFailed { index: 1700, message } if message.contains("number 2")
+        ));
+        assert_eq!(error.kind(), ErrorKind::Decode);
+    }
+
+    #[test]
+    fn test_validate_codes() {
+        let mut rng = SmallRng::seed_from_u64(919);
+        let mut codes: Vec<(Vec<u8>, u32)> = (0..1000)
+            .map(|_| {
+                let mut perm: Vec<u32> = (0..rng.gen_range(0..300)).collect();
+                perm.shuffle(&mut rng);
+                let element_count = u32::try_from(perm.len()).unwrap();
+                (encode(&perm).unwrap().into_vec(), element_count)
+            })
+            .collect();
+        validate_codes(codes.clone()).unwrap();
+        assert!(validate_codes([]).is_ok());
+
+        // The rank 100! is out of range for 100 elements, the first bad code by position is reported
+        codes[700] = (max_code(101).into_vec(), 100);
+        codes[900] = (vec![1, 0], 5);
+        assert!(matches!(
+            validate_codes(codes.clone()),
+            Err((700, Error::Decode))
+        ));
+        codes[30] = (vec![1, 0], 5);
+        assert!(matches!(validate_codes(codes), Err((30, Error::Decode))));
+
+        // The stream stops at the chunk with the bad code
+        let mut taken = 0;
+        let endless = std::iter::repeat_with(|| {
+            taken += 1;
+            if taken == 10 {
+                (vec![6], 3)
+            } else {
+                (vec![5], 3)
+            }
+        });
+        assert_eq!(validate_codes(endless), Err((9, Error::Decode)));
+        assert!(taken < 1000);
+
+        // Huge element counts only cost the length of the code
+        let reverse: Vec<u32> = (0..100).rev().collect();
+        let huge = vec![
+            (vec![], u32::MAX),
+            (encode(&reverse).unwrap().into_vec(), u32::MAX),
+            (vec![0xFF; 1000], u32::MAX - 1),
+            (vec![1, 0], u32::MAX),
+        ];
+        assert_eq!(validate_codes(huge), Err((3, Error::Decode)));
+        // The bound is tight enough to still reject codes just above element_count!
+        for element_count in [2, 20, 100, 1000] {
+            let too_large = (max_code(element_count + 1).into_vec(), element_count);
+            assert_eq!(validate_codes([too_large]), Err((0, Error::Decode)));
+            let largest = (max_code(element_count).into_vec(), element_count);
+            assert_eq!(validate_codes([largest]), Ok(()));
+        }
+    }
+
+    #[test]
+    fn test_encode_subrange() {
+        let mut rng = SmallRng::seed_from_u64(897);
+        let mut arena: Vec<u32> = (0..1000).collect();
+        arena[200..500].shuffle(&mut rng);
+        let window = &arena[200..500];
+        // The plain encode sees numbers up to the parent length
+        assert!(matches!(
+            encode(window),
+            Err(Error::ValidationOutOfRange { index, number }) if window[index] == number && number >= 300
+        ));
+        let (encoded, start) = encode_subrange(window).unwrap();
+        assert_eq!(start, 200);
+        let rebased: Vec<u32> = window.iter().map(|&n| n - 200).collect();
+        assert_eq!(encoded, encode(&rebased).unwrap());
+        let mut roundtrip = vec![0; 300];
+        decode_range(&encoded, start, &mut roundtrip).unwrap();
+        assert_eq!(roundtrip, window);
+
+        // Gaps report the original number
+        assert!(matches!(
+            encode_subrange(&[10, 12, 11, 14]),
+            Err(Error::ValidationOutOfRange {
+                index: 3,
+                number: 14
+            })
+        ));
+        assert!(matches!(
+            encode_subrange(&[4, 4]),
+            Err(Error::ValidationDuplicateNumber {
+                index: 1,
+                number: 4
+            })
+        ));
+        assert_eq!(encode_subrange(&[]).unwrap().1, 0);
+        assert_eq!(encode_subrange(&[u32::MAX]).unwrap().1, u32::MAX);
+    }
+
+    #[test]
+    fn test_delta_crossing_zero() {
+        // Ranks of 4 elements, rank 3 is [0, 2, 3, 1]
+        let at = |rank: u8| permutation_unrank(&UBig::from(rank), 4).unwrap();
+        let delta = encode_delta(&at(3), &at(0)).unwrap();
+        assert_eq!(*delta, [1, 3]);
+        // The same delta from other bases lands on rank 0, moves down, or would go past rank 0
+        assert_eq!(decode_delta(&at(3), &delta).unwrap(), at(0));
+        assert_eq!(decode_delta(&at(23), &delta).unwrap(), at(20));
+        for base in [0, 1, 2] {
+            assert!(matches!(
+                decode_delta(&at(base), &delta),
+                Err(Error::Decode)
+            ));
+        }
+        // Negative zero is accepted
+        assert_eq!(decode_delta(&at(5), &[1]).unwrap(), at(5));
+
+        // The rank navigation shares the signed path, also without wrapping
+        let code = encode(&at(3)).unwrap();
+        assert_eq!(&*code_sub(&code, 3, 4).unwrap(), &*encode(&at(0)).unwrap());
+        assert!(matches!(
+            code_sub(&code, 4, 4),
+            Err(Error::RankOutOfRange { element_count: 4 })
+        ));
+        assert!(matches!(
+            code_add(&code, 21, 4),
+            Err(Error::RankOutOfRange { element_count: 4 })
+        ));
+    }
+
+    #[test]
+    fn test_delta_invalid() {
+        assert!(matches!(
+            encode_delta(&[0, 1], &[0, 1, 2]),
+            Err(Error::LengthMismatch {
+                expected: 2,
+                got: 3
+            })
+        ));
+        // Negative delta below rank 0
+        assert!(matches!(
+            decode_delta(&[0, 1, 2], &[1, 1]),
+            Err(Error::Decode)
+        ));
+        assert!(matches!(decode_delta(&[0, 1], &[]), Err(Error::Decode)));
+        assert!(matches!(decode_delta(&[0, 1], &[7]), Err(Error::Decode)));
+    }
+
+    #[test]
+    fn test_decode_strategies() {
+        let mut sequence: Vec<u32> = (0..20_000).collect();
+        sequence.shuffle(&mut rand::thread_rng());
+        let encoded = encode(&sequence).unwrap();
+
+        for strategy in [
+            DecodeStrategy::Serial,
+            DecodeStrategy::Recursive,
+            DecodeStrategy::FlatParallel,
+            DecodeStrategy::Hybrid,
+        ] {
+            let mut roundtrip: Vec<u32> = vec![0; sequence.len()];
+            decode_with_strategy(&encoded, &mut roundtrip, strategy).unwrap();
+            assert_eq!(sequence, roundtrip);
+        }
+    }
+
+    #[test]
+    fn test_code_bit_length() {
+        let mut sequence: Vec<u32> = (0..1000).collect();
+        assert_eq!(code_bit_length(&sequence).unwrap(), 0);
+        assert_eq!(code_bit_length_of(&encode(&sequence).unwrap()), 0);
+
+        sequence.shuffle(&mut rand::thread_rng());
+        let encoded = encode(&sequence).unwrap();
+        let bits = code_bit_length(&sequence).unwrap();
+        assert_eq!(code_bit_length_of(&encoded), bits);
+        assert_eq!(encoded.len(), usize::try_from(bits.div_ceil(8)).unwrap());
+        assert!(bits <= get_encode_size(1000) as u64 * 8);
+    }
+
+    #[test]
+    fn test_actual_encode_size() {
+        let mut rng = SmallRng::seed_from_u64(912);
+        for len in [0, 1, 2, 3, 20, 21, 1000] {
+            let mut sequence: Vec<u32> = (0..len).collect();
+            assert_eq!(actual_encode_size(&sequence).unwrap(), 0);
+            sequence.shuffle(&mut rng);
+            let size = actual_encode_size(&sequence).unwrap();
+            assert_eq!(size, encode_trimmed(&sequence).unwrap().len());
+            assert!(size <= get_encode_size(len));
+            // The last permutation has the largest code
+            let reverse: Vec<u32> = (0..len).rev().collect();
+            assert!(actual_encode_size(&reverse).unwrap() >= size);
+        }
+        assert_eq!(actual_encode_size(&[0, 1, 3, 2]).unwrap(), 1);
+        assert!(matches!(
+            actual_encode_size(&[0, 0]),
+            Err(Error::ValidationDuplicateNumber { .. })
+        ));
+    }
+
+    #[test]
+    fn test_estimate_memory() {
+        assert!(estimate_encode_memory(0) < 1024);
+        assert!(estimate_decode_memory(0) < 1024);
+
+        let mut previous = (0, 0);
+        for element_count in [10, 1000, 100_000, 10_000_000] {
+            let estimate = (
+                estimate_encode_memory(element_count),
+                estimate_decode_memory(element_count),
+            );
+            // Tree and validation need 2.5 bit per element, the decode digits 4 byte per element
+            assert!(estimate.0 >= element_count as usize * 5 / 16);
+            assert!(estimate.1 >= element_count as usize * 4);
+            assert!(estimate.0 > previous.0 && estimate.1 > previous.1);
+            previous = estimate;
+        }
+    }
+
+    #[test]
+    fn test_decode_element_count_mismatch() {
+        let sequence: Vec<u32> = (0..10).rev().collect();
+        let encoded = encode(&sequence).unwrap();
+
+        let mut results = [0; 9];
+        assert!(matches!(
+            decode(&encoded, &mut results),
+            Err(Error::ElementCountMismatch {
+                expected: 10,
+                got: 9
+            })
+        ));
+        let mut results = [0; 3];
+        assert!(matches!(
+            decode(&encoded, &mut results),
+            Err(Error::ElementCountMismatch { expected, got: 3 }) if (5..=10).contains(&expected)
+        ));
+        assert!(matches!(
+            decode(&encoded, &mut []),
+            Err(Error::ElementCountMismatch { got: 0, .. })
+        ));
+        assert!(matches!(
+            decode_at(&encoded, 4, 0),
+            Err(Error::ElementCountMismatch { got: 4, .. })
+        ));
+
+        // Much larger codes must not panic in the divisions
+        for strategy in [
+            DecodeStrategy::Serial,
+            DecodeStrategy::Recursive,
+            DecodeStrategy::FlatParallel,
+            DecodeStrategy::Hybrid,
+        ] {
+            let mut results = vec![0; 100];
+            assert!(matches!(
+                decode_with_strategy(&[0xAB; 4000], &mut results, strategy),
+                Err(Error::ElementCountMismatch { got: 100, .. })
+            ));
+        }
+
+        // Codes of fewer elements are valid codes of more elements
+        let mut results = [0; 11];
+        decode(&encoded, &mut results).unwrap();
+    }
+
+    #[test]
+    fn test_decode_checked() {
+        let mut rng = SmallRng::seed_from_u64(859);
+        let mut sequence: Vec<u32> = (0..3000).collect();
+        sequence.shuffle(&mut rng);
+        let encoded = encode(&sequence).unwrap();
+        let mut results = vec![0; sequence.len()];
+        decode_checked(&encoded, &mut results).unwrap();
+        assert_eq!(results, sequence);
+        assert!(decode_checked(&[], &mut []).is_ok());
+        assert!(decode_checked(&[1], &mut []).is_err());
+
+        // Overlong: additional high bytes make the code too large
+        let mut overlong = encoded.to_vec();
+        overlong.extend_from_slice(&[0x12, 0x34]);
+        assert!(matches!(
+            decode_checked(&overlong, &mut results),
+            Err(Error::Decode)
+        ));
+        // High zero bytes do not change the code
+        let mut padded = encoded.to_vec();
+        padded.extend_from_slice(&[0, 0]);
+        decode_checked(&padded, &mut results).unwrap();
+        assert_eq!(results, sequence);
+
+        // Truncated: still a valid, smaller code
+        decode_checked(&encoded[..encoded.len() - 10], &mut results).unwrap();
+        assert!(is_valid_permutation(&results).is_ok());
+        assert_ne!(results, sequence);
+
+        // Corrupted: either a valid permutation or an error, the same result as decode
+        for _ in 0..20 {
+            let mut corrupted = encoded.to_vec();
+            let index = rng.gen_range(0..corrupted.len());
+            corrupted[index] ^= 0xFF;
+            let mut expected = vec![0; sequence.len()];
+            let decoded = decode(&corrupted, &mut expected);
+            match decode_checked(&corrupted, &mut results) {
+                Ok(()) => {
+                    assert!(decoded.is_ok());
+                    assert_eq!(results, expected);
+                    assert!(is_valid_permutation(&results).is_ok());
+                }
+                Err(_) => assert!(decoded.is_err()),
+            }
+        }
+        assert!(decode_checked(&[0xFF; 4000], &mut results).is_err());
+    }
+
+    #[test]
+    fn test_code_strategy_invariant() {
+        // Pinned code, computed independently as the Lehmer rank
+        let pinned = [
+            28, 34, 33, 25, 32, 38, 7, 31, 22, 37, 14, 10, 16, 6, 18, 13, 24, 4, 26, 1, 29, 2, 39,
+            23, 21, 5, 0, 15, 12, 8, 9, 35, 36, 27, 17, 3, 30, 20, 19, 11,
+        ];
+        assert_eq!(
+            *encode(&pinned).unwrap(),
+            [
+                231, 167, 143, 98, 46, 49, 185, 107, 188, 137, 175, 170, 202, 50, 63, 119, 71, 248,
+                36, 103
+            ]
+        );
+
+        let mut rng = SmallRng::seed_from_u64(883);
+        let mut sequence: Vec<u32> = (0..30_000).collect();
+        sequence.shuffle(&mut rng);
+        let expected = encode(&sequence).unwrap();
+
+        for min_chunk in [1, 3, 4096] {
+            for single_thread in [false, true] {
+                for (validate_first, reduce_ordered) in
+                    [(false, false), (true, false), (false, true), (true, true)]
+                {
+                    let mut encoder = Encoder::new()
+                        .with_min_chunk(min_chunk)
+                        .with_single_thread(single_thread)
+                        .with_validate_first(validate_first)
+                        .with_reduce_ordered(reduce_ordered);
+                    assert_eq!(encoder.encode(&sequence).unwrap(), expected);
+                }
+            }
+        }
+        assert_eq!(encode_with_backend::<UBig>(&sequence).unwrap(), expected);
+        assert_eq!(
+            encode_try_iter(sequence.iter().map(|&n| Ok::<_, Error>(n)), 30_000).unwrap(),
+            expected
+        );
+        for parallel in [false, true] {
+            let builder = LehmerBuilder::new().with_parallel(parallel);
+            assert_eq!(*builder.encode(&sequence).unwrap(), *expected);
+        }
+
+        let mut results = vec![0; sequence.len()];
+        for strategy in [
+            DecodeStrategy::Serial,
+            DecodeStrategy::Recursive,
+            DecodeStrategy::FlatParallel,
+            DecodeStrategy::Hybrid,
+        ] {
+            for parallel_cutoff in [1, 1000, usize::MAX] {
+                for single_thread in [false, true] {
+                    let config = DecodeConfig::new()
+                        .with_strategy(strategy)
+                        .with_parallel_cutoff(parallel_cutoff)
+                        .with_single_thread(single_thread);
+                    results.fill(0);
+                    decode_with_config(&expected, &mut results, config).unwrap();
+                    assert_eq!(results, sequence, "{config:?}");
+                }
+            }
+            for naive_threshold in [0, usize::MAX] {
+                let config = DecodeConfig::new()
+                    .with_strategy(strategy)
+                    .with_naive_threshold(naive_threshold);
+                results.fill(0);
+                decode_with_config(&expected, &mut results, config).unwrap();
+                assert_eq!(results, sequence, "{config:?}");
+            }
+        }
+        results.fill(0);
+        decode_with_backend::<UBig>(&expected, &mut results).unwrap();
+        assert_eq!(results, sequence);
+    }
+
+    #[test]
+    fn test_is_canonical_code() {
+        let sequence: Vec<u32> = (0..300).rev().collect();
+        let largest = encode(&sequence).unwrap();
+        assert!(is_canonical_code(&largest, 300));
+        let mut results = vec![0; 300];
+
+        // One more than the largest rank is 300!, out of range
+        let too_large = (UBig::from_le_bytes(&largest) + UBig::ONE).to_le_bytes();
+        assert!(!is_canonical_code(&too_large, 300));
+        assert!(matches!(
+            decode_checked(&too_large, &mut results),
+            Err(Error::Decode)
+        ));
+        assert!(is_canonical_code(&too_large, 301));
+        let mut bigger = vec![0; 301];
+        decode_checked(&too_large, &mut bigger).unwrap();
+
+        // High zero bytes are not canonical, but decode_checked accepts them
+        let mut padded = largest.to_vec();
+        padded.push(0);
+        assert!(!is_canonical_code(&padded, 300));
+        decode_checked(&padded, &mut results).unwrap();
+        assert_eq!(results, sequence);
+
+        assert!(is_canonical_code(&[], 0));
+        assert!(!is_canonical_code(&[1], 0));
+        assert!(matches!(decode_checked(&[1], &mut []), Err(Error::Decode)));
+        assert!(!is_canonical_code(&[0xFF; 8], 20));
+
+        // Short codes of huge permutations skip the factorial
+        assert!(is_canonical_code(&[0xFF; 8], u32::MAX));
+        assert!(!is_canonical_code(&[0xFF, 0], u32::MAX));
+    }
+
+    #[test]
+    fn test_try_decode_any_fuzz() {
+        let mut rng = SmallRng::seed_from_u64(879);
+        let mut accepted = 0;
+        for _ in 0..5000 {
+            let element_count = rng.gen_range(0..200);
+            let byte_count = rng.gen_range(0..=get_encode_size(element_count) + 2);
+            let mut encoded = vec![0; byte_count];
+            rng.fill_bytes(&mut encoded);
+            match try_decode_any(&encoded, element_count) {
+                Ok(results) => {
+                    // Canonical codes roundtrip to the same bytes
+                    assert_eq!(results.len(), element_count as usize);
+                    assert_eq!(*encode(&results).unwrap(), *encoded);
+                    accepted += 1;
+                }
+                Err(error) => assert!(matches!(error, Error::Decode)),
+            }
+        }
+        // Random bytes below the full size are mostly valid codes
+        assert!(accepted > 1000);
+
+        assert!(try_decode_any(&[], 0).unwrap().is_empty());
+        assert!(matches!(try_decode_any(&[1], 0), Err(Error::Decode)));
+        assert!(matches!(try_decode_any(&[0], 5), Err(Error::Decode)));
+        let sequence: Vec<u32> = (0..100).rev().collect();
+        let encoded = encode(&sequence).unwrap();
+        assert_eq!(try_decode_any(&encoded, 100).unwrap(), sequence);
+        assert!(matches!(try_decode_any(&encoded, 99), Err(Error::Decode)));
+    }
+
+    #[test]
+    fn test_decode_parallel_cutoff() {
+        let mut rng = SmallRng::seed_from_u64(827);
+        let mut sequence: Vec<u32> = (0..20_000).collect();
+        sequence.shuffle(&mut rng);
+        let encoded = encode(&sequence).unwrap();
+        for cutoff in [0, 10, 1000, usize::MAX] {
+            let config = DecodeConfig::new().with_parallel_cutoff(cutoff);
+            assert_eq!(config.parallel_cutoff(), cutoff);
+            let mut roundtrip = vec![0; sequence.len()];
+            decode_with_config(&encoded, &mut roundtrip, config).unwrap();
+            assert_eq!(sequence, roundtrip);
+        }
+    }
+
+    #[test]
+    fn test_single_thread() {
+        let mut rng = SmallRng::seed_from_u64(849);
+        let mut sequence: Vec<u32> = (0..20_000).collect();
+        sequence.shuffle(&mut rng);
+
+        let encoded = encode(&sequence).unwrap();
+        let serial = Encoder::new()
+            .with_single_thread(true)
+            .encode(&sequence)
+            .unwrap();
+        assert_eq!(serial, encoded);
+
+        for strategy in [
+            DecodeStrategy::Serial,
+            DecodeStrategy::Recursive,
+            DecodeStrategy::FlatParallel,
+            DecodeStrategy::Hybrid,
+        ] {
+            let config = DecodeConfig::new()
+                .with_strategy(strategy)
+                .with_single_thread(true);
+            assert!(config.single_thread());
+            let mut roundtrip = vec![0; sequence.len()];
+            decode_with_config(&encoded, &mut roundtrip, config).unwrap();
+            assert_eq!(sequence, roundtrip);
+        }
+    }
+
+    #[test]
+    fn test_encode_into_uninit() {
+        let mut rng = SmallRng::seed_from_u64(832);
+        let mut sequence: Vec<u32> = (0..5000).collect();
+        sequence.shuffle(&mut rng);
+        let size = get_encode_size(5000);
+
+        let mut out = vec![std::mem::MaybeUninit::uninit(); size];
+        let encoded = encode_into_uninit(&sequence, &mut out).unwrap();
+        assert_eq!(*encoded, *encode(&sequence).unwrap());
+
+        let mut out = vec![std::mem::MaybeUninit::uninit(); size - 1];
+        assert!(matches!(
+            encode_into_uninit(&sequence, &mut out),
+            Err(Error::OutVectorSize {
+                element_count: 5000,
+                ..
+            })
+        ));
+        assert!(encode_into_uninit(&[], &mut []).unwrap().is_empty());
+    }
+
+    proptest::proptest! {
+        #[test]
+        fn test_proptest_roundtrip(
+            sequence in (0u32..3000).prop_flat_map(|n| Just((0..n).collect::<Vec<u32>>()).prop_shuffle())
+        ) {
+            let encoded = encode(&sequence).unwrap();
+            let mut roundtrip = vec![0; sequence.len()];
+            decode(&encoded, &mut roundtrip).unwrap();
+            proptest::prop_assert_eq!(sequence, roundtrip);
+        }
+    }
+
+    #[test]
+    fn test_zero_and_singleton() {
+        // Both trivial permutations have the single rank 0 and the empty code
+        for element_count in [0u32, 1] {
+            let identity: Vec<u32> = (0..element_count).collect();
+            let len = identity.len();
+            assert!(encode(&identity).unwrap().is_empty());
+            assert!(encode_trimmed(&identity).unwrap().is_empty());
+            assert!(encode_range(&vec![9; len], 9).unwrap().is_empty());
+            assert!(encode_one_based(&vec![1; len]).unwrap().is_empty());
+            assert!(encode_with_backend::<UBig>(&identity).unwrap().is_empty());
+            assert!(pack(&[&identity, &identity]).unwrap().is_empty());
+            assert_eq!(get_encode_size(element_count), 0);
+            assert!(verify_encode_size(element_count));
+            assert_eq!(code_bit_length(&identity).unwrap(), 0);
+            assert_eq!(permutation_count(element_count), UBig::ONE);
+            assert_eq!(permutation_rank(&identity).unwrap(), UBig::ZERO);
+            assert_eq!(inversion_count(&identity).unwrap(), 0);
+            assert!(!parity(&identity).unwrap());
+            assert!(is_identity(&identity));
+            assert_eq!(to_factoradic(&identity).unwrap(), identity);
+            assert_eq!(decode_to_factoradic(&[], element_count).unwrap(), identity);
+
+            // The empty code and zero decode to the identity, everything else is too large
+            for code in [&[][..], &[0]] {
+                assert_eq!(decode_to_vec(code, element_count).unwrap(), identity);
+                let mut results = vec![7; len];
+                decode(code, &mut results).unwrap();
+                assert_eq!(results, identity);
+                let mut results = vec![7; len];
+                decode_with_backend::<UBig>(code, &mut results).unwrap();
+                assert_eq!(results, identity);
+            }
+            let mut results = vec![7; len];
+            decode_checked(&[], &mut results).unwrap();
+            assert_eq!(results, identity);
+            assert_eq!(try_decode_any(&[], element_count).unwrap(), identity);
+            assert_eq!(
+                permutation_unrank(&UBig::ZERO, element_count).unwrap(),
+                identity
+            );
+            assert_eq!(all_permutations(element_count).count(), 1);
+            assert_eq!(all_permutations(element_count).next().unwrap(), identity);
+            assert!(is_canonical_code(&[], element_count));
+            assert!(!is_canonical_code(&[1], element_count));
+            assert!(matches!(
+                decode_to_vec(&[1], element_count),
+                Err(Error::ElementCountMismatch { expected: 2, got }) if got == len
+            ));
+            assert!(matches!(
+                decode_checked(&[1], &mut vec![0; len]),
+                Err(Error::Decode)
+            ));
+        }
+
+        // The single element is still validated
+        assert!(matches!(
+            encode(&[1]),
+            Err(Error::ValidationOutOfRange {
+                index: 0,
+                number: 1
+            })
+        ));
+        assert!(matches!(
+            encode_range(&[3], 4),
+            Err(Error::ValidationOutOfRange {
+                index: 0,
+                number: 3
+            })
+        ));
+        assert!(encode_one_based(&[0]).is_err());
+        assert!(inversion_count(&[1]).is_err());
+    }
 }